@@ -60,4 +60,100 @@ pub enum ErrorCode {
     NotInTopK,
     #[msg("Invalid reward amount")]
     InvalidRewardAmount,
+    #[msg("Price moved past the acceptable price")]
+    SlippageExceeded,
+    #[msg("Invalid margin amount")]
+    InvalidMarginAmount,
+    #[msg("Margin mode is fixed by the league")]
+    MarginModeNotSelectable,
+    #[msg("Participant has open positions")]
+    OpenPositionsExist,
+    #[msg("Invalid position mode")]
+    InvalidPositionMode,
+    #[msg("Position is still open")]
+    PositionStillOpen,
+    #[msg("Invalid max open positions")]
+    InvalidMaxOpenPositions,
+    #[msg("Invalid order legs")]
+    InvalidOrderLegs,
+    #[msg("Invalid DCA schedule")]
+    InvalidDcaSchedule,
+    #[msg("DCA slice is not due yet")]
+    DcaSliceNotDue,
+    #[msg("DCA schedule is completed")]
+    DcaScheduleCompleted,
+    #[msg("DCA schedule is expired")]
+    DcaScheduleExpired,
+    #[msg("Invalid borrow rate")]
+    InvalidBorrowRate,
+    #[msg("Spot leagues only allow unleveraged long positions")]
+    InvalidSpotOrder,
+    #[msg("Oracle price is stale")]
+    StalePrice,
+    #[msg("Invalid max price age")]
+    InvalidPriceAge,
+    #[msg("Invalid basket weights")]
+    InvalidBasketWeights,
+    #[msg("Invalid binary instrument")]
+    InvalidBinaryInstrument,
+    #[msg("Staking on this binary instrument has closed")]
+    BinaryStakingClosed,
+    #[msg("Binary instrument has not expired yet")]
+    BinaryInstrumentNotExpired,
+    #[msg("Binary instrument is already settled")]
+    BinaryInstrumentSettled,
+    #[msg("Binary instrument is not settled yet")]
+    BinaryInstrumentNotSettled,
+    #[msg("Binary stake is already settled")]
+    BinaryStakeSettled,
+    #[msg("Invalid corporate action")]
+    InvalidCorporateAction,
+    #[msg("Position has corporate actions pending")]
+    CorporateActionPending,
+    #[msg("Invalid halt configuration")]
+    InvalidHaltConfig,
+    #[msg("Market is halted, only reducing trades are allowed")]
+    MarketHalted,
+    #[msg("Invalid fallback feed")]
+    InvalidFallbackFeed,
+    #[msg("Primary and fallback feeds diverge")]
+    PriceDivergence,
+    #[msg("Invalid price decimals")]
+    InvalidPriceDecimals,
+    #[msg("Creator market listing is not configured")]
+    ListingBondNotConfigured,
+    #[msg("Invalid listing bond account")]
+    InvalidBondAccount,
+    #[msg("Listing bond was already returned or slashed")]
+    ListingBondReleased,
+    #[msg("Market is restricted to its creator's leagues")]
+    MarketRestricted,
+    #[msg("Market is not active")]
+    MarketNotActive,
+    #[msg("Signer lacks the required role")]
+    Unauthorized,
+    #[msg("Role is already granted to this key")]
+    RoleAlreadyGranted,
+    #[msg("Role is not granted to this key")]
+    RoleNotGranted,
+    #[msg("Too many role grants")]
+    TooManyRoleGrants,
+    #[msg("Invalid fee bps")]
+    InvalidFeeBps,
+    #[msg("No admin transfer is pending for this signer")]
+    NotPendingAdmin,
+    #[msg("Treasury must be a system account other than the default key")]
+    InvalidTreasury,
+    #[msg("Permission program can't be the default key")]
+    InvalidPermissionProgram,
+    #[msg("Protocol is paused")]
+    ProtocolPaused,
+    #[msg("League is paused")]
+    LeaguePaused,
+    #[msg("Withdrawals are paused")]
+    WithdrawalsPaused,
+    #[msg("Account is not a program account of the type being migrated")]
+    AccountNotMigratable,
+    #[msg("Account belongs to a different league")]
+    LeagueMismatch,
 }
@@ -0,0 +1,69 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct BasketRebalanced {
+    pub basket: Pubkey,
+    pub symbol: [u8; 16],
+    pub feeds: Vec<Pubkey>,
+    pub weights: Vec<i64>,
+    pub rebalance_count: u32,
+    pub rebalanced_at: i64,
+}
+
+#[event]
+pub struct MarketHalted {
+    pub market: Pubkey,
+    pub reference_price: i64,
+    pub price: i64,
+    pub halted_until: i64,
+}
+
+#[event]
+pub struct AdminTransferProposed {
+    pub admin: Pubkey,
+    pub old_pending_admin: Option<Pubkey>,
+    pub new_pending_admin: Option<Pubkey>,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub old_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct FeeBpsUpdated {
+    pub old_fee_bps: u16,
+    pub new_fee_bps: u16,
+}
+
+#[event]
+pub struct TreasuryUpdated {
+    pub old_treasury: Pubkey,
+    pub new_treasury: Pubkey,
+}
+
+#[event]
+pub struct PermissionProgramUpdated {
+    pub old_permission_program: Pubkey,
+    pub new_permission_program: Pubkey,
+}
+
+#[event]
+pub struct ProtocolPauseUpdated {
+    pub paused: bool,
+    pub withdrawals_paused: bool,
+}
+
+#[event]
+pub struct LeaguePauseUpdated {
+    pub league: Pubkey,
+    pub paused: bool,
+    pub withdrawals_paused: bool,
+}
+
+#[event]
+pub struct AccountMigrated {
+    pub account: Pubkey,
+    pub version: u8,
+}
@@ -0,0 +1,206 @@
+use anchor_lang::prelude::*;
+
+use crate::events::BasketRebalanced;
+use crate::instructions::market::{init_market, require_price_decimals};
+use crate::state::{Basket, GlobalState, Market, Role};
+use crate::utils::{get_basket_price, read_basket_prices, rescale_basket_weights, PRICE_DECIMALS};
+
+/// Basket market: the basket account takes the place of the oracle feed,
+/// so the market is derived from it like any other market.
+#[derive(Accounts)]
+#[instruction(symbol: [u8; 16])]
+pub struct ListBasketMarket<'info> {
+    #[account(mut)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = Basket::SPACE,
+        seeds = [b"basket", symbol.as_ref()],
+        bump
+    )]
+    pub basket: Account<'info, Basket>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = Market::SPACE,
+        seeds = [b"market", basket.key().as_ref()],
+        bump
+    )]
+    pub market: Account<'info, Market>,
+    /// CHECK: Base currency account - validated by the token program
+    pub base_currency: AccountInfo<'info>,
+    /// CHECK: FX feed, baskets quoted in another currency than the league's only
+    pub fx_feed: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        constraint = global_state.has_role(&admin.key(), Role::MarketManager)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn list_basket_market(
+    ctx: Context<ListBasketMarket>,
+    symbol: [u8; 16],
+    decimals: u8,
+    max_leverage: u8,
+    borrow_rate_bps: u16,
+    max_price_age: i64,
+    fx_price_decimals: u8,
+    feeds: Vec<Pubkey>,
+    weights: Vec<i64>,
+    price_decimals: Vec<u8>,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+
+    let basket = &mut ctx.accounts.basket;
+    basket.symbol = symbol;
+    basket.rebalance_count = 0;
+    basket.bump = ctx.bumps.basket;
+    basket.version = Basket::VERSION;
+    set_weights(basket, feeds, weights, price_decimals, now)?;
+
+    let bump = ctx.bumps.market;
+    init_market(
+        &mut ctx.accounts.market,
+        symbol,
+        basket.key(),
+        None,
+        ctx.accounts
+            .fx_feed
+            .as_ref()
+            .map(|fx_feed| (fx_feed.key(), fx_price_decimals)),
+        ctx.accounts.base_currency.key(),
+        decimals,
+        ctx.accounts.admin.key(),
+        max_leverage,
+        borrow_rate_bps,
+        max_price_age,
+        // The level is computed at 1e6 from constituents read at their own decimals
+        PRICE_DECIMALS,
+        bump,
+    )?;
+
+    msg!("Basket market listed: {:?}", symbol);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetBasketWeights<'info> {
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [b"basket", basket.symbol.as_ref()],
+        bump = basket.bump
+    )]
+    pub basket: Account<'info, Basket>,
+
+    #[account(
+        seeds = [b"market", basket.key().as_ref()],
+        bump = market.bump
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        constraint = global_state.has_role(&admin.key(), Role::MarketManager)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+    // remaining accounts = [current constituent feeds..., new constituent feeds...], each in basket order
+}
+
+/// Rebalance the basket. `weights` give the new proportions and are rescaled so the index
+/// level is unchanged at current prices, open positions don't jump on a rebalance.
+pub fn set_basket_weights(
+    ctx: Context<SetBasketWeights>,
+    feeds: Vec<Pubkey>,
+    weights: Vec<i64>,
+    price_decimals: Vec<u8>,
+) -> Result<()> {
+    let basket = &mut ctx.accounts.basket;
+    let config = ctx.accounts.market.price_config();
+    let now = Clock::get()?.unix_timestamp;
+    require_valid_weights(&feeds, &weights, &price_decimals)?;
+
+    require!(
+        ctx.remaining_accounts.len() == basket.feeds.len() + feeds.len(),
+        crate::errors::ErrorCode::OracleMismatch
+    );
+    let (current_feeds, new_feeds) = ctx.remaining_accounts.split_at(basket.feeds.len());
+    let level = get_basket_price(basket, current_feeds, &config, now)?;
+    let prices = read_basket_prices(
+        &feeds,
+        &price_decimals,
+        new_feeds,
+        config.max_price_age,
+        now,
+    )?;
+    let weights = rescale_basket_weights(&weights, &prices, level)?;
+
+    basket.rebalance_count = basket
+        .rebalance_count
+        .checked_add(1)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    set_weights(basket, feeds, weights, price_decimals, now)?;
+
+    msg!(
+        "Basket {:?} rebalanced, {} constituents",
+        basket.symbol,
+        basket.feeds.len()
+    );
+    Ok(())
+}
+
+fn set_weights(
+    basket: &mut Account<Basket>,
+    feeds: Vec<Pubkey>,
+    weights: Vec<i64>,
+    price_decimals: Vec<u8>,
+    now: i64,
+) -> Result<()> {
+    require_valid_weights(&feeds, &weights, &price_decimals)?;
+
+    basket.feeds = feeds;
+    basket.weights = weights;
+    basket.price_decimals = price_decimals;
+    basket.last_rebalanced_at = now;
+
+    emit!(BasketRebalanced {
+        basket: basket.key(),
+        symbol: basket.symbol,
+        feeds: basket.feeds.clone(),
+        weights: basket.weights.clone(),
+        rebalance_count: basket.rebalance_count,
+        rebalanced_at: now,
+    });
+    Ok(())
+}
+
+fn require_valid_weights(feeds: &[Pubkey], weights: &[i64], price_decimals: &[u8]) -> Result<()> {
+    require!(
+        !feeds.is_empty()
+            && feeds.len() <= Basket::MAX_FEEDS
+            && feeds.len() == weights.len()
+            && feeds.len() == price_decimals.len(),
+        crate::errors::ErrorCode::InvalidBasketWeights
+    );
+    for decimals in price_decimals {
+        require_price_decimals(*decimals)?;
+    }
+    require!(
+        weights.iter().all(|weight| *weight > 0),
+        crate::errors::ErrorCode::InvalidBasketWeights
+    );
+    for (i, feed) in feeds.iter().enumerate() {
+        require!(
+            !feeds[..i].contains(feed),
+            crate::errors::ErrorCode::InvalidBasketWeights
+        );
+    }
+    Ok(())
+}
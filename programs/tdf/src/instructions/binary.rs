@@ -0,0 +1,521 @@
+use anchor_lang::prelude::*;
+
+use crate::instructions::pause::require_not_paused;
+use crate::math::Rounding;
+use crate::state::{
+    BinaryInstrument, BinaryOutcome, BinarySide, BinaryStake, GlobalState, League, LeagueStatus,
+    Market, Participant, PriceConfig,
+};
+use crate::utils::{calculate_pro_rata, get_market_price};
+
+/// How old the settlement price may be at expiry when the market has no max price age
+pub const SETTLEMENT_MAX_PRICE_AGE: i64 = 60;
+
+/// How long after expiry the instrument waits for a settlement. Past it with no price
+/// standing at expiry, it settles void and every stake is refunded.
+pub const SETTLEMENT_GRACE_PERIOD: i64 = 300;
+
+/// Staking closes this long before expiry, when the outcome is close to known
+pub const STAKE_CUTOFF: i64 = 300;
+
+#[derive(Accounts)]
+#[instruction(strike_price: i64, expiry_ts: i64)]
+pub struct CreateBinaryInstrument<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    #[account(has_one = creator)]
+    pub league: Account<'info, League>,
+    pub market: Account<'info, Market>,
+
+    #[account(
+        init,
+        payer = creator,
+        space = BinaryInstrument::SPACE,
+        seeds = [b"binary", league.key().as_ref(), market.key().as_ref(), strike_price.to_le_bytes().as_ref(), expiry_ts.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub instrument: Account<'info, BinaryInstrument>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// League creator offers a binary contract on one of the league's markets
+pub fn create_binary_instrument(
+    ctx: Context<CreateBinaryInstrument>,
+    strike_price: i64,
+    expiry_ts: i64,
+) -> Result<()> {
+    let league = &ctx.accounts.league;
+    let market = &ctx.accounts.market;
+    let now = Clock::get()?.unix_timestamp;
+
+    require!(
+        league.markets.contains(&market.key()),
+        crate::errors::ErrorCode::InvalidBinaryInstrument
+    );
+    require!(
+        strike_price > 0
+            && expiry_ts > now.saturating_add(STAKE_CUTOFF)
+            && expiry_ts <= league.end_ts,
+        crate::errors::ErrorCode::InvalidBinaryInstrument
+    );
+
+    let instrument = &mut ctx.accounts.instrument;
+    instrument.league = league.key();
+    instrument.market = market.key();
+    instrument.oracle_feed = market.oracle_feed;
+    instrument.price_config = market.price_config();
+    instrument.strike_price = strike_price;
+    instrument.expiry_ts = expiry_ts;
+    instrument.total_up_stake = 0;
+    instrument.total_down_stake = 0;
+    instrument.outcome = BinaryOutcome::Pending;
+    instrument.settlement_price = 0;
+    instrument.settled_at = 0;
+    instrument.created_at = now;
+    instrument.bump = ctx.bumps.instrument;
+    instrument.version = BinaryInstrument::VERSION;
+
+    msg!(
+        "Binary instrument created: strike {} expiring at {}",
+        strike_price,
+        expiry_ts
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct StakeBinary<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"participant", league.key().as_ref(), user.key().as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+
+    #[account(
+        mut,
+        has_one = league,
+        seeds = [b"binary", league.key().as_ref(), instrument.market.as_ref(), instrument.strike_price.to_le_bytes().as_ref(), instrument.expiry_ts.to_le_bytes().as_ref()],
+        bump = instrument.bump
+    )]
+    pub instrument: Account<'info, BinaryInstrument>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = BinaryStake::SPACE,
+        seeds = [b"binary_stake", instrument.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub stake: Account<'info, BinaryStake>,
+
+    pub league: Account<'info, League>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Lock `amount` of the available balance on one side until the instrument settles
+pub fn stake_binary(ctx: Context<StakeBinary>, side: BinarySide, amount: i64) -> Result<()> {
+    let league = &ctx.accounts.league;
+    let participant = &mut ctx.accounts.participant;
+    let instrument = &mut ctx.accounts.instrument;
+    let stake = &mut ctx.accounts.stake;
+    let now = Clock::get()?.unix_timestamp;
+
+    require!(
+        league.status == LeagueStatus::Active,
+        crate::errors::ErrorCode::LeagueNotActive
+    );
+    require_not_paused(&ctx.accounts.global_state, league)?;
+    require_staking_open(instrument, now)?;
+    require!(amount > 0, crate::errors::ErrorCode::InvalidMarginAmount);
+    require!(
+        participant.available_balance() >= amount,
+        crate::errors::ErrorCode::InsufficientMargin
+    );
+
+    stake.instrument = instrument.key();
+    stake.user = ctx.accounts.user.key();
+    stake.bump = ctx.bumps.stake;
+    stake.version = BinaryStake::VERSION;
+
+    match side {
+        BinarySide::Up => {
+            stake.up_stake = stake
+                .up_stake
+                .checked_add(amount)
+                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+            instrument.total_up_stake = instrument
+                .total_up_stake
+                .checked_add(amount)
+                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+        }
+        BinarySide::Down => {
+            stake.down_stake = stake
+                .down_stake
+                .checked_add(amount)
+                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+            instrument.total_down_stake = instrument
+                .total_down_stake
+                .checked_add(amount)
+                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+        }
+    }
+
+    // The stake leaves the balance but still counts toward equity until settlement
+    participant.virtual_balance = participant
+        .virtual_balance
+        .checked_sub(amount)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.binary_stake = participant
+        .binary_stake
+        .checked_add(amount)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    msg!("Binary stake placed: {}", amount);
+    Ok(())
+}
+
+fn require_staking_open(instrument: &BinaryInstrument, now: i64) -> Result<()> {
+    require!(
+        now < instrument.expiry_ts.saturating_sub(STAKE_CUTOFF),
+        crate::errors::ErrorCode::BinaryStakingClosed
+    );
+    Ok(())
+}
+
+/// Crank, settles the instrument against the market price once it has expired
+#[derive(Accounts)]
+pub struct SettleBinaryInstrument<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub instrument: Account<'info, BinaryInstrument>,
+
+    /// CHECK: oracle feed from the instrument
+    #[account(address = instrument.oracle_feed)]
+    pub oracle_feed: AccountInfo<'info>,
+    /// CHECK: denominator feed from the instrument, pair markets only
+    pub denominator_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fallback feed from the instrument, markets with one only
+    pub fallback_feed: Option<UncheckedAccount<'info>>,
+    // remaining accounts = basket constituent feeds, basket markets only
+}
+
+pub fn settle_binary_instrument(ctx: Context<SettleBinaryInstrument>) -> Result<()> {
+    let instrument = &mut ctx.accounts.instrument;
+    let now = Clock::get()?.unix_timestamp;
+
+    require!(
+        instrument.outcome == BinaryOutcome::Pending,
+        crate::errors::ErrorCode::BinaryInstrumentSettled
+    );
+    let price = read_settlement_price(
+        instrument,
+        &ctx.accounts.oracle_feed,
+        ctx.accounts.denominator_feed.as_deref(),
+        ctx.accounts.fallback_feed.as_deref(),
+        ctx.remaining_accounts,
+        now,
+    )?;
+    settle_at(instrument, price, now);
+
+    msg!("Binary instrument settled at price {}", price);
+    Ok(())
+}
+
+/// The price standing at expiry: every leg last updated at or before `expiry_ts` and no
+/// more than the max price age before it. Feeds keep only their latest price, so an update
+/// after expiry can't be told apart from a later, more favorable one and is never used.
+/// Once a feed has moved past expiry unsettled, the instrument voids (0) after the grace
+/// period.
+fn read_settlement_price(
+    instrument: &BinaryInstrument,
+    oracle_feed: &AccountInfo,
+    denominator_feed: Option<&AccountInfo>,
+    fallback_feed: Option<&AccountInfo>,
+    constituent_feeds: &[AccountInfo],
+    now: i64,
+) -> Result<i64> {
+    require!(
+        now >= instrument.expiry_ts,
+        crate::errors::ErrorCode::BinaryInstrumentNotExpired
+    );
+
+    // Read as of expiry, a leg updated after it counts as stale
+    let config = PriceConfig {
+        max_price_age: match instrument.price_config.max_price_age {
+            0 => SETTLEMENT_MAX_PRICE_AGE,
+            max_price_age => max_price_age,
+        },
+        ..instrument.price_config.clone()
+    };
+    let window_end = instrument
+        .expiry_ts
+        .checked_add(SETTLEMENT_GRACE_PERIOD)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    match get_market_price(
+        oracle_feed,
+        denominator_feed,
+        fallback_feed,
+        constituent_feeds,
+        &config,
+        instrument.expiry_ts,
+    ) {
+        Ok(price) => Ok(price),
+        Err(err) if now > window_end && err == crate::errors::ErrorCode::StalePrice.into() => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+/// Settle against `price`, a price of 0 voids the instrument
+fn settle_at(instrument: &mut BinaryInstrument, price: i64, now: i64) {
+    let (outcome, winning_stake) = if price == 0 {
+        (BinaryOutcome::Void, 0)
+    } else if price > instrument.strike_price {
+        (BinaryOutcome::Up, instrument.total_up_stake)
+    } else if price < instrument.strike_price {
+        (BinaryOutcome::Down, instrument.total_down_stake)
+    } else {
+        (BinaryOutcome::Void, 0)
+    };
+    // With nobody on the winning side there is no one to pay, so everyone is refunded
+    instrument.outcome = if winning_stake == 0 {
+        BinaryOutcome::Void
+    } else {
+        outcome
+    };
+    instrument.settlement_price = price;
+    instrument.settled_at = now;
+}
+
+/// Crank, pays a participant's stake out of a settled instrument
+#[derive(Accounts)]
+pub struct SettleBinaryStake<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"participant", instrument.league.as_ref(), stake.user.as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+
+    pub instrument: Account<'info, BinaryInstrument>,
+
+    #[account(
+        mut,
+        has_one = instrument,
+        seeds = [b"binary_stake", instrument.key().as_ref(), stake.user.as_ref()],
+        bump = stake.bump
+    )]
+    pub stake: Account<'info, BinaryStake>,
+}
+
+pub fn settle_binary_stake(ctx: Context<SettleBinaryStake>) -> Result<()> {
+    let instrument = &ctx.accounts.instrument;
+    let participant = &mut ctx.accounts.participant;
+    let stake = &mut ctx.accounts.stake;
+
+    require!(
+        instrument.outcome != BinaryOutcome::Pending,
+        crate::errors::ErrorCode::BinaryInstrumentNotSettled
+    );
+    require!(!stake.settled, crate::errors::ErrorCode::BinaryStakeSettled);
+
+    let total_stake = stake
+        .up_stake
+        .checked_add(stake.down_stake)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    let pool = instrument
+        .total_up_stake
+        .checked_add(instrument.total_down_stake)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    // Winners split the pool pro rata, rounded down so payouts never exceed it
+    let payout = match instrument.outcome {
+        BinaryOutcome::Up => calculate_pro_rata(
+            pool,
+            stake.up_stake,
+            instrument.total_up_stake,
+            Rounding::Down,
+        )?,
+        BinaryOutcome::Down => calculate_pro_rata(
+            pool,
+            stake.down_stake,
+            instrument.total_down_stake,
+            Rounding::Down,
+        )?,
+        BinaryOutcome::Void | BinaryOutcome::Pending => total_stake,
+    };
+
+    participant.binary_stake = participant
+        .binary_stake
+        .checked_sub(total_stake)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.virtual_balance = participant
+        .virtual_balance
+        .checked_add(payout)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    stake.payout = payout;
+    stake.settled = true;
+
+    msg!(
+        "Binary stake settled: staked {}, paid out {}",
+        total_stake,
+        payout
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ErrorCode;
+    use crate::utils::PriceFeed;
+
+    const EXPIRY: i64 = 1_000_000;
+    const STRIKE: i64 = 100_000_000;
+
+    fn feed(price: i64, last_updated: i64) -> AccountInfo<'static> {
+        let mut data = vec![0u8; 8];
+        PriceFeed {
+            price,
+            last_updated,
+            authority: Pubkey::default(),
+            bump: 0,
+        }
+        .serialize(&mut data)
+        .unwrap();
+        AccountInfo::new(
+            Box::leak(Box::new(Pubkey::new_unique())),
+            false,
+            false,
+            Box::leak(Box::new(0)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(Pubkey::default())),
+            false,
+            0,
+        )
+    }
+
+    fn instrument(max_price_age: i64) -> BinaryInstrument {
+        let mut instrument = BinaryInstrument::try_deserialize_unchecked(
+            &mut &vec![0u8; BinaryInstrument::SPACE][..],
+        )
+        .unwrap();
+        instrument.strike_price = STRIKE;
+        instrument.expiry_ts = EXPIRY;
+        instrument.total_up_stake = 100;
+        instrument.total_down_stake = 100;
+        instrument.price_config.max_price_age = max_price_age;
+        instrument.price_config.price_decimals = 6;
+        instrument
+    }
+
+    fn settle(
+        instrument: &mut BinaryInstrument,
+        price: i64,
+        last_updated: i64,
+        now: i64,
+    ) -> Result<()> {
+        let price =
+            read_settlement_price(instrument, &feed(price, last_updated), None, None, &[], now)?;
+        settle_at(instrument, price, now);
+        Ok(())
+    }
+
+    #[test]
+    fn settles_up_above_the_strike() {
+        let mut instrument = instrument(0);
+        settle(&mut instrument, 110_000_000, EXPIRY, EXPIRY).unwrap();
+        assert!(instrument.outcome == BinaryOutcome::Up);
+        assert_eq!(instrument.settlement_price, 110_000_000);
+    }
+
+    #[test]
+    fn settles_down_below_the_strike() {
+        let mut instrument = instrument(30);
+        settle(&mut instrument, 90_000_000, EXPIRY - 30, EXPIRY + 10).unwrap();
+        assert!(instrument.outcome == BinaryOutcome::Down);
+    }
+
+    #[test]
+    fn settles_void_at_the_strike_or_without_winners() {
+        let mut instrument = instrument(0);
+        settle(&mut instrument, STRIKE, EXPIRY, EXPIRY).unwrap();
+        assert!(instrument.outcome == BinaryOutcome::Void);
+
+        let mut instrument = self::instrument(0);
+        instrument.total_up_stake = 0;
+        settle(&mut instrument, 110_000_000, EXPIRY, EXPIRY).unwrap();
+        assert!(instrument.outcome == BinaryOutcome::Void);
+    }
+
+    #[test]
+    fn rejects_settlement_before_expiry() {
+        let mut instrument = instrument(0);
+        let err = settle(&mut instrument, 110_000_000, EXPIRY - 1, EXPIRY - 1).unwrap_err();
+        assert_eq!(err, ErrorCode::BinaryInstrumentNotExpired.into());
+    }
+
+    #[test]
+    fn rejects_prices_not_standing_at_expiry() {
+        // Without a market max price age the default still bounds how old the price may be
+        let mut instrument = instrument(0);
+        let err = settle(
+            &mut instrument,
+            110_000_000,
+            EXPIRY - SETTLEMENT_MAX_PRICE_AGE - 1,
+            EXPIRY,
+        )
+        .unwrap_err();
+        assert_eq!(err, ErrorCode::StalePrice.into());
+
+        // Any update after expiry could have been picked among several, even inside the
+        // grace period
+        let err = settle(&mut instrument, 110_000_000, EXPIRY + 1, EXPIRY + 10).unwrap_err();
+        assert_eq!(err, ErrorCode::StalePrice.into());
+    }
+
+    #[test]
+    fn settles_late_on_the_price_still_standing_at_expiry() {
+        let mut instrument = instrument(0);
+        let now = EXPIRY + SETTLEMENT_GRACE_PERIOD + 100;
+        settle(&mut instrument, 90_000_000, EXPIRY - 5, now).unwrap();
+        assert!(instrument.outcome == BinaryOutcome::Down);
+        assert_eq!(instrument.settlement_price, 90_000_000);
+    }
+
+    #[test]
+    fn voids_once_the_grace_period_passes_without_a_price() {
+        let mut instrument = instrument(0);
+        let now = EXPIRY + SETTLEMENT_GRACE_PERIOD + 1;
+        settle(&mut instrument, 110_000_000, EXPIRY + 1, now).unwrap();
+        assert!(instrument.outcome == BinaryOutcome::Void);
+        assert_eq!(instrument.settlement_price, 0);
+    }
+
+    #[test]
+    fn staking_closes_before_expiry() {
+        let instrument = instrument(0);
+        require_staking_open(&instrument, EXPIRY - STAKE_CUTOFF - 1).unwrap();
+        let err = require_staking_open(&instrument, EXPIRY - STAKE_CUTOFF).unwrap_err();
+        assert_eq!(err, ErrorCode::BinaryStakingClosed.into());
+        let err = require_staking_open(&instrument, EXPIRY - 1).unwrap_err();
+        assert_eq!(err, ErrorCode::BinaryStakingClosed.into());
+    }
+}
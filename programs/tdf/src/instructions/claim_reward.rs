@@ -1,10 +1,17 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 
-use crate::state::{League, Leaderboard, Participant, LeagueStatus};
+use crate::instructions::pause::require_withdrawals_open;
+use crate::state::{GlobalState, Leaderboard, League, LeagueStatus, Participant};
 
 #[derive(Accounts)]
 pub struct ClaimReward<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
     #[account(mut)]
     pub league: Account<'info, League>,
 
@@ -43,6 +50,7 @@ pub fn claim_reward(ctx: Context<ClaimReward>) -> Result<()> {
         crate::errors::ErrorCode::LeagueNotClosed
     );
     require!(!participant.claimed, crate::errors::ErrorCode::AlreadyClaimed);
+    require_withdrawals_open(&ctx.accounts.global_state, league)?;
 
     let participant_key = participant.key();
     let topk_equity = &leaderboard.topk_equity;
@@ -0,0 +1,278 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::{get_associated_token_address, AssociatedToken};
+use anchor_spl::token::Token;
+
+use crate::instructions::market::init_market;
+use crate::state::{GlobalState, League, ListingBond, Market, Role};
+
+#[derive(Accounts)]
+pub struct SetListingBond<'info> {
+    #[account(mut)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        constraint = global_state.has_role(&admin.key(), Role::FeeManager)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
+
+/// Set the token and amount league creators bond to list a market, an amount of 0 disables it
+pub fn set_listing_bond(ctx: Context<SetListingBond>, mint: Pubkey, amount: u64) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+    global_state.listing_bond_mint = mint;
+    global_state.listing_bond_amount = amount;
+
+    msg!("Listing bond set to {} of {:?}", amount, mint);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ListCommunityMarket<'info> {
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Any league of the creator's, only league creators list markets
+    #[account(has_one = creator @ crate::errors::ErrorCode::NotCreator)]
+    pub league: Account<'info, League>,
+
+    // Namespaced by creator so it never takes the address of an admin listing on the same feed
+    #[account(
+        init,
+        payer = creator,
+        space = Market::SPACE,
+        seeds = [b"community_market", creator.key().as_ref(), oracle_feed.key().as_ref()],
+        bump
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        init,
+        payer = creator,
+        space = ListingBond::SPACE,
+        seeds = [b"listing_bond", market.key().as_ref()],
+        bump
+    )]
+    pub listing_bond: Account<'info, ListingBond>,
+
+    /// CHECK: Oracle feed account - validated by the oracle program
+    pub oracle_feed: AccountInfo<'info>,
+    /// CHECK: Base currency account - validated by the token program
+    pub base_currency: AccountInfo<'info>,
+
+    /// CHECK: Bond mint, must match the global listing bond mint
+    #[account(address = global_state.listing_bond_mint)]
+    pub bond_mint: UncheckedAccount<'info>,
+
+    /// CHECK: Creator's bond ATA
+    #[account(mut)]
+    pub creator_bond_ata: UncheckedAccount<'info>,
+
+    /// CHECK: Listing bond vault ATA - created here
+    #[account(mut)]
+    pub bond_vault: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+/// League creators list a market by posting the listing bond. The market trades only in
+/// the creator's own leagues until the admin promotes it.
+pub fn list_community_market(
+    ctx: Context<ListCommunityMarket>,
+    symbol: [u8; 16],
+    decimals: u8,
+    max_leverage: u8,
+    borrow_rate_bps: u16,
+    max_price_age: i64,
+    price_decimals: u8,
+) -> Result<()> {
+    let global_state = &ctx.accounts.global_state;
+    let bond_mint = ctx.accounts.bond_mint.key();
+    let bond_amount = global_state.listing_bond_amount;
+    require!(
+        bond_amount > 0,
+        crate::errors::ErrorCode::ListingBondNotConfigured
+    );
+
+    let creator = ctx.accounts.creator.key();
+    require_keys_eq!(
+        ctx.accounts.creator_bond_ata.key(),
+        get_associated_token_address(&creator, &bond_mint),
+        crate::errors::ErrorCode::InvalidBondAccount
+    );
+    let listing_bond_key = ctx.accounts.listing_bond.key();
+    require_keys_eq!(
+        ctx.accounts.bond_vault.key(),
+        get_associated_token_address(&listing_bond_key, &bond_mint),
+        crate::errors::ErrorCode::InvalidBondAccount
+    );
+
+    anchor_spl::associated_token::create(CpiContext::new(
+        ctx.accounts.associated_token_program.to_account_info(),
+        anchor_spl::associated_token::Create {
+            payer: ctx.accounts.creator.to_account_info(),
+            associated_token: ctx.accounts.bond_vault.to_account_info(),
+            authority: ctx.accounts.listing_bond.to_account_info(),
+            mint: ctx.accounts.bond_mint.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+        },
+    ))?;
+    anchor_spl::token::transfer(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: ctx.accounts.creator_bond_ata.to_account_info(),
+                to: ctx.accounts.bond_vault.to_account_info(),
+                authority: ctx.accounts.creator.to_account_info(),
+            },
+        ),
+        bond_amount,
+    )?;
+
+    let bump = ctx.bumps.market;
+    let market = &mut ctx.accounts.market;
+    init_market(
+        market,
+        symbol,
+        ctx.accounts.oracle_feed.key(),
+        None,
+        None,
+        ctx.accounts.base_currency.key(),
+        decimals,
+        creator,
+        max_leverage,
+        borrow_rate_bps,
+        max_price_age,
+        price_decimals,
+        bump,
+    )?;
+    market.restricted = true;
+
+    let listing_bond = &mut ctx.accounts.listing_bond;
+    listing_bond.market = market.key();
+    listing_bond.creator = creator;
+    listing_bond.mint = bond_mint;
+    listing_bond.amount = bond_amount;
+    listing_bond.bump = ctx.bumps.listing_bond;
+    listing_bond.version = ListingBond::VERSION;
+
+    msg!(
+        "Community market listed: {:?}, bond {}",
+        market.symbol,
+        bond_amount
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ReleaseListingBond<'info> {
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        has_one = market,
+        seeds = [b"listing_bond", market.key().as_ref()],
+        bump = listing_bond.bump
+    )]
+    pub listing_bond: Account<'info, ListingBond>,
+
+    /// CHECK: Listing bond vault ATA
+    #[account(mut)]
+    pub bond_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Creator's bond ATA, or the treasury's when slashing
+    #[account(mut)]
+    pub recipient_ata: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = global_state.has_role(&admin.key(), Role::MarketManager)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Open a creator-listed market to every league and return the bond to its creator
+pub fn promote_market(ctx: Context<ReleaseListingBond>) -> Result<()> {
+    let creator = ctx.accounts.listing_bond.creator;
+    release_listing_bond(ctx.accounts, creator)?;
+    ctx.accounts.market.restricted = false;
+
+    msg!("Market promoted: {:?}", ctx.accounts.market.symbol);
+    Ok(())
+}
+
+/// Deactivate a creator-listed market. With `slash` (e.g. a bad oracle) the bond goes to
+/// the treasury, otherwise back to the creator.
+pub fn delist_community_market(ctx: Context<ReleaseListingBond>, slash: bool) -> Result<()> {
+    require!(
+        ctx.accounts.market.is_active,
+        crate::errors::ErrorCode::MarketNotActive
+    );
+    let recipient = if slash {
+        ctx.accounts.global_state.treasury
+    } else {
+        ctx.accounts.listing_bond.creator
+    };
+    release_listing_bond(ctx.accounts, recipient)?;
+    ctx.accounts.market.is_active = false;
+
+    msg!(
+        "Market delisted: {:?}, bond slashed: {}",
+        ctx.accounts.market.symbol,
+        slash
+    );
+    Ok(())
+}
+
+fn release_listing_bond(accounts: &mut ReleaseListingBond, recipient: Pubkey) -> Result<()> {
+    let listing_bond = &accounts.listing_bond;
+    require!(
+        listing_bond.amount > 0,
+        crate::errors::ErrorCode::ListingBondReleased
+    );
+    require_keys_eq!(
+        accounts.bond_vault.key(),
+        get_associated_token_address(&listing_bond.key(), &listing_bond.mint),
+        crate::errors::ErrorCode::InvalidBondAccount
+    );
+    require_keys_eq!(
+        accounts.recipient_ata.key(),
+        get_associated_token_address(&recipient, &listing_bond.mint),
+        crate::errors::ErrorCode::InvalidBondAccount
+    );
+
+    // PDA signer seeds (bond vault authority)
+    let market_key = listing_bond.market;
+    let seeds = &[
+        b"listing_bond".as_ref(),
+        market_key.as_ref(),
+        &[listing_bond.bump],
+    ];
+    let signer_seeds = &[&seeds[..]];
+
+    anchor_spl::token::transfer(
+        CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            anchor_spl::token::Transfer {
+                from: accounts.bond_vault.to_account_info(),
+                to: accounts.recipient_ata.to_account_info(),
+                authority: accounts.listing_bond.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        listing_bond.amount,
+    )?;
+
+    accounts.listing_bond.amount = 0;
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use anchor_lang::prelude::*;
+
+use crate::instructions::position::update_liquidation_prices;
+use crate::math::Rounding;
+use crate::state::{
+    CorporateAction, CorporateActionKind, Direction, GlobalState, Market, Participant, Position,
+    Role,
+};
+use crate::utils::{calculate_notional, calculate_pro_rata};
+
+#[derive(Accounts)]
+pub struct RecordCorporateAction<'info> {
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = CorporateAction::SPACE,
+        seeds = [b"corporate_action", market.key().as_ref(), market.corporate_action_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub corporate_action: Account<'info, CorporateAction>,
+
+    #[account(
+        mut,
+        constraint = global_state.has_role(&admin.key(), Role::MarketManager)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Record a split or dividend on the market. Positions opened before it must have it applied
+/// before they can trade or be refreshed again, so record it when the oracle switches over.
+pub fn record_corporate_action(
+    ctx: Context<RecordCorporateAction>,
+    kind: CorporateActionKind,
+) -> Result<()> {
+    match kind {
+        CorporateActionKind::Split {
+            numerator,
+            denominator,
+        } => require!(
+            numerator > 0 && denominator > 0 && numerator != denominator,
+            crate::errors::ErrorCode::InvalidCorporateAction
+        ),
+        CorporateActionKind::Dividend { amount_per_unit } => require!(
+            amount_per_unit > 0,
+            crate::errors::ErrorCode::InvalidCorporateAction
+        ),
+    }
+
+    let market = &mut ctx.accounts.market;
+    let corporate_action = &mut ctx.accounts.corporate_action;
+    corporate_action.market = market.key();
+    corporate_action.index = market.corporate_action_count;
+    corporate_action.kind = kind;
+    corporate_action.recorded_at = Clock::get()?.unix_timestamp;
+    corporate_action.bump = ctx.bumps.corporate_action;
+    corporate_action.version = CorporateAction::VERSION;
+
+    market.corporate_action_count = market
+        .corporate_action_count
+        .checked_add(1)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    msg!(
+        "Corporate action {} recorded on {:?}",
+        corporate_action.index,
+        market.symbol
+    );
+    Ok(())
+}
+
+/// Crank, applies the position's next pending corporate action
+#[derive(Accounts)]
+pub struct ApplyCorporateAction<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"participant", position.league.as_ref(), position.user.as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+
+    #[account(
+        mut,
+        seeds = [b"position", position.league.as_ref(), position.user.as_ref(), position.seq_num.to_le_bytes().as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, Position>,
+
+    #[account(address = position.market)]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        seeds = [b"corporate_action", market.key().as_ref(), position.corporate_actions_applied.to_le_bytes().as_ref()],
+        bump = corporate_action.bump
+    )]
+    pub corporate_action: Account<'info, CorporateAction>,
+}
+
+/// A split rescales size and entry price while notional, margin and PnL stay the same.
+/// A dividend credits longs and debits shorts by the cash paid on the current size.
+pub fn apply_corporate_action(ctx: Context<ApplyCorporateAction>) -> Result<()> {
+    let participant = &mut ctx.accounts.participant;
+    let position = &mut ctx.accounts.position;
+    adjust_position(position, participant, &ctx.accounts.corporate_action.kind)?;
+
+    position.corporate_actions_applied = position
+        .corporate_actions_applied
+        .checked_add(1)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    Ok(())
+}
+
+/// Apply `kind` to the position, paying dividends into the participant's balance
+fn adjust_position(
+    position: &mut Position,
+    participant: &mut Participant,
+    kind: &CorporateActionKind,
+) -> Result<()> {
+    match *kind {
+        CorporateActionKind::Split {
+            numerator,
+            denominator,
+        } => {
+            let (numerator, denominator) = (numerator as i64, denominator as i64);
+            // Fractional units left over by the split are dropped
+            position.size =
+                calculate_pro_rata(position.size, numerator, denominator, Rounding::Down)?;
+            position.entry_size =
+                calculate_pro_rata(position.entry_size, numerator, denominator, Rounding::Down)?;
+            position.closed_size =
+                calculate_pro_rata(position.closed_size, numerator, denominator, Rounding::Down)?;
+            position.entry_price = calculate_pro_rata(
+                position.entry_price,
+                denominator,
+                numerator,
+                Rounding::TowardZero,
+            )?;
+            position.closed_price = calculate_pro_rata(
+                position.closed_price,
+                denominator,
+                numerator,
+                Rounding::TowardZero,
+            )?;
+            update_liquidation_prices(position, participant)?;
+
+            msg!(
+                "Split {}:{} applied, new size {}",
+                numerator,
+                denominator,
+                position.size
+            );
+        }
+        CorporateActionKind::Dividend { amount_per_unit } => {
+            let cash =
+                calculate_notional(amount_per_unit, position.size, position.market_decimals)?;
+            let cash = match position.direction {
+                Direction::Long => cash,
+                Direction::Short => -cash,
+            };
+            participant.virtual_balance = participant
+                .virtual_balance
+                .checked_add(cash)
+                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+            msg!("Dividend applied: {}", cash);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::position::fixtures::{open_position, participant};
+
+    fn open(direction: Direction) -> (Position, Participant) {
+        let mut participant = participant(10_000_000_000);
+        let position = open_position(&mut participant, direction);
+        (position, participant)
+    }
+
+    #[test]
+    fn split_rescales_size_and_price_but_not_value() {
+        let (mut position, mut participant) = open(Direction::Long);
+        let split = CorporateActionKind::Split {
+            numerator: 4,
+            denominator: 1,
+        };
+        adjust_position(&mut position, &mut participant, &split).unwrap();
+
+        assert_eq!(position.size, 40_000_000);
+        assert_eq!(position.entry_size, 40_000_000);
+        assert_eq!(position.entry_price, 25_000_000);
+        assert_eq!(position.notional, 1_000_000_000);
+        assert_eq!(position.margin, 200_000_000);
+        assert_eq!(position.liquidation_price, 20_000_000);
+        assert_eq!(participant.virtual_balance, 10_000_000_000);
+    }
+
+    #[test]
+    fn reverse_split_drops_fractional_units() {
+        let (mut position, mut participant) = open(Direction::Long);
+        position.size = 10_000_001;
+        let split = CorporateActionKind::Split {
+            numerator: 1,
+            denominator: 3,
+        };
+        adjust_position(&mut position, &mut participant, &split).unwrap();
+        assert_eq!(position.size, 3_333_333);
+        assert_eq!(position.entry_price, 300_000_000);
+    }
+
+    #[test]
+    fn dividend_credits_longs_and_debits_shorts() {
+        let dividend = CorporateActionKind::Dividend {
+            amount_per_unit: 1_500_000,
+        };
+        let (mut long, mut long_holder) = open(Direction::Long);
+        adjust_position(&mut long, &mut long_holder, &dividend).unwrap();
+        assert_eq!(long_holder.virtual_balance, 10_015_000_000);
+
+        let (mut short, mut short_holder) = open(Direction::Short);
+        adjust_position(&mut short, &mut short_holder, &dividend).unwrap();
+        assert_eq!(short_holder.virtual_balance, 9_985_000_000);
+        assert_eq!(short.size, 10_000_000);
+    }
+}
@@ -0,0 +1,559 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::instructions::pause::{require_not_paused, require_withdrawals_open};
+use crate::instructions::position::{
+    allocate_position_account, decrease_position, fill_position, increase_position, new_position,
+    require_available_margin, require_corporate_actions_applied, require_market_listed_for,
+    require_market_not_halted, require_spot_order,
+};
+use crate::state::{
+    DcaAction, DcaOpen, DcaSchedule, GlobalState, League, LeagueStatus, Market, Participant,
+    Position, PositionMode,
+};
+use crate::utils::{check_acceptable_price, convert_with_fx, get_market_price};
+
+#[derive(Accounts)]
+#[instruction(nonce: u32)]
+pub struct CreateDcaSchedule<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"participant", league.key().as_ref(), user.key().as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+
+    pub market: Account<'info, Market>,
+
+    /// Existing position the slices are applied to, none when the first slice opens one
+    pub position: Option<Account<'info, Position>>,
+
+    #[account(
+        init,
+        payer = user,
+        space = DcaSchedule::SPACE,
+        seeds = [b"dca", participant.key().as_ref(), market.key().as_ref(), nonce.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub dca_schedule: Account<'info, DcaSchedule>,
+
+    pub league: Account<'info, League>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Schedule `total_slices` slices of `slice_size`, one every `interval` seconds from `start_ts`.
+/// Without a position the schedule buys into a new one described by `open`, whose account
+/// rent is deposited with the schedule until the first slice opens it.
+pub fn create_dca_schedule(
+    ctx: Context<CreateDcaSchedule>,
+    nonce: u32,
+    action: DcaAction,
+    open: Option<DcaOpen>,
+    slice_size: i64,
+    total_slices: u32,
+    interval: i64,
+    start_ts: i64,
+    expires_at: i64,
+    acceptable_price: Option<i64>,
+) -> Result<()> {
+    let league = &ctx.accounts.league;
+    let market = &ctx.accounts.market;
+    let participant = &ctx.accounts.participant;
+    let now = Clock::get()?.unix_timestamp;
+
+    require!(
+        league.status == LeagueStatus::Active,
+        crate::errors::ErrorCode::LeagueNotActive
+    );
+    require!(
+        slice_size > 0 && total_slices > 0 && interval > 0,
+        crate::errors::ErrorCode::InvalidDcaSchedule
+    );
+    require!(
+        expires_at == 0 || expires_at > start_ts.max(now),
+        crate::errors::ErrorCode::InvalidDcaSchedule
+    );
+
+    let position_key = match (&ctx.accounts.position, &open) {
+        (Some(position), None) => {
+            require!(
+                participant.positions.contains(&position.key()),
+                crate::errors::ErrorCode::PositionMismatch
+            );
+            require_keys_eq!(
+                position.market,
+                market.key(),
+                crate::errors::ErrorCode::PositionMismatch
+            );
+            Some(position.key())
+        }
+        (None, Some(open)) => {
+            // Only buying can open a position, one-way leagues net through their own path
+            require!(
+                action == DcaAction::Increase,
+                crate::errors::ErrorCode::InvalidDcaSchedule
+            );
+            require!(
+                league.position_mode == PositionMode::Hedge,
+                crate::errors::ErrorCode::InvalidPositionMode
+            );
+            require!(
+                open.leverage > 0 && open.leverage <= league.max_leverage,
+                crate::errors::ErrorCode::InvalidLeverage
+            );
+            require_spot_order(league, &open.direction, open.leverage)?;
+            require_market_listed_for(league, market)?;
+            None
+        }
+        _ => return err!(crate::errors::ErrorCode::InvalidDcaSchedule),
+    };
+
+    // The account the first slice opens is paid for now, the keeper doesn't front it
+    let position_rent = if position_key.is_none() {
+        let rent = Rent::get()?.minimum_balance(Position::SPACE);
+        transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user.to_account_info(),
+                    to: ctx.accounts.dca_schedule.to_account_info(),
+                },
+            ),
+            rent,
+        )?;
+        rent
+    } else {
+        0
+    };
+
+    let dca_schedule = &mut ctx.accounts.dca_schedule;
+    dca_schedule.league = league.key();
+    dca_schedule.user = ctx.accounts.user.key();
+    dca_schedule.participant = participant.key();
+    dca_schedule.market = market.key();
+    dca_schedule.nonce = nonce;
+    dca_schedule.position = position_key;
+    dca_schedule.open = open;
+    dca_schedule.position_rent = position_rent;
+    dca_schedule.action = action;
+    dca_schedule.slice_size = slice_size;
+    dca_schedule.total_slices = total_slices;
+    dca_schedule.interval = interval;
+    dca_schedule.expires_at = expires_at;
+    dca_schedule.acceptable_price = acceptable_price;
+    dca_schedule.executed_slices = 0;
+    dca_schedule.executed_size = 0;
+    dca_schedule.next_execution_ts = start_ts.max(now);
+    dca_schedule.last_executed_at = 0;
+    dca_schedule.created_at = now;
+    dca_schedule.bump = ctx.bumps.dca_schedule;
+    dca_schedule.version = DcaSchedule::VERSION;
+
+    msg!(
+        "DCA schedule created: {} slices of {} every {}s",
+        total_slices,
+        slice_size,
+        interval
+    );
+    Ok(())
+}
+
+/// Keeper crank, executes the next due slice through the same path as a manual open, increase or decrease
+#[derive(Accounts)]
+pub struct ExecuteDcaSlice<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub keeper: Signer<'info>,
+
+    /// CHECK: This account is validated by the dca schedule's user field
+    #[account(address = dca_schedule.user)]
+    pub user: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        address = dca_schedule.participant
+    )]
+    pub participant: Account<'info, Participant>,
+
+    /// CHECK: the schedule's position, or the position PDA for the participant's next
+    /// sequence number when the first slice opens it
+    #[account(mut)]
+    pub position: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"dca", dca_schedule.participant.as_ref(), dca_schedule.market.as_ref(), dca_schedule.nonce.to_le_bytes().as_ref()],
+        bump = dca_schedule.bump
+    )]
+    pub dca_schedule: Account<'info, DcaSchedule>,
+
+    #[account(address = dca_schedule.league)]
+    pub league: Account<'info, League>,
+    #[account(address = dca_schedule.market)]
+    pub market: Account<'info, Market>,
+    /// CHECK: oracle feed from market
+    pub oracle_feed: AccountInfo<'info>,
+    /// CHECK: denominator feed from market, pair markets only
+    pub denominator_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fallback feed from market, markets with one only
+    pub fallback_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fx feed from market, markets quoted in another currency only
+    pub fx_feed: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+    // remaining accounts = basket constituent feeds, basket markets only
+}
+
+pub fn execute_dca_slice(ctx: Context<ExecuteDcaSlice>) -> Result<()> {
+    let league = &ctx.accounts.league;
+    let market = &ctx.accounts.market;
+    let position_ai = &ctx.accounts.position;
+    let now = Clock::get()?.unix_timestamp;
+
+    {
+        let dca_schedule = &ctx.accounts.dca_schedule;
+        require!(
+            league.status == LeagueStatus::Active,
+            crate::errors::ErrorCode::LeagueNotActive
+        );
+        require!(
+            dca_schedule.executed_slices < dca_schedule.total_slices,
+            crate::errors::ErrorCode::DcaScheduleCompleted
+        );
+        require!(
+            dca_schedule.expires_at == 0 || now < dca_schedule.expires_at,
+            crate::errors::ErrorCode::DcaScheduleExpired
+        );
+        require!(
+            now >= dca_schedule.next_execution_ts,
+            crate::errors::ErrorCode::DcaSliceNotDue
+        );
+    }
+    require!(
+        ctx.accounts.oracle_feed.key() == market.oracle_feed,
+        crate::errors::ErrorCode::OracleMismatch
+    );
+
+    let market_price = get_market_price(
+        &ctx.accounts.oracle_feed,
+        ctx.accounts.denominator_feed.as_deref(),
+        ctx.accounts.fallback_feed.as_deref(),
+        ctx.remaining_accounts,
+        &market.price_config(),
+        now,
+    )?;
+    let current_price = convert_with_fx(
+        market_price,
+        ctx.accounts.fx_feed.as_deref(),
+        market.fx_feed,
+        &market.price_config(),
+        now,
+    )?;
+
+    let slice_size = match ctx.accounts.dca_schedule.position {
+        Some(position_key) => {
+            require_keys_eq!(
+                position_ai.key(),
+                position_key,
+                crate::errors::ErrorCode::PositionMismatch
+            );
+            require_keys_eq!(
+                *position_ai.owner,
+                crate::ID,
+                crate::errors::ErrorCode::PositionMismatch
+            );
+            let mut data = position_ai.try_borrow_mut_data()?;
+            let mut position = Position::try_deserialize(&mut &data[..])?;
+            require!(
+                position.size > 0,
+                crate::errors::ErrorCode::InvalidPositionSize
+            );
+            require_corporate_actions_applied(&position, market)?;
+
+            let dca_schedule = &ctx.accounts.dca_schedule;
+            let is_increase = dca_schedule.action == DcaAction::Increase;
+            if is_increase {
+                require_not_paused(&ctx.accounts.global_state, league)?;
+                require_market_not_halted(market, market_price, now)?;
+            } else {
+                require_withdrawals_open(&ctx.accounts.global_state, league)?;
+            }
+            check_acceptable_price(
+                position.direction.clone(),
+                is_increase,
+                current_price,
+                dca_schedule.acceptable_price,
+            )?;
+
+            let slice_size = apply_slice(
+                &mut position,
+                position_key,
+                &mut ctx.accounts.participant,
+                dca_schedule,
+                current_price,
+                now,
+            )?;
+            let mut dst = &mut data[..];
+            position.try_serialize(&mut dst)?;
+            slice_size
+        }
+        None => {
+            let open = ctx
+                .accounts
+                .dca_schedule
+                .open
+                .clone()
+                .ok_or(crate::errors::ErrorCode::InvalidDcaSchedule)?;
+            let participant = &mut ctx.accounts.participant;
+            let dca_schedule = &mut ctx.accounts.dca_schedule;
+
+            require_not_paused(&ctx.accounts.global_state, league)?;
+            require_market_listed_for(league, market)?;
+            require_market_not_halted(market, market_price, now)?;
+            require!(
+                participant.positions.len() < league.max_open_positions as usize,
+                crate::errors::ErrorCode::MaxOpenPositionExceeded
+            );
+            check_acceptable_price(
+                open.direction.clone(),
+                true,
+                current_price,
+                dca_schedule.acceptable_price,
+            )?;
+
+            let seq_num = participant.current_position_seq;
+            let bump = create_position_from_deposit(
+                position_ai,
+                dca_schedule,
+                &ctx.accounts.system_program,
+                &league.key(),
+                &ctx.accounts.user.key(),
+                seq_num,
+            )?;
+            let mut position = new_position(
+                league.key(),
+                ctx.accounts.user.key(),
+                market,
+                seq_num,
+                open.direction.clone(),
+                bump,
+                now,
+            );
+            fill_position(
+                &mut position,
+                participant,
+                open.direction,
+                dca_schedule.slice_size,
+                open.leverage,
+                current_price,
+                now,
+            )?;
+            require_available_margin(participant)?;
+
+            let mut data = position_ai.try_borrow_mut_data()?;
+            let mut dst = &mut data[..];
+            position.try_serialize(&mut dst)?;
+
+            participant.current_position_seq = participant
+                .current_position_seq
+                .checked_add(1)
+                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+            participant.positions.push(position_ai.key());
+            dca_schedule.position = Some(position_ai.key());
+            msg!("DCA schedule opened position {}", seq_num);
+            dca_schedule.slice_size
+        }
+    };
+
+    let dca_schedule = &mut ctx.accounts.dca_schedule;
+    dca_schedule.executed_slices += 1;
+    dca_schedule.executed_size = dca_schedule
+        .executed_size
+        .checked_add(slice_size)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    dca_schedule.last_executed_at = now;
+    dca_schedule.next_execution_ts = now
+        .checked_add(dca_schedule.interval)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    msg!(
+        "DCA slice {}/{} executed: {} at price {}",
+        dca_schedule.executed_slices,
+        dca_schedule.total_slices,
+        slice_size,
+        current_price
+    );
+    Ok(())
+}
+
+/// Increase or decrease an open position by one slice, returning the size traded
+fn apply_slice(
+    position: &mut Position,
+    position_key: Pubkey,
+    participant: &mut Participant,
+    dca_schedule: &DcaSchedule,
+    current_price: i64,
+    now: i64,
+) -> Result<i64> {
+    if dca_schedule.action == DcaAction::Increase {
+        increase_position(
+            position,
+            participant,
+            dca_schedule.slice_size,
+            current_price,
+            now,
+        )?;
+        require_available_margin(participant)?;
+        Ok(dca_schedule.slice_size)
+    } else {
+        // The last slices of a sell schedule shrink to whatever is left of the position
+        let size_to_close = dca_schedule.slice_size.min(position.size);
+        decrease_position(
+            position,
+            position_key,
+            participant,
+            size_to_close,
+            current_price,
+            now,
+        )?;
+        Ok(size_to_close)
+    }
+}
+
+/// Create the position PDA for `seq_num` with the rent deposited on the schedule. Returns its bump.
+fn create_position_from_deposit<'info>(
+    position_ai: &AccountInfo<'info>,
+    dca_schedule: &mut Account<'info, DcaSchedule>,
+    system_program: &Program<'info, System>,
+    league_key: &Pubkey,
+    user_key: &Pubkey,
+    seq_num: u64,
+) -> Result<u8> {
+    let bump =
+        allocate_position_account(position_ai, system_program, league_key, user_key, seq_num)?;
+
+    // The schedule is owned by this program, so its deposit moves without a signature
+    let rent = dca_schedule.position_rent;
+    dca_schedule.sub_lamports(rent)?;
+    position_ai.add_lamports(rent)?;
+    dca_schedule.position_rent = 0;
+
+    Ok(bump)
+}
+
+#[derive(Accounts)]
+pub struct CancelDcaSchedule<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        has_one = user,
+        seeds = [b"dca", dca_schedule.participant.as_ref(), dca_schedule.market.as_ref(), dca_schedule.nonce.to_le_bytes().as_ref()],
+        bump = dca_schedule.bump
+    )]
+    pub dca_schedule: Account<'info, DcaSchedule>,
+}
+
+/// Cancel a schedule, or clean up a completed one, and return its rent and any unused
+/// position deposit to the user
+pub fn cancel_dca_schedule(ctx: Context<CancelDcaSchedule>) -> Result<()> {
+    let dca_schedule = &ctx.accounts.dca_schedule;
+    msg!(
+        "DCA schedule canceled after {}/{} slices",
+        dca_schedule.executed_slices,
+        dca_schedule.total_slices
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ErrorCode;
+    use crate::instructions::position::fixtures::{open_position, participant};
+    use crate::state::Direction;
+
+    fn open_long(participant: &mut Participant, key: Pubkey) -> Position {
+        let position = open_position(participant, Direction::Long);
+        participant.positions.push(key);
+        position
+    }
+
+    fn schedule(action: DcaAction, slice_size: i64) -> DcaSchedule {
+        let mut schedule =
+            DcaSchedule::try_deserialize_unchecked(&mut &vec![0u8; DcaSchedule::SPACE][..])
+                .unwrap();
+        schedule.action = action;
+        schedule.slice_size = slice_size;
+        schedule
+    }
+
+    #[test]
+    fn buy_slices_add_to_the_position() {
+        let key = Pubkey::new_unique();
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_long(&mut participant, key);
+
+        let traded = apply_slice(
+            &mut position,
+            key,
+            &mut participant,
+            &schedule(DcaAction::Increase, 10_000_000),
+            120_000_000,
+            0,
+        )
+        .unwrap();
+        assert_eq!(traded, 10_000_000);
+        assert_eq!(position.size, 20_000_000);
+        assert_eq!(position.entry_price, 110_000_000);
+        // 200 for the first 1,000 and 240 for the 1,200 slice
+        assert_eq!(participant.used_margin, 440_000_000);
+    }
+
+    #[test]
+    fn buy_slices_need_available_margin() {
+        let key = Pubkey::new_unique();
+        // 250 covers the first 200 of margin but not another 200
+        let mut participant = participant(250_000_000);
+        let mut position = open_long(&mut participant, key);
+
+        let err = apply_slice(
+            &mut position,
+            key,
+            &mut participant,
+            &schedule(DcaAction::Increase, 10_000_000),
+            100_000_000,
+            0,
+        )
+        .unwrap_err();
+        assert_eq!(err, ErrorCode::InsufficientMargin.into());
+    }
+
+    #[test]
+    fn sell_slices_shrink_to_what_is_left() {
+        let key = Pubkey::new_unique();
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_long(&mut participant, key);
+        let sell = schedule(DcaAction::Decrease, 6_000_000);
+
+        let traded =
+            apply_slice(&mut position, key, &mut participant, &sell, 100_000_000, 0).unwrap();
+        assert_eq!(traded, 6_000_000);
+        assert_eq!(position.size, 4_000_000);
+
+        let traded =
+            apply_slice(&mut position, key, &mut participant, &sell, 100_000_000, 0).unwrap();
+        assert_eq!(traded, 4_000_000);
+        assert_eq!(position.size, 0);
+        assert!(participant.positions.is_empty());
+    }
+}
@@ -1,20 +1,28 @@
 use anchor_lang::prelude::*;
-use crate::state::GlobalState;
+
+use crate::events::{
+    AdminTransferProposed, AdminTransferred, FeeBpsUpdated, PermissionProgramUpdated,
+    TreasuryUpdated,
+};
+use crate::state::{GlobalState, Role, RoleGrant};
+
+/// Protocol fee can't exceed 100%
+pub const MAX_FEE_BPS: u16 = 10_000;
 
 #[derive(Accounts)]
 pub struct InitializeGlobalState<'info> {
     #[account(
         init,
         payer = admin,
-        space = 8 + 32 + 2 + 32 + 32 + 1,
+        space = GlobalState::SPACE,
         seeds = [b"global_state"],
         bump
     )]
     pub global_state: Account<'info, GlobalState>,
-    
+
     #[account(mut)]
     pub admin: Signer<'info>,
-    
+
     pub system_program: Program<'info, System>,
 }
 
@@ -24,6 +32,11 @@ pub fn initialize_global_state(
     treasury: Pubkey,
     permission_program: Pubkey,
 ) -> Result<()> {
+    require!(
+        fee_bps <= MAX_FEE_BPS,
+        crate::errors::ErrorCode::InvalidFeeBps
+    );
+
     let global_state = &mut ctx.accounts.global_state;
     let bump = ctx.bumps.global_state;
     
@@ -31,8 +44,199 @@ pub fn initialize_global_state(
     global_state.fee_bps = fee_bps;
     global_state.treasury = treasury;
     global_state.permission_program = permission_program;
+    global_state.listing_bond_mint = Pubkey::default();
+    global_state.listing_bond_amount = 0;
+    global_state.roles = Vec::new();
+    global_state.pending_admin = None;
+    global_state.paused = false;
+    global_state.withdrawals_paused = false;
     global_state.bump = bump;
-    
+    global_state.version = GlobalState::VERSION;
+
     msg!("Global state initialized with admin: {:?}", global_state.admin);
     Ok(())
 }
+
+#[derive(Accounts)]
+pub struct UpdateGlobalState<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        constraint = admin.key() == global_state.admin @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
+
+pub fn grant_role(ctx: Context<UpdateGlobalState>, key: Pubkey, role: Role) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+    let grant = RoleGrant { key, role };
+    require!(
+        !global_state.roles.contains(&grant),
+        crate::errors::ErrorCode::RoleAlreadyGranted
+    );
+    require!(
+        global_state.roles.len() < GlobalState::MAX_ROLE_GRANTS,
+        crate::errors::ErrorCode::TooManyRoleGrants
+    );
+    global_state.roles.push(grant);
+
+    msg!("Granted {:?} to {:?}", role, key);
+    Ok(())
+}
+
+pub fn revoke_role(ctx: Context<UpdateGlobalState>, key: Pubkey, role: Role) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+    let index = global_state
+        .roles
+        .iter()
+        .position(|grant| grant.key == key && grant.role == role)
+        .ok_or(crate::errors::ErrorCode::RoleNotGranted)?;
+    global_state.roles.swap_remove(index);
+
+    msg!("Revoked {:?} from {:?}", role, key);
+    Ok(())
+}
+
+/// First step of an admin transfer, `None` cancels a pending proposal
+pub fn propose_admin(ctx: Context<UpdateGlobalState>, new_admin: Option<Pubkey>) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+    let old_pending_admin = global_state.pending_admin;
+    global_state.pending_admin = new_admin;
+
+    emit!(AdminTransferProposed {
+        admin: global_state.admin,
+        old_pending_admin,
+        new_pending_admin: new_admin,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub new_admin: Signer<'info>,
+}
+
+/// Second step of an admin transfer, signed by the proposed admin
+pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+    let new_admin = ctx.accounts.new_admin.key();
+    require!(
+        global_state.pending_admin == Some(new_admin),
+        crate::errors::ErrorCode::NotPendingAdmin
+    );
+
+    let old_admin = global_state.admin;
+    global_state.admin = new_admin;
+    global_state.pending_admin = None;
+
+    emit!(AdminTransferred {
+        old_admin,
+        new_admin,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetFeeBps<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        constraint = global_state.has_role(&admin.key(), Role::FeeManager)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
+
+pub fn set_fee_bps(ctx: Context<SetFeeBps>, fee_bps: u16) -> Result<()> {
+    require!(
+        fee_bps <= MAX_FEE_BPS,
+        crate::errors::ErrorCode::InvalidFeeBps
+    );
+    let global_state = &mut ctx.accounts.global_state;
+    let old_fee_bps = global_state.fee_bps;
+    global_state.fee_bps = fee_bps;
+
+    emit!(FeeBpsUpdated {
+        old_fee_bps,
+        new_fee_bps: fee_bps,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetTreasury<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// CHECK: New treasury. Fees and slashed bonds go to its ATAs, so it has to be a wallet
+    /// or a system-owned PDA, not a token account or another program's account.
+    #[account(
+        owner = anchor_lang::system_program::ID @ crate::errors::ErrorCode::InvalidTreasury
+    )]
+    pub treasury: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = admin.key() == global_state.admin @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
+
+/// Treasury stays with the admin, fee managers can't redirect funds
+pub fn set_treasury(ctx: Context<SetTreasury>) -> Result<()> {
+    let treasury = ctx.accounts.treasury.key();
+    require_keys_neq!(
+        treasury,
+        Pubkey::default(),
+        crate::errors::ErrorCode::InvalidTreasury
+    );
+    let global_state = &mut ctx.accounts.global_state;
+    let old_treasury = global_state.treasury;
+    global_state.treasury = treasury;
+
+    emit!(TreasuryUpdated {
+        old_treasury,
+        new_treasury: treasury,
+    });
+    Ok(())
+}
+
+pub fn set_permission_program(
+    ctx: Context<UpdateGlobalState>,
+    permission_program: Pubkey,
+) -> Result<()> {
+    require_keys_neq!(
+        permission_program,
+        Pubkey::default(),
+        crate::errors::ErrorCode::InvalidPermissionProgram
+    );
+    let global_state = &mut ctx.accounts.global_state;
+    let old_permission_program = global_state.permission_program;
+    global_state.permission_program = permission_program;
+
+    emit!(PermissionProgramUpdated {
+        old_permission_program,
+        new_permission_program: permission_program,
+    });
+    Ok(())
+}
@@ -5,20 +5,31 @@ use ephemeral_rollups_sdk::anchor::{commit, delegate};
 use ephemeral_rollups_sdk::cpi::DelegateConfig;
 use ephemeral_rollups_sdk::ephem::commit_and_undelegate_accounts;
 
-use crate::state::{Leaderboard, League, LeagueStatus, Participant};
+use crate::instructions::pause::{require_not_paused, require_protocol_not_paused};
+use crate::state::{
+    GlobalState, Leaderboard, League, LeagueStatus, LeagueType, MarginMode, Participant,
+    PositionMode,
+};
 
 /// Market is bounded to 10
-/// top k is bounded to 50
+/// top k is bounded to 100
+/// open positions per participant are bounded to 25
 #[derive(Accounts)]
-#[instruction(start_ts: i64, end_ts: i64, entry_amount: u64, markets: Vec<Pubkey>, metadata_uri: String, max_participants: u32, virtual_on_deposit: u64, max_leverage: u8, nonce: u8)]
+#[instruction(start_ts: i64, end_ts: i64, entry_amount: u64, markets: Vec<Pubkey>, metadata_uri: String, max_participants: u32, virtual_on_deposit: u64, max_leverage: u8, nonce: u8, k: u16)]
 pub struct CreateLeague<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
     #[account(mut)]
     pub creator: Signer<'info>,
 
     #[account(
         init,
         payer = creator,
-        space = 8 + 32 + (4 + 32 * 50) + 8 + 8 + 1 + 32 + 8 + 32 + 8 + (4 + 200) + 1 + 4 + 8 + 1 + 1,
+        space = League::SPACE,
         seeds = [b"league", creator.key().as_ref(), &[nonce]],
         bump
     )]
@@ -27,7 +38,7 @@ pub struct CreateLeague<'info> {
     #[account(
       init,
       payer = creator,
-      space = 8 + 32 + (4 + 32 * 50) + 8 + 8 + 1 + 32 + 8 + 32 + (4 + 200) + 1 + 4 + 8 + 1 + 1,
+      space = Leaderboard::space(k),
       seeds = [b"leaderboard", league.key().as_ref()],
       bump
     )]
@@ -57,7 +68,13 @@ pub fn create_league(
     max_leverage: u8,
     nonce: u8,
     k: u16,
+    margin_mode: MarginMode,
+    margin_mode_selectable: bool,
+    position_mode: PositionMode,
+    max_open_positions: u8,
+    league_type: LeagueType,
 ) -> Result<()> {
+    require_protocol_not_paused(&ctx.accounts.global_state)?;
     // Validate markets vector size (max 10 markets)
     require!(
         markets.len() <= 10,
@@ -83,6 +100,18 @@ pub fn create_league(
         crate::errors::ErrorCode::InvalidLeaderboardSizeLimit
     );
 
+    // Check if the max open positions is valid, for now max is 25
+    require!(
+        max_open_positions > 0 && max_open_positions <= 25,
+        crate::errors::ErrorCode::InvalidMaxOpenPositions
+    );
+
+    // Spot leagues trade without leverage
+    require!(
+        league_type == LeagueType::Perp || max_leverage == 1,
+        crate::errors::ErrorCode::InvalidLeverage
+    );
+
     // Check if the ATA account exists and has data
     let ata_account_info = &ctx.accounts.reward_vault;
     if ata_account_info.data_is_empty() {
@@ -120,6 +149,13 @@ pub fn create_league(
     league.entry_amount = entry_amount;
     league.virtual_on_deposit = virtual_on_deposit;
     league.max_leverage = max_leverage;
+    league.margin_mode = margin_mode;
+    league.margin_mode_selectable = margin_mode_selectable;
+    league.position_mode = position_mode;
+    league.max_open_positions = max_open_positions;
+    league.league_type = league_type;
+    league.paused = false;
+    league.withdrawals_paused = false;
 
     league.reward_vault = ctx.accounts.reward_vault.key();
     league.total_reward_amount = 0; // Will be set when league is closed
@@ -127,6 +163,7 @@ pub fn create_league(
     league.status = LeagueStatus::Pending;
     league.max_participants = max_participants;
     league.bump = ctx.bumps.league;
+    league.version = League::VERSION;
 
     leaderboard.league = league.key();
     leaderboard.k = k;
@@ -138,6 +175,7 @@ pub fn create_league(
 
     leaderboard.last_updated = Clock::get()?.unix_timestamp;
     leaderboard.bump = ctx.bumps.leaderboard;
+    leaderboard.version = Leaderboard::VERSION;
 
     msg!("League created: {:?}", league.key());
 
@@ -146,6 +184,12 @@ pub fn create_league(
 
 #[derive(Accounts)]
 pub struct JoinLeague<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
     #[account(mut)]
     pub user: Signer<'info>,
 
@@ -155,7 +199,7 @@ pub struct JoinLeague<'info> {
     #[account(
         init_if_needed,
         payer = user,
-        space = 8 + 32 + 32 + 1 + 8 + 8 + 8 + 8 + 2 + 2 + 8 + (4 + 32 * 10) + 1,
+        space = Participant::space(league.max_open_positions),
         seeds = [b"participant", league.key().as_ref(), user.key().as_ref()],
         bump
     )]
@@ -186,6 +230,7 @@ pub fn join_league(ctx: Context<JoinLeague>, amount: i64) -> Result<()> {
         league.status == LeagueStatus::Active,
         crate::errors::ErrorCode::LeagueNotActive
     );
+    require_not_paused(&ctx.accounts.global_state, league)?;
     require!(
         amount >= league.entry_amount,
         crate::errors::ErrorCode::InsufficientEntryAmount
@@ -227,16 +272,52 @@ pub fn join_league(ctx: Context<JoinLeague>, amount: i64) -> Result<()> {
     participant.league = league.key();
     participant.user = ctx.accounts.user.key();
     participant.claimed = false;
+    participant.margin_mode = league.margin_mode.clone();
     participant.virtual_balance = league.virtual_on_deposit;
     participant.positions = Vec::new();
     participant.topk_equity_index = 0xFFFF;
     participant.topk_volume_index = 0xFFFF;
     participant.bump = ctx.bumps.participant;
+    participant.version = Participant::VERSION;
 
     msg!("User joined league: {:?}", league.key());
     Ok(())
 }
 
+#[derive(Accounts)]
+pub struct SetMarginMode<'info> {
+    pub user: Signer<'info>,
+
+    pub league: Account<'info, League>,
+
+    #[account(
+        mut,
+        seeds = [b"participant", league.key().as_ref(), user.key().as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+}
+
+pub fn set_margin_mode(ctx: Context<SetMarginMode>, margin_mode: MarginMode) -> Result<()> {
+    let league = &ctx.accounts.league;
+    let participant = &mut ctx.accounts.participant;
+
+    require!(
+        league.margin_mode_selectable,
+        crate::errors::ErrorCode::MarginModeNotSelectable
+    );
+    // Switching modes with open positions would change how they get liquidated
+    require!(
+        participant.positions.is_empty(),
+        crate::errors::ErrorCode::OpenPositionsExist
+    );
+
+    participant.margin_mode = margin_mode;
+
+    msg!("Participant {:?} switched margin mode", participant.key());
+    Ok(())
+}
+
 #[delegate]
 #[derive(Accounts)]
 pub struct DelegateParticipant<'info> {
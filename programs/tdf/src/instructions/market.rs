@@ -1,6 +1,14 @@
 use anchor_lang::prelude::*;
 
-use crate::state::{GlobalState, Market};
+use crate::events::MarketHalted;
+use crate::state::{GlobalState, Market, Role};
+use crate::utils::{calculate_price_move_bps, get_market_price, is_basket, MAX_PRICE_DECIMALS};
+
+/// Upper bound on the hourly borrow rate, 100% of the borrowed notional
+pub const MAX_BORROW_RATE_BPS: u16 = 10_000;
+
+/// Upper bound on the allowed gap between primary and fallback feeds
+pub const MAX_DIVERGENCE_BPS: u16 = 10_000;
 
 #[derive(Accounts)]
 pub struct ListMarket<'info> {
@@ -10,7 +18,7 @@ pub struct ListMarket<'info> {
     #[account(
         init,
         payer = admin,
-        space = 8 + 16 + 32 + 32 + 1 + 32 + 1 + 8 + 1 + 1,
+        space = Market::SPACE,
         seeds = [b"market", oracle_feed.key().as_ref()],
         bump
     )]
@@ -19,9 +27,12 @@ pub struct ListMarket<'info> {
     pub oracle_feed: AccountInfo<'info>,
     /// CHECK: Base currency account - validated by the token program
     pub base_currency: AccountInfo<'info>,
+    /// CHECK: FX feed, markets quoted in another currency than the league's only
+    pub fx_feed: Option<UncheckedAccount<'info>>,
     #[account(
         mut,
-        constraint = admin.key() == global_state.admin
+        constraint = global_state.has_role(&admin.key(), Role::MarketManager)
+            @ crate::errors::ErrorCode::Unauthorized
     )]
     pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
@@ -32,21 +43,527 @@ pub fn list_market(
     symbol: [u8; 16],
     decimals: u8,
     max_leverage: u8,
+    borrow_rate_bps: u16,
+    max_price_age: i64,
+    price_decimals: u8,
+    fx_price_decimals: u8,
 ) -> Result<()> {
-    let now = Clock::get()?.unix_timestamp;
+    let bump = ctx.bumps.market;
+    let market = &mut ctx.accounts.market;
+    init_market(
+        market,
+        symbol,
+        ctx.accounts.oracle_feed.key(),
+        None,
+        ctx.accounts
+            .fx_feed
+            .as_ref()
+            .map(|fx_feed| (fx_feed.key(), fx_price_decimals)),
+        ctx.accounts.base_currency.key(),
+        decimals,
+        ctx.accounts.admin.key(),
+        max_leverage,
+        borrow_rate_bps,
+        max_price_age,
+        price_decimals,
+        bump,
+    )?;
+
+    msg!("Market listed: {:?}", market.symbol);
+    Ok(())
+}
+
+/// Synthetic market priced as the ratio of two feeds, e.g. GOLD/BTC
+#[derive(Accounts)]
+pub struct ListPairMarket<'info> {
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = Market::SPACE,
+        seeds = [b"market", oracle_feed.key().as_ref(), denominator_feed.key().as_ref()],
+        bump
+    )]
+    pub market: Account<'info, Market>,
+    /// CHECK: Numerator oracle feed account - validated by the oracle program
+    pub oracle_feed: AccountInfo<'info>,
+    /// CHECK: Denominator oracle feed account - validated by the oracle program
+    #[account(constraint = denominator_feed.key() != oracle_feed.key())]
+    pub denominator_feed: AccountInfo<'info>,
+    /// CHECK: Base currency account - validated by the token program
+    pub base_currency: AccountInfo<'info>,
+    /// CHECK: FX feed, markets quoted in another currency than the league's only
+    pub fx_feed: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        constraint = global_state.has_role(&admin.key(), Role::MarketManager)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
 
+pub fn list_pair_market(
+    ctx: Context<ListPairMarket>,
+    symbol: [u8; 16],
+    decimals: u8,
+    max_leverage: u8,
+    borrow_rate_bps: u16,
+    max_price_age: i64,
+    price_decimals: u8,
+    denominator_price_decimals: u8,
+    fx_price_decimals: u8,
+) -> Result<()> {
     let bump = ctx.bumps.market;
     let market = &mut ctx.accounts.market;
+    init_market(
+        market,
+        symbol,
+        ctx.accounts.oracle_feed.key(),
+        Some((
+            ctx.accounts.denominator_feed.key(),
+            denominator_price_decimals,
+        )),
+        ctx.accounts
+            .fx_feed
+            .as_ref()
+            .map(|fx_feed| (fx_feed.key(), fx_price_decimals)),
+        ctx.accounts.base_currency.key(),
+        decimals,
+        ctx.accounts.admin.key(),
+        max_leverage,
+        borrow_rate_bps,
+        max_price_age,
+        price_decimals,
+        bump,
+    )?;
+
+    msg!("Pair market listed: {:?}", market.symbol);
+    Ok(())
+}
+
+/// Open positions are always priced through the market's current config, so the feeds
+/// that define what the price means (oracle, denominator, fx) can only be set here
+pub(crate) fn init_market(
+    market: &mut Market,
+    symbol: [u8; 16],
+    oracle_feed: Pubkey,
+    denominator_feed: Option<(Pubkey, u8)>,
+    fx_feed: Option<(Pubkey, u8)>,
+    base_currency: Pubkey,
+    decimals: u8,
+    listed_by: Pubkey,
+    max_leverage: u8,
+    borrow_rate_bps: u16,
+    max_price_age: i64,
+    price_decimals: u8,
+    bump: u8,
+) -> Result<()> {
+    require!(
+        borrow_rate_bps <= MAX_BORROW_RATE_BPS,
+        crate::errors::ErrorCode::InvalidBorrowRate
+    );
+    require!(
+        max_price_age >= 0,
+        crate::errors::ErrorCode::InvalidPriceAge
+    );
+    require_price_decimals(price_decimals)?;
+    for (_, feed_decimals) in denominator_feed.iter().chain(fx_feed.iter()) {
+        require_price_decimals(*feed_decimals)?;
+    }
+
     market.symbol = symbol;
-    market.oracle_feed = ctx.accounts.oracle_feed.key();
-    market.base_currency = ctx.accounts.base_currency.key();
+    market.oracle_feed = oracle_feed;
+    market.base_currency = base_currency;
     market.decimals = decimals;
-    market.listed_by = ctx.accounts.admin.key();
+    market.listed_by = listed_by;
     market.is_active = true;
-    market.created_at = now;
+    market.created_at = Clock::get()?.unix_timestamp;
     market.max_leverage = max_leverage;
+    market.borrow_rate_bps = borrow_rate_bps;
+    market.denominator_feed = denominator_feed.map(|(feed, _)| feed);
+    market.max_price_age = max_price_age;
+    market.corporate_action_count = 0;
+    market.fx_feed = fx_feed.map(|(feed, _)| feed);
+    market.halt_threshold_bps = 0;
+    market.halt_window = 0;
+    market.halt_cooldown = 0;
+    market.reference_price = 0;
+    market.reference_price_ts = 0;
+    market.last_observed_price = 0;
+    market.last_observed_ts = 0;
+    market.halted_until = 0;
+    market.fallback_feed = None;
+    market.max_divergence_bps = 0;
+    market.price_decimals = price_decimals;
+    market.denominator_price_decimals = denominator_feed.map_or(0, |(_, decimals)| decimals);
+    market.fallback_price_decimals = 0;
+    market.fx_price_decimals = fx_feed.map_or(0, |(_, decimals)| decimals);
+    market.restricted = false;
     market.bump = bump;
+    market.version = Market::VERSION;
+    Ok(())
+}
 
-    msg!("Market listed: {:?}", market.symbol);
+/// Feeds quote with at most `MAX_PRICE_DECIMALS` decimals
+pub(crate) fn require_price_decimals(price_decimals: u8) -> Result<()> {
+    require!(
+        price_decimals <= MAX_PRICE_DECIMALS,
+        crate::errors::ErrorCode::InvalidPriceDecimals
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetBorrowRate<'info> {
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        constraint = global_state.has_role(&admin.key(), Role::FeeManager)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
+
+/// Update the market's hourly borrow rate. Positions keep the rate they were opened at.
+pub fn set_borrow_rate(ctx: Context<SetBorrowRate>, borrow_rate_bps: u16) -> Result<()> {
+    require!(
+        borrow_rate_bps <= MAX_BORROW_RATE_BPS,
+        crate::errors::ErrorCode::InvalidBorrowRate
+    );
+    let market = &mut ctx.accounts.market;
+    market.borrow_rate_bps = borrow_rate_bps;
+
+    msg!(
+        "Borrow rate of {:?} set to {} bps/h",
+        market.symbol,
+        borrow_rate_bps
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetFallbackFeed<'info> {
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    /// CHECK: the market's primary feed, only read to tell a basket apart
+    #[account(address = market.oracle_feed)]
+    pub oracle_feed: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = global_state.has_role(&admin.key(), Role::MarketManager)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
+
+/// Set or clear the secondary feed used when the market's primary feed is stale or halted.
+/// While both are live, a gap above `max_divergence_bps` rejects the price (0 = unchecked).
+pub fn set_fallback_feed(
+    ctx: Context<SetFallbackFeed>,
+    fallback_feed: Option<Pubkey>,
+    max_divergence_bps: u16,
+    fallback_price_decimals: u8,
+) -> Result<()> {
+    let market = &mut ctx.accounts.market;
+    if let Some(fallback_feed) = fallback_feed {
+        require_fallback_feed(
+            market,
+            is_basket(&ctx.accounts.oracle_feed),
+            fallback_feed,
+            fallback_price_decimals,
+        )?;
+    }
+    require!(
+        max_divergence_bps <= MAX_DIVERGENCE_BPS,
+        crate::errors::ErrorCode::InvalidFallbackFeed
+    );
+    market.fallback_feed = fallback_feed;
+    market.max_divergence_bps = max_divergence_bps;
+    market.fallback_price_decimals = fallback_feed.map_or(0, |_| fallback_price_decimals);
+
+    msg!(
+        "Fallback feed of {:?} set to {:?}, max divergence {} bps",
+        market.symbol,
+        fallback_feed,
+        max_divergence_bps
+    );
+    Ok(())
+}
+
+/// A fallback only stands in for a feed that can go stale. Baskets price from their
+/// constituents and take no fallback, and without `max_price_age` the primary is never stale.
+fn require_fallback_feed(
+    market: &Market,
+    oracle_feed_is_basket: bool,
+    fallback_feed: Pubkey,
+    fallback_price_decimals: u8,
+) -> Result<()> {
+    require!(
+        !oracle_feed_is_basket
+            && market.max_price_age > 0
+            && fallback_feed != market.oracle_feed
+            && Some(fallback_feed) != market.denominator_feed,
+        crate::errors::ErrorCode::InvalidFallbackFeed
+    );
+    require_price_decimals(fallback_price_decimals)
+}
+
+#[derive(Accounts)]
+pub struct SetHaltConfig<'info> {
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        constraint = global_state.has_role(&admin.key(), Role::MarketManager)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
+
+/// Halt new exposure for `halt_cooldown` seconds once the price moves more than
+/// `halt_threshold_bps` within `halt_window` seconds. A threshold of 0 disables halts.
+/// Halts need a keeper cranking `observe_market_price` at least every `halt_window`
+/// seconds: trades check their price against the reference price but never move it.
+pub fn set_halt_config(
+    ctx: Context<SetHaltConfig>,
+    halt_threshold_bps: u16,
+    halt_window: i64,
+    halt_cooldown: i64,
+) -> Result<()> {
+    require!(
+        halt_window >= 0 && halt_cooldown >= 0,
+        crate::errors::ErrorCode::InvalidHaltConfig
+    );
+    require!(
+        halt_threshold_bps == 0 || halt_window > 0,
+        crate::errors::ErrorCode::InvalidHaltConfig
+    );
+    let market = &mut ctx.accounts.market;
+    market.halt_threshold_bps = halt_threshold_bps;
+    market.halt_window = halt_window;
+    market.halt_cooldown = halt_cooldown;
+
+    msg!(
+        "Halt config of {:?} set to {} bps over {}s, cool-down {}s",
+        market.symbol,
+        halt_threshold_bps,
+        halt_window,
+        halt_cooldown
+    );
+    Ok(())
+}
+
+/// Crank, records the market price and halts the market if it moved past the threshold.
+/// This is the only thing that moves the reference price, without it no halt triggers.
+#[derive(Accounts)]
+pub struct ObserveMarketPrice<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    /// CHECK: oracle feed from market
+    #[account(address = market.oracle_feed)]
+    pub oracle_feed: AccountInfo<'info>,
+    /// CHECK: denominator feed from market, pair markets only
+    pub denominator_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fallback feed from market, markets with one only
+    pub fallback_feed: Option<UncheckedAccount<'info>>,
+    // remaining accounts = basket constituent feeds, basket markets only
+}
+
+/// While halted, opening and increasing (including DCA increases and one-way flips) fail with
+/// `MarketHalted`. Decreases, closes, margin changes and liquidations keep working.
+pub fn observe_market_price(ctx: Context<ObserveMarketPrice>) -> Result<()> {
+    let market = &mut ctx.accounts.market;
+    let now = Clock::get()?.unix_timestamp;
+
+    let price = get_market_price(
+        &ctx.accounts.oracle_feed,
+        ctx.accounts.denominator_feed.as_deref(),
+        ctx.accounts.fallback_feed.as_deref(),
+        ctx.remaining_accounts,
+        &market.price_config(),
+        now,
+    )?;
+
+    if record_observation(market, price, now)? {
+        emit!(MarketHalted {
+            market: market.key(),
+            reference_price: market.reference_price,
+            price,
+            halted_until: market.halted_until,
+        });
+        msg!(
+            "Market {:?} halted until {}",
+            market.symbol,
+            market.halted_until
+        );
+    }
+    Ok(())
+}
+
+/// Record `price` as the latest observation, returns whether it halted the market
+fn record_observation(market: &mut Market, price: i64, now: i64) -> Result<bool> {
+    let halted = volatility_halt_triggered(market, price, now)? && now >= market.halted_until;
+    if halted {
+        market.halted_until = now
+            .checked_add(market.halt_cooldown)
+            .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    }
+    // A halt or an expired window starts a new window from this price
+    if now < market.halted_until || !reference_price_live(market, now) {
+        market.reference_price = price;
+        market.reference_price_ts = now;
+    }
+    market.last_observed_price = price;
+    market.last_observed_ts = now;
+    Ok(halted)
+}
+
+#[derive(Accounts)]
+pub struct ResumeMarket<'info> {
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        constraint = global_state.has_role(&admin.key(), Role::Pauser)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
+
+/// Lift a halt before its cool-down ends. The next observation seeds a new reference price.
+pub fn resume_market(ctx: Context<ResumeMarket>) -> Result<()> {
+    let market = &mut ctx.accounts.market;
+    clear_halt(market);
+
+    msg!("Market {:?} resumed", market.symbol);
     Ok(())
 }
+
+fn clear_halt(market: &mut Market) {
+    market.halted_until = 0;
+    market.reference_price = 0;
+    market.reference_price_ts = 0;
+}
+
+fn reference_price_live(market: &Market, now: i64) -> bool {
+    market.reference_price > 0
+        && now.saturating_sub(market.reference_price_ts) <= market.halt_window
+}
+
+/// Whether `price` has moved past the halt threshold from the market's live reference price
+pub(crate) fn volatility_halt_triggered(market: &Market, price: i64, now: i64) -> Result<bool> {
+    if market.halt_threshold_bps == 0 || !reference_price_live(market, now) {
+        return Ok(false);
+    }
+    let move_bps = calculate_price_move_bps(market.reference_price, price)?;
+    Ok(move_bps > market.halt_threshold_bps as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ErrorCode;
+    use crate::instructions::position::require_market_not_halted;
+
+    fn market() -> Market {
+        let mut market =
+            Market::try_deserialize_unchecked(&mut &vec![0u8; Market::SPACE][..]).unwrap();
+        market.is_active = true;
+        // 10% within a minute halts for five minutes
+        market.halt_threshold_bps = 1_000;
+        market.halt_window = 60;
+        market.halt_cooldown = 300;
+        market
+    }
+
+    #[test]
+    fn fallback_needs_a_feed_that_can_go_stale() {
+        let mut market = market();
+        market.oracle_feed = Pubkey::new_unique();
+        let fallback_feed = Pubkey::new_unique();
+
+        // Without a max price age the primary is never stale
+        let err = require_fallback_feed(&market, false, fallback_feed, 6).unwrap_err();
+        assert_eq!(err, ErrorCode::InvalidFallbackFeed.into());
+
+        market.max_price_age = 60;
+        require_fallback_feed(&market, false, fallback_feed, 6).unwrap();
+        let err = require_fallback_feed(&market, true, fallback_feed, 6).unwrap_err();
+        assert_eq!(err, ErrorCode::InvalidFallbackFeed.into());
+        let err = require_fallback_feed(&market, false, market.oracle_feed, 6).unwrap_err();
+        assert_eq!(err, ErrorCode::InvalidFallbackFeed.into());
+    }
+
+    #[test]
+    fn observing_a_move_past_the_threshold_halts() {
+        let mut market = market();
+        assert!(!record_observation(&mut market, 100_000_000, 0).unwrap());
+        assert_eq!(market.reference_price, 100_000_000);
+
+        // 9% stays inside the threshold and keeps the reference
+        assert!(!record_observation(&mut market, 109_000_000, 30).unwrap());
+        assert_eq!(market.reference_price, 100_000_000);
+
+        assert!(record_observation(&mut market, 111_000_000, 40).unwrap());
+        assert_eq!(market.halted_until, 340);
+        let err = require_market_not_halted(&market, 111_000_000, 41).unwrap_err();
+        assert_eq!(err, ErrorCode::MarketHalted.into());
+    }
+
+    #[test]
+    fn trades_are_checked_against_the_observed_reference() {
+        let mut market = market();
+        record_observation(&mut market, 100_000_000, 0).unwrap();
+        // Before any crank notices, a trade at a price past the threshold is refused
+        let err = require_market_not_halted(&market, 80_000_000, 10).unwrap_err();
+        assert_eq!(err, ErrorCode::MarketHalted.into());
+        // Once the reference is older than the window nothing is compared against it
+        require_market_not_halted(&market, 80_000_000, 61).unwrap();
+    }
+
+    #[test]
+    fn halt_lasts_for_the_cooldown() {
+        let mut market = market();
+        record_observation(&mut market, 100_000_000, 0).unwrap();
+        record_observation(&mut market, 120_000_000, 10).unwrap();
+        assert_eq!(market.halted_until, 310);
+
+        // Observations during the halt move the reference along without extending it
+        assert!(!record_observation(&mut market, 140_000_000, 200).unwrap());
+        assert_eq!(market.halted_until, 310);
+        assert_eq!(market.reference_price, 140_000_000);
+        let err = require_market_not_halted(&market, 140_000_000, 309).unwrap_err();
+        assert_eq!(err, ErrorCode::MarketHalted.into());
+
+        require_market_not_halted(&market, 140_000_000, 310).unwrap();
+    }
+
+    #[test]
+    fn resume_lifts_the_halt_and_reseeds_the_reference() {
+        let mut market = market();
+        record_observation(&mut market, 100_000_000, 0).unwrap();
+        record_observation(&mut market, 120_000_000, 10).unwrap();
+
+        clear_halt(&mut market);
+        require_market_not_halted(&market, 120_000_000, 11).unwrap();
+        // The next observation starts a new window instead of comparing to the old price
+        assert!(!record_observation(&mut market, 125_000_000, 12).unwrap());
+        assert_eq!(market.reference_price, 125_000_000);
+        assert_eq!(market.reference_price_ts, 12);
+    }
+}
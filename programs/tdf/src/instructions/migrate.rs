@@ -0,0 +1,874 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_lang::Discriminator;
+
+use crate::events::AccountMigrated;
+use crate::state::{
+    Direction, GlobalState, Leaderboard, League, LeagueStatus, LeagueType, MarginMode, Market,
+    Participant, Position, PositionMode,
+};
+use crate::utils::{calculate_required_margin, PRICE_DECIMALS};
+
+/// Accounts created before versioning use the original layouts below, where later fields
+/// were added ahead of `bump`. Those accounts still have exactly the size the original
+/// program allocated, which no new account has, so a migration recognizes them by size and
+/// a version of 0, decodes them through the `*V0` struct and maps them field by field.
+/// Accounts already in the current layout are grown if needed and stamped with the current
+/// version.
+/// Anyone can migrate an account, the payer covers the extra rent.
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    /// CHECK: owner and discriminator are checked before it is touched, it may not
+    /// deserialize until it has been grown
+    #[account(mut)]
+    pub account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// The league has to be migrated first, participants are sized for its `max_open_positions`
+#[derive(Accounts)]
+pub struct MigrateParticipant<'info> {
+    /// CHECK: see MigrateAccount
+    #[account(mut)]
+    pub participant: UncheckedAccount<'info>,
+
+    pub league: Account<'info, League>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(AnchorDeserialize)]
+struct GlobalStateV0 {
+    admin: Pubkey,
+    fee_bps: u16,
+    treasury: Pubkey,
+    permission_program: Pubkey,
+    bump: u8,
+}
+
+impl GlobalStateV0 {
+    const SPACE: usize = 8 + 32 + 2 + 32 + 32 + 1;
+}
+
+#[derive(AnchorDeserialize)]
+struct MarketV0 {
+    symbol: [u8; 16],
+    oracle_feed: Pubkey,
+    base_currency: Pubkey,
+    decimals: u8,
+    listed_by: Pubkey,
+    is_active: bool,
+    created_at: i64,
+    max_leverage: u8,
+    bump: u8,
+}
+
+impl MarketV0 {
+    const SPACE: usize = 8 + 16 + 32 + 32 + 1 + 32 + 1 + 8 + 1 + 1;
+}
+
+#[derive(AnchorDeserialize)]
+struct LeagueV0 {
+    creator: Pubkey,
+    markets: Vec<Pubkey>,
+    start_ts: i64,
+    end_ts: i64,
+    nonce: u8,
+    entry_token_mint: Pubkey,
+    entry_amount: i64,
+    reward_vault: Pubkey,
+    total_reward_amount: u64,
+    metadata_uri: String,
+    status: LeagueStatus,
+    max_participants: u32,
+    virtual_on_deposit: i64,
+    max_leverage: u8,
+    bump: u8,
+}
+
+impl LeagueV0 {
+    const SPACE: usize =
+        8 + 32 + (4 + 32 * 50) + 8 + 8 + 1 + 32 + 8 + 32 + 8 + (4 + 200) + 1 + 4 + 8 + 1 + 1;
+    // Participants held at most this many positions
+    const MAX_OPEN_POSITIONS: u8 = 10;
+}
+
+#[derive(AnchorDeserialize)]
+struct ParticipantV0 {
+    league: Pubkey,
+    user: Pubkey,
+    claimed: bool,
+    virtual_balance: i64,
+    unrealized_pnl: i64,
+    used_margin: i64,
+    total_volume: i64,
+    topk_equity_index: u16,
+    topk_volume_index: u16,
+    current_position_seq: u64,
+    positions: Vec<Pubkey>,
+    bump: u8,
+}
+
+impl ParticipantV0 {
+    const SPACE: usize = 8 + 32 + 32 + 1 + 8 + 8 + 8 + 8 + 2 + 2 + 8 + (4 + 32 * 10) + 1;
+}
+
+#[derive(AnchorDeserialize)]
+struct PositionV0 {
+    league: Pubkey,
+    user: Pubkey,
+    market: Pubkey,
+    market_decimals: u8,
+    oracle_feed: Pubkey,
+    seq_num: u64,
+    direction: Direction,
+    entry_price: i64,
+    entry_size: i64,
+    leverage: u8,
+    size: i64,
+    notional: i64,
+    unrealized_pnl: i64,
+    opened_at: i64,
+    closed_at: i64,
+    closed_size: i64,
+    closed_price: i64,
+    closed_equity: i64,
+    closed_pnl: i64,
+    bump: u8,
+}
+
+impl PositionV0 {
+    // Allocated for five keys though it only ever held four
+    const SPACE: usize = 8 + 32 * 5 + 8 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1;
+}
+
+pub fn migrate_global_state(ctx: Context<MigrateAccount>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let is_v0 = is_original_layout::<GlobalState>(
+        &accounts.account,
+        GlobalStateV0::SPACE,
+        |global_state| global_state.version,
+    );
+    grow_account::<GlobalState>(
+        &accounts.account,
+        &accounts.payer,
+        &accounts.system_program,
+        GlobalState::SPACE,
+    )?;
+
+    let mut global_state = if is_v0 {
+        global_state_from_v0(&accounts.account.try_borrow_data()?)?
+    } else {
+        read_account::<GlobalState>(&accounts.account)?
+    };
+    global_state.version = GlobalState::VERSION;
+    write_account(&accounts.account, &global_state)?;
+
+    emit!(AccountMigrated {
+        account: accounts.account.key(),
+        version: GlobalState::VERSION,
+    });
+    Ok(())
+}
+
+pub fn migrate_market(ctx: Context<MigrateAccount>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let is_v0 =
+        is_original_layout::<Market>(&accounts.account, MarketV0::SPACE, |market| market.version);
+    grow_account::<Market>(
+        &accounts.account,
+        &accounts.payer,
+        &accounts.system_program,
+        Market::SPACE,
+    )?;
+
+    let mut market = if is_v0 {
+        market_from_v0(&accounts.account.try_borrow_data()?)?
+    } else {
+        read_account::<Market>(&accounts.account)?
+    };
+    market.version = Market::VERSION;
+    write_account(&accounts.account, &market)?;
+
+    emit!(AccountMigrated {
+        account: accounts.account.key(),
+        version: Market::VERSION,
+    });
+    Ok(())
+}
+
+pub fn migrate_league(ctx: Context<MigrateAccount>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    // The original league is larger than the current one, growing leaves it as it is and
+    // only its version tells a migrated league apart
+    let is_v0 =
+        is_original_layout::<League>(&accounts.account, LeagueV0::SPACE, |league| league.version);
+    grow_account::<League>(
+        &accounts.account,
+        &accounts.payer,
+        &accounts.system_program,
+        League::SPACE,
+    )?;
+
+    let mut league = if is_v0 {
+        league_from_v0(&accounts.account.try_borrow_data()?)?
+    } else {
+        read_account::<League>(&accounts.account)?
+    };
+    league.version = League::VERSION;
+    write_account(&accounts.account, &league)?;
+
+    emit!(AccountMigrated {
+        account: accounts.account.key(),
+        version: League::VERSION,
+    });
+    Ok(())
+}
+
+/// The leaderboard layout only gained `version`, and original leaderboards were allocated
+/// with room to spare, so they read through the current layout as they are.
+pub fn migrate_leaderboard(ctx: Context<MigrateAccount>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    // Leaderboards used to be sized for fewer entries than k allows. Whatever they hold still
+    // fits, so k can be read before growing them to match it.
+    let k = read_account::<Leaderboard>(&accounts.account)?.k;
+    grow_account::<Leaderboard>(
+        &accounts.account,
+        &accounts.payer,
+        &accounts.system_program,
+        Leaderboard::space(k),
+    )?;
+
+    let mut leaderboard: Leaderboard = read_account(&accounts.account)?;
+    leaderboard.version = Leaderboard::VERSION;
+    write_account(&accounts.account, &leaderboard)?;
+
+    emit!(AccountMigrated {
+        account: accounts.account.key(),
+        version: Leaderboard::VERSION,
+    });
+    Ok(())
+}
+
+pub fn migrate_participant(ctx: Context<MigrateParticipant>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let is_v0 = is_original_layout::<Participant>(
+        &accounts.participant,
+        ParticipantV0::SPACE,
+        |participant| participant.version,
+    );
+    grow_account::<Participant>(
+        &accounts.participant,
+        &accounts.payer,
+        &accounts.system_program,
+        Participant::space(accounts.league.max_open_positions),
+    )?;
+
+    let mut participant = if is_v0 {
+        participant_from_v0(
+            &accounts.participant.try_borrow_data()?,
+            &accounts.league.margin_mode,
+        )?
+    } else {
+        read_account::<Participant>(&accounts.participant)?
+    };
+    require_keys_eq!(
+        participant.league,
+        accounts.league.key(),
+        crate::errors::ErrorCode::LeagueMismatch
+    );
+    participant.version = Participant::VERSION;
+    write_account(&accounts.participant, &participant)?;
+
+    emit!(AccountMigrated {
+        account: accounts.participant.key(),
+        version: Participant::VERSION,
+    });
+    Ok(())
+}
+
+pub fn migrate_position(ctx: Context<MigrateAccount>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let is_v0 = is_original_layout::<Position>(&accounts.account, PositionV0::SPACE, |position| {
+        position.version
+    });
+    grow_account::<Position>(
+        &accounts.account,
+        &accounts.payer,
+        &accounts.system_program,
+        Position::SPACE,
+    )?;
+
+    let mut position = if is_v0 {
+        position_from_v0(&accounts.account.try_borrow_data()?)?
+    } else {
+        read_account::<Position>(&accounts.account)?
+    };
+    position.version = Position::VERSION;
+    write_account(&accounts.account, &position)?;
+
+    emit!(AccountMigrated {
+        account: accounts.account.key(),
+        version: Position::VERSION,
+    });
+    Ok(())
+}
+
+// The `*_from_v0` mappings take the whole account data, discriminator included, and leave
+// `version` at 0 for the caller to stamp.
+
+fn global_state_from_v0(data: &[u8]) -> Result<GlobalState> {
+    let v0 = GlobalStateV0::deserialize(&mut &data[8..])?;
+    Ok(GlobalState {
+        admin: v0.admin,
+        fee_bps: v0.fee_bps,
+        treasury: v0.treasury,
+        permission_program: v0.permission_program,
+        listing_bond_mint: Pubkey::default(),
+        listing_bond_amount: 0,
+        roles: Vec::new(),
+        pending_admin: None,
+        paused: false,
+        withdrawals_paused: false,
+        bump: v0.bump,
+        version: 0,
+    })
+}
+
+fn market_from_v0(data: &[u8]) -> Result<Market> {
+    let v0 = MarketV0::deserialize(&mut &data[8..])?;
+    Ok(Market {
+        symbol: v0.symbol,
+        oracle_feed: v0.oracle_feed,
+        base_currency: v0.base_currency,
+        decimals: v0.decimals,
+        listed_by: v0.listed_by,
+        is_active: v0.is_active,
+        created_at: v0.created_at,
+        max_leverage: v0.max_leverage,
+        borrow_rate_bps: 0,
+        denominator_feed: None,
+        max_price_age: 0,
+        corporate_action_count: 0,
+        fx_feed: None,
+        halt_threshold_bps: 0,
+        halt_window: 0,
+        halt_cooldown: 0,
+        reference_price: 0,
+        reference_price_ts: 0,
+        last_observed_price: 0,
+        last_observed_ts: 0,
+        halted_until: 0,
+        fallback_feed: None,
+        max_divergence_bps: 0,
+        // Every feed quoted 1e6 before markets carried their own price decimals
+        price_decimals: PRICE_DECIMALS,
+        denominator_price_decimals: 0,
+        fallback_price_decimals: 0,
+        fx_price_decimals: 0,
+        restricted: false,
+        bump: v0.bump,
+        version: 0,
+    })
+}
+
+fn league_from_v0(data: &[u8]) -> Result<League> {
+    let v0 = LeagueV0::deserialize(&mut &data[8..])?;
+    Ok(League {
+        creator: v0.creator,
+        markets: v0.markets,
+        start_ts: v0.start_ts,
+        end_ts: v0.end_ts,
+        nonce: v0.nonce,
+        entry_token_mint: v0.entry_token_mint,
+        entry_amount: v0.entry_amount,
+        reward_vault: v0.reward_vault,
+        total_reward_amount: v0.total_reward_amount,
+        metadata_uri: v0.metadata_uri,
+        status: v0.status,
+        max_participants: v0.max_participants,
+        virtual_on_deposit: v0.virtual_on_deposit,
+        max_leverage: v0.max_leverage,
+        // Original leagues behaved as cross margin, hedge mode perp leagues
+        margin_mode: MarginMode::Cross,
+        margin_mode_selectable: false,
+        position_mode: PositionMode::Hedge,
+        max_open_positions: LeagueV0::MAX_OPEN_POSITIONS,
+        league_type: LeagueType::Perp,
+        paused: false,
+        withdrawals_paused: false,
+        bump: v0.bump,
+        version: 0,
+    })
+}
+
+fn participant_from_v0(data: &[u8], margin_mode: &MarginMode) -> Result<Participant> {
+    let v0 = ParticipantV0::deserialize(&mut &data[8..])?;
+    Ok(Participant {
+        league: v0.league,
+        user: v0.user,
+        claimed: v0.claimed,
+        margin_mode: margin_mode.clone(),
+        virtual_balance: v0.virtual_balance,
+        unrealized_pnl: v0.unrealized_pnl,
+        used_margin: v0.used_margin,
+        binary_stake: 0,
+        total_volume: v0.total_volume,
+        closed_positions: 0,
+        winning_positions: 0,
+        realized_pnl: 0,
+        topk_equity_index: v0.topk_equity_index,
+        topk_volume_index: v0.topk_volume_index,
+        current_position_seq: v0.current_position_seq,
+        positions: v0.positions,
+        bump: v0.bump,
+        version: 0,
+    })
+}
+
+fn position_from_v0(data: &[u8]) -> Result<Position> {
+    let v0 = PositionV0::deserialize(&mut &data[8..])?;
+    // Open positions locked notional / leverage, the same as fill_position does now
+    let margin = if v0.size > 0 {
+        calculate_required_margin(v0.notional, v0.leverage)?
+    } else {
+        0
+    };
+    Ok(Position {
+        league: v0.league,
+        user: v0.user,
+        market: v0.market,
+        market_decimals: v0.market_decimals,
+        oracle_feed: v0.oracle_feed,
+        corporate_actions_applied: 0,
+        seq_num: v0.seq_num,
+        direction: v0.direction,
+        entry_price: v0.entry_price,
+        entry_size: v0.entry_size,
+        leverage: v0.leverage,
+        margin,
+        size: v0.size,
+        notional: v0.notional,
+        unrealized_pnl: v0.unrealized_pnl,
+        // Estimates, recomputed the next time the position is touched
+        liquidation_price: 0,
+        cross_liquidation_price: 0,
+        // No borrow fee was charged before markets had a rate
+        borrow_rate_bps: 0,
+        last_fee_accrual_ts: v0.opened_at,
+        borrow_fees_paid: 0,
+        opened_at: v0.opened_at,
+        closed_at: v0.closed_at,
+        closed_size: v0.closed_size,
+        closed_price: v0.closed_price,
+        closed_equity: v0.closed_equity,
+        closed_pnl: v0.closed_pnl,
+        bump: v0.bump,
+        version: 0,
+    })
+}
+
+/// Grow a `T` account to `space`, topping up rent from the payer. Accounts already that
+/// large are left alone, older layouts were sometimes sized above what they need now.
+fn grow_account<'info, T: Discriminator>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+) -> Result<()> {
+    require_keys_eq!(
+        *account.owner,
+        crate::ID,
+        crate::errors::ErrorCode::AccountNotMigratable
+    );
+    require!(
+        account.try_borrow_data()?.starts_with(T::DISCRIMINATOR),
+        crate::errors::ErrorCode::AccountNotMigratable
+    );
+    if account.data_len() >= space {
+        return Ok(());
+    }
+
+    let lamports_needed = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if lamports_needed > 0 {
+        transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            lamports_needed,
+        )?;
+    }
+    account.resize(space)?;
+    Ok(())
+}
+
+/// Original accounts never read through the current layout with a version set: either the
+/// fields that moved fail to decode, or the trailing `version` lands in the zeroed rest of
+/// the allocation.
+fn is_original_layout<T: AccountDeserialize>(
+    account: &AccountInfo,
+    original_space: usize,
+    version: fn(&T) -> u8,
+) -> bool {
+    account.data_len() == original_space
+        && read_account::<T>(account).map_or(true, |current| version(&current) == 0)
+}
+
+fn read_account<T: AccountDeserialize>(account: &AccountInfo) -> Result<T> {
+    T::try_deserialize(&mut &account.try_borrow_data()?[..])
+}
+
+fn write_account<T: AccountSerialize>(account: &AccountInfo, value: &T) -> Result<()> {
+    let mut data = account.try_borrow_mut_data()?;
+    let mut dst = &mut data[..];
+    value.try_serialize(&mut dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Account data as the original program wrote it: discriminator, then the fields in
+    /// order, then the rest of the allocation zeroed
+    fn original_account<T: Discriminator>(fields: &[&[u8]], space: usize) -> Vec<u8> {
+        let mut data = T::DISCRIMINATOR.to_vec();
+        for field in fields {
+            data.extend_from_slice(field);
+        }
+        assert!(data.len() <= space);
+        data.resize(space, 0);
+        data
+    }
+
+    /// Write a migrated account into `len` bytes and read it back through the current layout
+    fn round_trip<T: AccountSerialize + AccountDeserialize>(value: &T, len: usize) -> T {
+        let mut data = vec![0u8; len];
+        value.try_serialize(&mut &mut data[..]).unwrap();
+        T::try_deserialize(&mut &data[..]).unwrap()
+    }
+
+    #[test]
+    fn original_sizes_are_never_current_sizes() {
+        assert_ne!(GlobalStateV0::SPACE, GlobalState::SPACE);
+        assert_ne!(MarketV0::SPACE, Market::SPACE);
+        assert_ne!(LeagueV0::SPACE, League::SPACE);
+        assert_ne!(PositionV0::SPACE, Position::SPACE);
+        for max_open_positions in 0..=u8::MAX {
+            assert_ne!(ParticipantV0::SPACE, Participant::space(max_open_positions));
+        }
+    }
+
+    #[test]
+    fn global_state_maps_from_the_original_layout() {
+        let admin = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+        let permission_program = Pubkey::new_unique();
+        let data = original_account::<GlobalState>(
+            &[
+                admin.as_ref(),
+                &250u16.to_le_bytes(),
+                treasury.as_ref(),
+                permission_program.as_ref(),
+                &[254],
+            ],
+            GlobalStateV0::SPACE,
+        );
+
+        let global_state = round_trip(&global_state_from_v0(&data).unwrap(), GlobalState::SPACE);
+        assert_eq!(global_state.admin, admin);
+        assert_eq!(global_state.fee_bps, 250);
+        assert_eq!(global_state.treasury, treasury);
+        assert_eq!(global_state.permission_program, permission_program);
+        assert_eq!(global_state.bump, 254);
+        assert!(global_state.roles.is_empty());
+        assert!(global_state.pending_admin.is_none());
+        assert!(!global_state.paused && !global_state.withdrawals_paused);
+    }
+
+    #[test]
+    fn market_maps_from_the_original_layout() {
+        let symbol = *b"SOL/USDC\0\0\0\0\0\0\0\0";
+        let oracle_feed = Pubkey::new_unique();
+        let base_currency = Pubkey::new_unique();
+        let listed_by = Pubkey::new_unique();
+        let data = original_account::<Market>(
+            &[
+                &symbol,
+                oracle_feed.as_ref(),
+                base_currency.as_ref(),
+                &[9],
+                listed_by.as_ref(),
+                &[1],
+                &1_700_000_000i64.to_le_bytes(),
+                &[20],
+                &[253],
+            ],
+            MarketV0::SPACE,
+        );
+
+        let market = round_trip(&market_from_v0(&data).unwrap(), Market::SPACE);
+        assert_eq!(market.symbol, symbol);
+        assert_eq!(market.oracle_feed, oracle_feed);
+        assert_eq!(market.base_currency, base_currency);
+        assert_eq!(market.decimals, 9);
+        assert_eq!(market.listed_by, listed_by);
+        assert!(market.is_active);
+        assert_eq!(market.created_at, 1_700_000_000);
+        assert_eq!(market.max_leverage, 20);
+        assert_eq!(market.bump, 253);
+        assert_eq!(market.price_decimals, PRICE_DECIMALS);
+        assert_eq!(market.borrow_rate_bps, 0);
+        assert!(market.denominator_feed.is_none() && market.fx_feed.is_none());
+        assert!(!market.restricted);
+    }
+
+    #[test]
+    fn league_maps_from_the_original_layout() {
+        let creator = Pubkey::new_unique();
+        let markets = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let entry_token_mint = Pubkey::new_unique();
+        let reward_vault = Pubkey::new_unique();
+        let uri = b"https://example.com/league.json";
+        let data = original_account::<League>(
+            &[
+                creator.as_ref(),
+                &2u32.to_le_bytes(),
+                markets[0].as_ref(),
+                markets[1].as_ref(),
+                &100i64.to_le_bytes(),
+                &200i64.to_le_bytes(),
+                &[7],
+                entry_token_mint.as_ref(),
+                &5_000_000i64.to_le_bytes(),
+                reward_vault.as_ref(),
+                &15_000_000u64.to_le_bytes(),
+                &(uri.len() as u32).to_le_bytes(),
+                uri,
+                &[1], // Active
+                &100u32.to_le_bytes(),
+                &10_000_000_000i64.to_le_bytes(),
+                &[10],
+                &[252],
+            ],
+            LeagueV0::SPACE,
+        );
+
+        let league = round_trip(&league_from_v0(&data).unwrap(), LeagueV0::SPACE);
+        assert_eq!(league.creator, creator);
+        assert_eq!(league.markets, markets);
+        assert_eq!(league.start_ts, 100);
+        assert_eq!(league.end_ts, 200);
+        assert_eq!(league.nonce, 7);
+        assert_eq!(league.entry_token_mint, entry_token_mint);
+        assert_eq!(league.entry_amount, 5_000_000);
+        assert_eq!(league.reward_vault, reward_vault);
+        assert_eq!(league.total_reward_amount, 15_000_000);
+        assert_eq!(league.metadata_uri.as_bytes(), uri);
+        assert!(league.status == LeagueStatus::Active);
+        assert_eq!(league.max_participants, 100);
+        assert_eq!(league.virtual_on_deposit, 10_000_000_000);
+        assert_eq!(league.max_leverage, 10);
+        assert_eq!(league.bump, 252);
+        assert!(league.margin_mode == MarginMode::Cross);
+        assert!(league.position_mode == PositionMode::Hedge);
+        assert!(league.league_type == LeagueType::Perp);
+        assert_eq!(league.max_open_positions, LeagueV0::MAX_OPEN_POSITIONS);
+        assert!(!league.paused && !league.withdrawals_paused);
+    }
+
+    fn leak_account(
+        key: Pubkey,
+        owner: Pubkey,
+        data: Vec<u8>,
+        is_signer: bool,
+        executable: bool,
+    ) -> &'static AccountInfo<'static> {
+        Box::leak(Box::new(AccountInfo::new(
+            Box::leak(Box::new(key)),
+            is_signer,
+            true,
+            Box::leak(Box::new(1_000_000_000)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(owner)),
+            executable,
+            0,
+        )))
+    }
+
+    #[test]
+    fn league_migrates_only_once() {
+        let data = original_account::<League>(
+            &[
+                Pubkey::new_unique().as_ref(),
+                &0u32.to_le_bytes(),
+                &100i64.to_le_bytes(),
+                &200i64.to_le_bytes(),
+                &[7],
+                Pubkey::new_unique().as_ref(),
+                &5_000_000i64.to_le_bytes(),
+                Pubkey::new_unique().as_ref(),
+                &0u64.to_le_bytes(),
+                &0u32.to_le_bytes(),
+                &[0], // Pending
+                &100u32.to_le_bytes(),
+                &10_000_000_000i64.to_le_bytes(),
+                &[10],
+                &[252],
+            ],
+            LeagueV0::SPACE,
+        );
+        let account = leak_account(Pubkey::new_unique(), crate::ID, data, false, false);
+        let payer = leak_account(Pubkey::new_unique(), System::id(), Vec::new(), true, false);
+        let system_program = leak_account(System::id(), Pubkey::default(), Vec::new(), false, true);
+        let migrate = || {
+            let mut accounts = MigrateAccount {
+                account: UncheckedAccount::try_from(account),
+                payer: Signer::try_from(payer).unwrap(),
+                system_program: Program::try_from(system_program).unwrap(),
+            };
+            migrate_league(Context::new(
+                &crate::ID,
+                &mut accounts,
+                &[],
+                MigrateAccountBumps::default(),
+            ))
+            .unwrap();
+        };
+
+        migrate();
+        // Settings changed after the first migration survive the second one
+        let mut league: League = read_account(account).unwrap();
+        league.margin_mode = MarginMode::Isolated;
+        league.position_mode = PositionMode::OneWay;
+        league.max_open_positions = 3;
+        league.paused = true;
+        write_account(account, &league).unwrap();
+        migrate();
+
+        let league: League = read_account(account).unwrap();
+        assert_eq!(account.data_len(), LeagueV0::SPACE);
+        assert_eq!(league.max_leverage, 10);
+        assert_eq!(league.bump, 252);
+        assert!(league.margin_mode == MarginMode::Isolated);
+        assert!(league.position_mode == PositionMode::OneWay);
+        assert_eq!(league.max_open_positions, 3);
+        assert!(league.paused && !league.withdrawals_paused);
+        assert_eq!(league.version, League::VERSION);
+    }
+
+    #[test]
+    fn participant_maps_from_the_original_layout() {
+        let league = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let position = Pubkey::new_unique();
+        let data = original_account::<Participant>(
+            &[
+                league.as_ref(),
+                user.as_ref(),
+                &[1],
+                &9_000_000_000i64.to_le_bytes(),
+                &(-250_000_000i64).to_le_bytes(),
+                &400_000_000i64.to_le_bytes(),
+                &2_000_000_000i64.to_le_bytes(),
+                &3u16.to_le_bytes(),
+                &0xFFFFu16.to_le_bytes(),
+                &4u64.to_le_bytes(),
+                &1u32.to_le_bytes(),
+                position.as_ref(),
+                &[251],
+            ],
+            ParticipantV0::SPACE,
+        );
+
+        let space = Participant::space(LeagueV0::MAX_OPEN_POSITIONS);
+        let participant = round_trip(
+            &participant_from_v0(&data, &MarginMode::Cross).unwrap(),
+            space,
+        );
+        assert_eq!(participant.league, league);
+        assert_eq!(participant.user, user);
+        assert!(participant.claimed);
+        assert!(participant.margin_mode == MarginMode::Cross);
+        assert_eq!(participant.virtual_balance, 9_000_000_000);
+        assert_eq!(participant.unrealized_pnl, -250_000_000);
+        assert_eq!(participant.used_margin, 400_000_000);
+        assert_eq!(participant.binary_stake, 0);
+        assert_eq!(participant.total_volume, 2_000_000_000);
+        assert_eq!(participant.topk_equity_index, 3);
+        assert_eq!(participant.topk_volume_index, 0xFFFF);
+        assert_eq!(participant.current_position_seq, 4);
+        assert_eq!(participant.positions, vec![position]);
+        assert_eq!(participant.bump, 251);
+    }
+
+    #[test]
+    fn position_maps_from_the_original_layout() {
+        let league = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let market = Pubkey::new_unique();
+        let oracle_feed = Pubkey::new_unique();
+        let data = original_account::<Position>(
+            &[
+                league.as_ref(),
+                user.as_ref(),
+                market.as_ref(),
+                &[6],
+                oracle_feed.as_ref(),
+                &3u64.to_le_bytes(),
+                &[1], // Short
+                &100_000_000i64.to_le_bytes(),
+                &5_000_000i64.to_le_bytes(),
+                &[5],
+                &4_000_000i64.to_le_bytes(),
+                &400_000_000i64.to_le_bytes(),
+                &(-20_000_000i64).to_le_bytes(),
+                &1_700_000_000i64.to_le_bytes(),
+                &0i64.to_le_bytes(),
+                &1_000_000i64.to_le_bytes(),
+                &110_000_000i64.to_le_bytes(),
+                &110_000_000i64.to_le_bytes(),
+                &(-10_000_000i64).to_le_bytes(),
+                &[250],
+            ],
+            PositionV0::SPACE,
+        );
+
+        let position = round_trip(&position_from_v0(&data).unwrap(), Position::SPACE);
+        assert_eq!(position.league, league);
+        assert_eq!(position.user, user);
+        assert_eq!(position.market, market);
+        assert_eq!(position.market_decimals, 6);
+        assert_eq!(position.oracle_feed, oracle_feed);
+        assert_eq!(position.seq_num, 3);
+        assert!(position.direction == Direction::Short);
+        assert_eq!(position.entry_price, 100_000_000);
+        assert_eq!(position.entry_size, 5_000_000);
+        assert_eq!(position.leverage, 5);
+        assert_eq!(position.size, 4_000_000);
+        assert_eq!(position.notional, 400_000_000);
+        assert_eq!(position.margin, 80_000_000);
+        assert_eq!(position.unrealized_pnl, -20_000_000);
+        assert_eq!(position.opened_at, 1_700_000_000);
+        assert_eq!(position.closed_at, 0);
+        assert_eq!(position.closed_size, 1_000_000);
+        assert_eq!(position.closed_price, 110_000_000);
+        assert_eq!(position.closed_equity, 110_000_000);
+        assert_eq!(position.closed_pnl, -10_000_000);
+        assert_eq!(position.bump, 250);
+        assert_eq!(position.borrow_rate_bps, 0);
+        assert_eq!(position.last_fee_accrual_ts, 1_700_000_000);
+    }
+}
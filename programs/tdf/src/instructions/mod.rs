@@ -1,14 +1,29 @@
+mod basket;
+mod binary;
+mod claim_reward;
 mod community;
+mod corporate_action;
+mod dca;
 mod initialize;
 mod league;
 mod market;
+mod migrate;
+mod order;
+mod pause;
 mod position;
 mod refresh_participant;
-mod claim_reward;
 
+pub use basket::*;
+pub use binary::*;
+pub use claim_reward::*;
+pub use community::*;
+pub use corporate_action::*;
+pub use dca::*;
 pub use initialize::*;
 pub use league::*;
 pub use market::*;
+pub use migrate::*;
+pub use order::*;
+pub use pause::*;
 pub use position::*;
 pub use refresh_participant::*;
-pub use claim_reward::*;
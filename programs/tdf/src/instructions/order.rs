@@ -0,0 +1,459 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::instructions::pause::{require_not_paused, require_withdrawals_open};
+use crate::instructions::position::{
+    allocate_position_account, decrease_position, fill_position, increase_position, new_position,
+    require_available_margin, require_corporate_actions_applied, require_market_listed_for,
+    require_market_not_halted, require_spot_order,
+};
+use crate::state::{
+    Direction, GlobalState, League, LeagueStatus, Market, Participant, Position, PositionMode,
+};
+use crate::utils::{
+    check_acceptable_price, constituent_feed_count, convert_with_fx, get_market_price,
+};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum OrderKind {
+    Open { direction: Direction, leverage: u8 },
+    Increase,
+    Decrease,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct OrderLeg {
+    pub kind: OrderKind,
+    pub size: i64,
+    pub acceptable_price: Option<i64>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteOrders<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"participant", league.key().as_ref(), user.key().as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+
+    pub league: Account<'info, League>,
+
+    pub system_program: Program<'info, System>,
+    // remaining accounts = [position_0, market_0, oracle_0, (denominator_0), (fallback_0), (fx_0), position_1, market_1, oracle_1, ...]
+    // the denominator feed follows the oracle for pair markets only, then the fallback and fx feeds for markets with them,
+    // the constituent feeds follow the basket account for basket markets only
+    // an open leg passes the position PDA for the participant's next sequence number
+}
+
+/// Execute several opens, increases and decreases atomically.
+/// Margin is checked once after every leg is applied, so a leg may rely on margin freed by a later one.
+pub fn execute_orders<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ExecuteOrders<'info>>,
+    legs: Vec<OrderLeg>,
+) -> Result<()> {
+    let league = &ctx.accounts.league;
+    let user = &ctx.accounts.user;
+    let participant = &mut ctx.accounts.participant;
+    let remaining: &[AccountInfo<'info>] = ctx.remaining_accounts;
+
+    require!(
+        league.status == LeagueStatus::Active,
+        crate::errors::ErrorCode::LeagueNotActive
+    );
+    require!(!legs.is_empty(), crate::errors::ErrorCode::InvalidOrderLegs);
+
+    let now = Clock::get()?.unix_timestamp;
+    let mut cursor = 0;
+
+    for leg in legs.iter() {
+        let LegAccounts {
+            position: position_ai,
+            market,
+            oracle: oracle_ai,
+            denominator: denominator_ai,
+            fallback: fallback_ai,
+            fx: fx_ai,
+            constituents: constituent_feeds,
+        } = next_leg_accounts(remaining, &mut cursor)?;
+
+        require!(leg.size > 0, crate::errors::ErrorCode::InvalidPositionSize);
+        let market_price = get_market_price(
+            oracle_ai,
+            denominator_ai,
+            fallback_ai,
+            constituent_feeds,
+            &market.price_config(),
+            now,
+        )?;
+        let current_price = convert_with_fx(
+            market_price,
+            fx_ai,
+            market.fx_feed,
+            &market.price_config(),
+            now,
+        )?;
+
+        match &leg.kind {
+            OrderKind::Open {
+                direction,
+                leverage,
+            } => {
+                // One-way leagues net against existing positions, which needs the position-specific path
+                require!(
+                    league.position_mode == PositionMode::Hedge,
+                    crate::errors::ErrorCode::InvalidPositionMode
+                );
+                require!(
+                    *leverage <= league.max_leverage,
+                    crate::errors::ErrorCode::InvalidLeverage
+                );
+                require_spot_order(league, direction, *leverage)?;
+                require_not_paused(&ctx.accounts.global_state, league)?;
+                require_market_listed_for(league, &market)?;
+                require_market_not_halted(&market, market_price, now)?;
+                require!(
+                    participant.positions.len() < league.max_open_positions as usize,
+                    crate::errors::ErrorCode::MaxOpenPositionExceeded
+                );
+                check_acceptable_price(
+                    direction.clone(),
+                    true,
+                    current_price,
+                    leg.acceptable_price,
+                )?;
+
+                let seq_num = participant.current_position_seq;
+                let bump = create_position_account(
+                    position_ai,
+                    user,
+                    &ctx.accounts.system_program,
+                    &league.key(),
+                    seq_num,
+                )?;
+
+                let mut position = new_position(
+                    league.key(),
+                    user.key(),
+                    &market,
+                    seq_num,
+                    direction.clone(),
+                    bump,
+                    now,
+                );
+                fill_position(
+                    &mut position,
+                    participant,
+                    direction.clone(),
+                    leg.size,
+                    *leverage,
+                    current_price,
+                    now,
+                )?;
+
+                let mut data = position_ai.try_borrow_mut_data()?;
+                let mut dst = &mut data[..];
+                position.try_serialize(&mut dst)?;
+
+                participant.current_position_seq = participant
+                    .current_position_seq
+                    .checked_add(1)
+                    .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+                participant.positions.push(position_ai.key());
+
+                msg!("Leg opened position {} at price {}", seq_num, current_price);
+            }
+            OrderKind::Increase | OrderKind::Decrease => {
+                require!(
+                    participant.positions.contains(&position_ai.key()),
+                    crate::errors::ErrorCode::PositionMismatch
+                );
+                require_keys_eq!(
+                    *position_ai.owner,
+                    crate::ID,
+                    crate::errors::ErrorCode::PositionMismatch
+                );
+
+                let mut data = position_ai.try_borrow_mut_data()?;
+                let mut position: Position = Position::try_deserialize(&mut &data[..])?;
+                require_keys_eq!(
+                    position.market,
+                    market.key(),
+                    crate::errors::ErrorCode::PositionMismatch
+                );
+                require_corporate_actions_applied(&position, &market)?;
+
+                let is_increase = leg.kind == OrderKind::Increase;
+                if is_increase {
+                    require_not_paused(&ctx.accounts.global_state, league)?;
+                    require_market_not_halted(&market, market_price, now)?;
+                } else {
+                    require_withdrawals_open(&ctx.accounts.global_state, league)?;
+                }
+                check_acceptable_price(
+                    position.direction.clone(),
+                    is_increase,
+                    current_price,
+                    leg.acceptable_price,
+                )?;
+
+                if is_increase {
+                    increase_position(&mut position, participant, leg.size, current_price, now)?;
+                    msg!("Leg increased position to {}", position.size);
+                } else {
+                    let realized_pnl = decrease_position(
+                        &mut position,
+                        position_ai.key(),
+                        participant,
+                        leg.size,
+                        current_price,
+                        now,
+                    )?;
+                    msg!(
+                        "Leg decreased position to {}, PnL: {}",
+                        position.size,
+                        realized_pnl
+                    );
+                }
+
+                let mut dst = &mut data[..];
+                position.try_serialize(&mut dst)?;
+            }
+        }
+    }
+
+    require!(
+        cursor == remaining.len(),
+        crate::errors::ErrorCode::InvalidOrderLegs
+    );
+    require_available_margin(participant)?;
+
+    msg!("Executed {} order legs", legs.len());
+    Ok(())
+}
+
+/// The accounts one leg reads, in remaining-accounts order
+struct LegAccounts<'info> {
+    position: &'info AccountInfo<'info>,
+    market: Account<'info, Market>,
+    oracle: &'info AccountInfo<'info>,
+    denominator: Option<&'info AccountInfo<'info>>,
+    fallback: Option<&'info AccountInfo<'info>>,
+    fx: Option<&'info AccountInfo<'info>>,
+    constituents: &'info [AccountInfo<'info>],
+}
+
+/// Take the next leg's accounts from `remaining`, advancing `cursor` past them.
+/// Which optional feeds follow the oracle is decided by the market, not by the caller.
+fn next_leg_accounts<'info>(
+    remaining: &'info [AccountInfo<'info>],
+    cursor: &mut usize,
+) -> Result<LegAccounts<'info>> {
+    let mut next = || -> Result<&'info AccountInfo<'info>> {
+        let account = remaining
+            .get(*cursor)
+            .ok_or(crate::errors::ErrorCode::InvalidOrderLegs)?;
+        *cursor += 1;
+        Ok(account)
+    };
+
+    let position = next()?;
+    let market: Account<'info, Market> = Account::try_from(next()?)?;
+    let oracle = next()?;
+    require!(
+        oracle.key() == market.oracle_feed,
+        crate::errors::ErrorCode::OracleMismatch
+    );
+    let denominator = market.denominator_feed.map(|_| next()).transpose()?;
+    let fallback = market.fallback_feed.map(|_| next()).transpose()?;
+    let fx = market.fx_feed.map(|_| next()).transpose()?;
+
+    let constituent_count = constituent_feed_count(oracle)?;
+    let constituents = remaining
+        .get(*cursor..*cursor + constituent_count)
+        .ok_or(crate::errors::ErrorCode::InvalidOrderLegs)?;
+    *cursor += constituent_count;
+
+    Ok(LegAccounts {
+        position,
+        market,
+        oracle,
+        denominator,
+        fallback,
+        fx,
+        constituents,
+    })
+}
+
+/// Create the position PDA for `seq_num`, paid by the user. Returns its bump.
+fn create_position_account<'info>(
+    position_ai: &AccountInfo<'info>,
+    user: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    league_key: &Pubkey,
+    seq_num: u64,
+) -> Result<u8> {
+    let bump = allocate_position_account(
+        position_ai,
+        system_program,
+        league_key,
+        &user.key(),
+        seq_num,
+    )?;
+
+    // Lamports sent to the address beforehand count towards the rent
+    let lamports_needed = Rent::get()?
+        .minimum_balance(Position::SPACE)
+        .saturating_sub(position_ai.lamports());
+    if lamports_needed > 0 {
+        transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                Transfer {
+                    from: user.to_account_info(),
+                    to: position_ai.clone(),
+                },
+            ),
+            lamports_needed,
+        )?;
+    }
+
+    Ok(bump)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ErrorCode;
+    use crate::instructions::position::fixtures::{empty_position, participant};
+
+    fn leak_account(owner: Pubkey, data: Vec<u8>) -> AccountInfo<'static> {
+        AccountInfo::new(
+            Box::leak(Box::new(Pubkey::new_unique())),
+            false,
+            true,
+            Box::leak(Box::new(0)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(owner)),
+            false,
+            0,
+        )
+    }
+
+    fn market_account(oracle: Pubkey, fallback: bool, fx: bool) -> AccountInfo<'static> {
+        let mut market =
+            Market::try_deserialize_unchecked(&mut &vec![0u8; Market::SPACE][..]).unwrap();
+        market.oracle_feed = oracle;
+        market.fallback_feed = fallback.then(Pubkey::new_unique);
+        market.fx_feed = fx.then(Pubkey::new_unique);
+        let mut data = Vec::new();
+        market.try_serialize(&mut data).unwrap();
+        leak_account(crate::ID, data)
+    }
+
+    /// `[position, market, oracle, ..extra]` for one leg on a market with the given feeds
+    fn leg(fallback: bool, fx: bool, extra: usize) -> Vec<AccountInfo<'static>> {
+        let oracle = leak_account(Pubkey::new_unique(), vec![0u8; 57]);
+        let mut accounts = vec![
+            leak_account(crate::ID, vec![]),
+            market_account(oracle.key(), fallback, fx),
+            oracle,
+        ];
+        accounts.extend((0..extra).map(|_| leak_account(Pubkey::new_unique(), vec![])));
+        accounts
+    }
+
+    fn remaining_accounts(legs: Vec<Vec<AccountInfo<'static>>>) -> &'static [AccountInfo<'static>] {
+        Box::leak(legs.concat().into_boxed_slice())
+    }
+
+    #[test]
+    fn leg_accounts_follow_the_market() {
+        let remaining = remaining_accounts(vec![leg(false, false, 0), leg(true, true, 2)]);
+        let mut cursor = 0;
+
+        let first = next_leg_accounts(remaining, &mut cursor).unwrap();
+        assert_eq!(cursor, 3);
+        assert!(first.fallback.is_none() && first.fx.is_none());
+
+        let second = next_leg_accounts(remaining, &mut cursor).unwrap();
+        assert_eq!(cursor, 8);
+        assert_eq!(second.fallback.unwrap().key(), remaining[6].key());
+        assert_eq!(second.fx.unwrap().key(), remaining[7].key());
+    }
+
+    #[test]
+    fn leg_missing_a_feed_is_rejected() {
+        // The market has a fallback and an fx feed but only one follows the oracle
+        let remaining = remaining_accounts(vec![leg(true, true, 1)]);
+        let err = next_leg_accounts(remaining, &mut 0).err().unwrap();
+        assert_eq!(err, ErrorCode::InvalidOrderLegs.into());
+    }
+
+    #[test]
+    fn leg_with_the_wrong_oracle_is_rejected() {
+        let mut accounts = leg(false, false, 0);
+        accounts.swap(0, 2);
+        let remaining = remaining_accounts(vec![accounts]);
+        assert!(next_leg_accounts(remaining, &mut 0).is_err());
+
+        let mut accounts = leg(false, false, 0);
+        accounts[2] = leak_account(Pubkey::new_unique(), vec![0u8; 57]);
+        let remaining = remaining_accounts(vec![accounts]);
+        let err = next_leg_accounts(remaining, &mut 0).err().unwrap();
+        assert_eq!(err, ErrorCode::OracleMismatch.into());
+    }
+
+    #[test]
+    fn margin_is_checked_after_every_leg() {
+        let mut participant = participant(1_000_000_000);
+
+        // An existing 1x long of 8 at 100 holds 800 of the 1000 balance
+        let mut held = empty_position();
+        fill_position(
+            &mut held,
+            &mut participant,
+            Direction::Long,
+            8_000_000,
+            1,
+            100_000_000,
+            0,
+        )
+        .unwrap();
+
+        // The first leg opens a short that needs 500, more than is free on its own
+        let mut short = empty_position();
+        fill_position(
+            &mut short,
+            &mut participant,
+            Direction::Short,
+            5_000_000,
+            1,
+            100_000_000,
+            0,
+        )
+        .unwrap();
+        assert!(require_available_margin(&participant).is_err());
+
+        // The second leg sells half the long, which frees enough for both
+        decrease_position(
+            &mut held,
+            Pubkey::default(),
+            &mut participant,
+            4_000_000,
+            100_000_000,
+            0,
+        )
+        .unwrap();
+        require_available_margin(&participant).unwrap();
+    }
+}
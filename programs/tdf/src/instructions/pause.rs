@@ -0,0 +1,111 @@
+use anchor_lang::prelude::*;
+
+use crate::events::{LeaguePauseUpdated, ProtocolPauseUpdated};
+use crate::state::{GlobalState, League, Role};
+
+#[derive(Accounts)]
+pub struct SetProtocolPause<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        constraint = global_state.has_role(&admin.key(), Role::Pauser)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
+
+/// `paused` stops leagues being created or joined and new exposure everywhere.
+/// `withdrawals_paused` also stops decreases, margin withdrawals and reward claims.
+pub fn set_protocol_pause(
+    ctx: Context<SetProtocolPause>,
+    paused: bool,
+    withdrawals_paused: bool,
+) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+    global_state.paused = paused;
+    global_state.withdrawals_paused = withdrawals_paused;
+
+    emit!(ProtocolPauseUpdated {
+        paused,
+        withdrawals_paused,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetLeaguePause<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        constraint = league.creator == authority.key()
+            || global_state.has_role(&authority.key(), Role::LeagueModerator)
+            @ crate::errors::ErrorCode::Unauthorized
+    )]
+    pub league: Account<'info, League>,
+
+    pub authority: Signer<'info>,
+}
+
+/// Same switches as the protocol pause, for a single league. Its creator or a league
+/// moderator can pause it, but only a pauser or a league moderator can lock participants'
+/// funds by pausing withdrawals, or unlock them again.
+pub fn set_league_pause(
+    ctx: Context<SetLeaguePause>,
+    paused: bool,
+    withdrawals_paused: bool,
+) -> Result<()> {
+    let global_state = &ctx.accounts.global_state;
+    let authority = ctx.accounts.authority.key();
+    let league = &mut ctx.accounts.league;
+    if withdrawals_paused != league.withdrawals_paused {
+        require!(
+            global_state.has_role(&authority, Role::Pauser)
+                || global_state.has_role(&authority, Role::LeagueModerator),
+            crate::errors::ErrorCode::Unauthorized
+        );
+    }
+    league.paused = paused;
+    league.withdrawals_paused = withdrawals_paused;
+
+    emit!(LeaguePauseUpdated {
+        league: league.key(),
+        paused,
+        withdrawals_paused,
+    });
+    Ok(())
+}
+
+/// Leagues can't be created while the protocol is paused
+pub(crate) fn require_protocol_not_paused(global_state: &GlobalState) -> Result<()> {
+    require!(
+        !global_state.paused,
+        crate::errors::ErrorCode::ProtocolPaused
+    );
+    Ok(())
+}
+
+/// Joining and new exposure stop while the protocol or the league is paused
+pub(crate) fn require_not_paused(global_state: &GlobalState, league: &League) -> Result<()> {
+    require_protocol_not_paused(global_state)?;
+    require!(!league.paused, crate::errors::ErrorCode::LeaguePaused);
+    Ok(())
+}
+
+/// Reducing exposure and claiming stay open through a pause unless withdrawals are paused too
+pub(crate) fn require_withdrawals_open(global_state: &GlobalState, league: &League) -> Result<()> {
+    require!(
+        !global_state.withdrawals_paused && !league.withdrawals_paused,
+        crate::errors::ErrorCode::WithdrawalsPaused
+    );
+    Ok(())
+}
@@ -1,16 +1,31 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program::{allocate, assign, Allocate, Assign};
 use ephemeral_rollups_sdk::anchor::delegate;
 use ephemeral_rollups_sdk::cpi::DelegateConfig;
 
-use crate::state::{Direction, League, LeagueStatus, Market, Participant, Position};
+use crate::instructions::market::volatility_halt_triggered;
+use crate::instructions::pause::{require_not_paused, require_withdrawals_open};
+use crate::math::Rounding;
+use crate::state::{
+    Direction, GlobalState, League, LeagueStatus, LeagueType, Market, Participant, Position,
+    PositionMode,
+};
 use crate::utils::{
-    calculate_notional, calculate_price_from_notional_and_size, calculate_unrealized_pnl, dir_sign,
-    get_price_from_oracle,
+    calculate_average_entry_price, calculate_borrow_fee, calculate_effective_leverage,
+    calculate_liquidation_price, calculate_notional, calculate_price_from_notional_and_size,
+    calculate_pro_rata, calculate_required_margin, calculate_unrealized_pnl,
+    check_acceptable_price, convert_with_fx, get_market_price,
 };
 
 // TODO: participant should be updated in realtime to avoid liquidation risk
 #[derive(Accounts)]
 pub struct OpenPosition<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
     #[account(mut)]
     pub user: Signer<'info>,
 
@@ -24,7 +39,7 @@ pub struct OpenPosition<'info> {
     #[account(
         init_if_needed,
         payer = user,
-        space = 8 + 32*5 + 8 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1,
+        space = Position::SPACE,
         seeds = [b"position", league.key().as_ref(), user.key().as_ref(), participant.current_position_seq.to_le_bytes().as_ref()],
         bump
     )]
@@ -34,8 +49,15 @@ pub struct OpenPosition<'info> {
     pub market: Account<'info, Market>,
     /// CHECK: oracle feed from market
     pub oracle_feed: AccountInfo<'info>,
+    /// CHECK: denominator feed from market, pair markets only
+    pub denominator_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fallback feed from market, markets with one only
+    pub fallback_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fx feed from market, markets quoted in another currency only
+    pub fx_feed: Option<UncheckedAccount<'info>>,
 
     pub system_program: Program<'info, System>,
+    // remaining accounts = [open positions (one-way only)..., basket constituent feeds...]
 }
 
 pub fn open_position(
@@ -44,6 +66,7 @@ pub fn open_position(
     size: i64,
     leverage: u8,
     seq_num: u64,
+    acceptable_price: Option<i64>,
 ) -> Result<()> {
     let league = &ctx.accounts.league;
     let market = &ctx.accounts.market;
@@ -58,8 +81,10 @@ pub fn open_position(
         leverage <= league.max_leverage,
         crate::errors::ErrorCode::InvalidLeverage
     );
+    require_spot_order(league, &direction, leverage)?;
+    require_market_listed_for(league, market)?;
     require!(
-        participant.positions.len() < 10,
+        participant.positions.len() < league.max_open_positions as usize,
         crate::errors::ErrorCode::MaxOpenPositionExceeded
     );
     require!(
@@ -71,16 +96,37 @@ pub fn open_position(
         crate::errors::ErrorCode::OracleMismatch
     );
 
-    let current_price = get_price_from_oracle(&ctx.accounts.oracle_feed)?;
-    let notional = calculate_notional(current_price, size, market.decimals);
+    // One-way leagues hold a single netted position per market
+    let mut constituent_feeds = ctx.remaining_accounts;
+    if league.position_mode == PositionMode::OneWay {
+        require!(
+            ctx.remaining_accounts.len() >= participant.positions.len(),
+            crate::errors::ErrorCode::InvalidRefreshAccounts
+        );
+        let (open_positions, rest) = ctx.remaining_accounts.split_at(participant.positions.len());
+        require_no_position_on_market(participant, open_positions, &market.key())?;
+        constituent_feeds = rest;
+    }
 
-    let required_margin = notional
-        .checked_div(leverage as i64)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    require!(
-        participant.available_balance() >= required_margin,
-        crate::errors::ErrorCode::InsufficientMargin
-    );
+    let now = Clock::get()?.unix_timestamp;
+    let current_price = get_market_price(
+        &ctx.accounts.oracle_feed,
+        ctx.accounts.denominator_feed.as_deref(),
+        ctx.accounts.fallback_feed.as_deref(),
+        constituent_feeds,
+        &market.price_config(),
+        now,
+    )?;
+    require_not_paused(&ctx.accounts.global_state, league)?;
+    require_market_not_halted(market, current_price, now)?;
+    let current_price = convert_with_fx(
+        current_price,
+        ctx.accounts.fx_feed.as_deref(),
+        market.fx_feed,
+        &market.price_config(),
+        now,
+    )?;
+    check_acceptable_price(direction.clone(), true, current_price, acceptable_price)?;
 
     // Create new position
     position.league = league.key();
@@ -88,25 +134,22 @@ pub fn open_position(
     position.market = market.key();
     position.market_decimals = market.decimals;
     position.oracle_feed = ctx.accounts.oracle_feed.key();
+    position.corporate_actions_applied = market.corporate_action_count;
+    position.borrow_rate_bps = market.borrow_rate_bps;
     position.seq_num = participant.current_position_seq;
-    position.direction = direction;
-    position.entry_size = size;
-    position.size = size;
-    position.entry_price = current_price;
-    position.notional = notional;
-    position.leverage = leverage;
-    position.opened_at = Clock::get()?.unix_timestamp;
     position.bump = ctx.bumps.position;
+    position.version = Position::VERSION;
+    fill_position(
+        position,
+        participant,
+        direction,
+        size,
+        leverage,
+        current_price,
+        now,
+    )?;
+    require_available_margin(participant)?;
 
-    // Update participant with overflow protection
-    participant.total_volume = participant
-        .total_volume
-        .checked_add(notional)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    participant.used_margin = participant
-        .used_margin
-        .checked_add(required_margin)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
     participant.current_position_seq = participant
         .current_position_seq
         .checked_add(1)
@@ -147,6 +190,12 @@ pub fn delegate_position(ctx: Context<DelegatePosition>, league_key: Pubkey, seq
 
 #[derive(Accounts)]
 pub struct IncreasePositionSize<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
     #[account(mut)]
     pub user: Signer<'info>,
 
@@ -168,9 +217,20 @@ pub struct IncreasePositionSize<'info> {
     pub market: Account<'info, Market>,
     /// CHECK: oracle feed from market
     pub oracle_feed: AccountInfo<'info>,
+    /// CHECK: denominator feed from market, pair markets only
+    pub denominator_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fallback feed from market, markets with one only
+    pub fallback_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fx feed from market, markets quoted in another currency only
+    pub fx_feed: Option<UncheckedAccount<'info>>,
+    // remaining accounts = basket constituent feeds, basket markets only
 }
 
-pub fn increase_position_size(ctx: Context<IncreasePositionSize>, size: i64) -> Result<()> {
+pub fn increase_position_size(
+    ctx: Context<IncreasePositionSize>,
+    size: i64,
+    acceptable_price: Option<i64>,
+) -> Result<()> {
     let league = &ctx.accounts.league;
     let market = &ctx.accounts.market;
     let participant = &mut ctx.accounts.participant;
@@ -184,67 +244,38 @@ pub fn increase_position_size(ctx: Context<IncreasePositionSize>, size: i64) ->
         ctx.accounts.oracle_feed.key() == market.oracle_feed,
         crate::errors::ErrorCode::OracleMismatch
     );
-
-    let leverage = position.leverage;
-    let current_price = get_price_from_oracle(&ctx.accounts.oracle_feed)?;
-    let new_notional = calculate_notional(current_price, size, market.decimals);
-
-    let additional_margin = new_notional
-        .checked_div(leverage as i64)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
     require!(
-        participant.available_balance() >= additional_margin,
-        crate::errors::ErrorCode::InsufficientMargin
+        position.market == market.key(),
+        crate::errors::ErrorCode::PositionMismatch
     );
+    require_corporate_actions_applied(position, market)?;
 
-    // Update entry stats with overflow protection
-    let prev_entry_notional =
-        calculate_notional(position.entry_price, position.entry_size, market.decimals);
-    position.entry_size = position
-        .entry_size
-        .checked_add(size)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    let new_entry_notional = prev_entry_notional
-        .checked_add(new_notional)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    position.entry_price = new_entry_notional / position.entry_size;
-
-    // Update realtime stats with overflow protection
-    let prev_upnl = position.unrealized_pnl;
-    position.size = position
-        .size
-        .checked_add(size)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    position.notional = position
-        .notional
-        .checked_add(new_notional)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    position.unrealized_pnl = calculate_unrealized_pnl(
-        position.notional,
+    let now = Clock::get()?.unix_timestamp;
+    let current_price = get_market_price(
+        &ctx.accounts.oracle_feed,
+        ctx.accounts.denominator_feed.as_deref(),
+        ctx.accounts.fallback_feed.as_deref(),
+        ctx.remaining_accounts,
+        &market.price_config(),
+        now,
+    )?;
+    require_not_paused(&ctx.accounts.global_state, league)?;
+    require_market_not_halted(market, current_price, now)?;
+    let current_price = convert_with_fx(
         current_price,
-        position.size,
-        market.decimals,
+        ctx.accounts.fx_feed.as_deref(),
+        market.fx_feed,
+        &market.price_config(),
+        now,
+    )?;
+    check_acceptable_price(
         position.direction.clone(),
-    );
-
-    // Update participant with overflow protection
-    participant.used_margin = participant
-        .used_margin
-        .checked_add(additional_margin)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    participant.total_volume = participant
-        .total_volume
-        .checked_add(new_notional)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-
-    let upnl_delta = position
-        .unrealized_pnl
-        .checked_sub(prev_upnl)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    participant.unrealized_pnl = participant
-        .unrealized_pnl
-        .checked_add(upnl_delta)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+        true,
+        current_price,
+        acceptable_price,
+    )?;
+    increase_position(position, participant, size, current_price, now)?;
+    require_available_margin(participant)?;
 
     msg!("Position size increased to {}", position.size);
 
@@ -253,6 +284,12 @@ pub fn increase_position_size(ctx: Context<IncreasePositionSize>, size: i64) ->
 
 #[derive(Accounts)]
 pub struct DecreasePositionSize<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
     #[account(mut)]
     pub user: Signer<'info>,
 
@@ -274,17 +311,40 @@ pub struct DecreasePositionSize<'info> {
     pub market: Account<'info, Market>,
     /// CHECK: oracle feed from market
     pub oracle_feed: AccountInfo<'info>,
+    /// CHECK: denominator feed from market, pair markets only
+    pub denominator_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fallback feed from market, markets with one only
+    pub fallback_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fx feed from market, markets quoted in another currency only
+    pub fx_feed: Option<UncheckedAccount<'info>>,
+    // remaining accounts = basket constituent feeds, basket markets only
 }
 
 pub fn decrease_position_size(
     ctx: Context<DecreasePositionSize>,
     size_to_close: i64,
+    acceptable_price: Option<i64>,
 ) -> Result<()> {
     let league = &ctx.accounts.league;
     let market = &ctx.accounts.market;
     let participant = &mut ctx.accounts.participant;
     let position = &mut ctx.accounts.position;
-    let current_price = get_price_from_oracle(&ctx.accounts.oracle_feed)?;
+    let now = Clock::get()?.unix_timestamp;
+    let current_price = get_market_price(
+        &ctx.accounts.oracle_feed,
+        ctx.accounts.denominator_feed.as_deref(),
+        ctx.accounts.fallback_feed.as_deref(),
+        ctx.remaining_accounts,
+        &market.price_config(),
+        now,
+    )?;
+    let current_price = convert_with_fx(
+        current_price,
+        ctx.accounts.fx_feed.as_deref(),
+        market.fx_feed,
+        &market.price_config(),
+        now,
+    )?;
 
     require!(current_price > 0, crate::errors::ErrorCode::InvalidPrice);
     require!(
@@ -303,101 +363,1464 @@ pub fn decrease_position_size(
         size_to_close <= position.size,
         crate::errors::ErrorCode::InvalidReduceSize
     );
-    let prev_upnl = position.unrealized_pnl;
-
-    // Calculate realized PnL with overflow protection
-    let closing_equity = calculate_notional(current_price, size_to_close, market.decimals);
-    let closing_notional = calculate_notional(position.entry_price, size_to_close, market.decimals);
-    let realized_pnl = (closing_equity as i64 - closing_notional as i64)
-        .checked_mul(dir_sign(position.direction.clone()) as i64)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    let prev_locked = position
-        .notional
-        .checked_div(position.leverage as i64)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    let new_locked = position
-        .notional
-        .checked_sub(closing_notional)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?
-        .checked_div(position.leverage as i64)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    let released_margin = prev_locked
-        .checked_sub(new_locked)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-
-    // Calculate closed stats with overflow protection
-    position.closed_size = position
-        .closed_size
-        .checked_add(size_to_close)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    position.closed_equity = position
-        .closed_equity
-        .checked_add(closing_equity)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    position.closed_price = calculate_price_from_notional_and_size(
-        position.closed_equity,
-        position.closed_size,
-        market.decimals,
+    require!(
+        position.market == market.key(),
+        crate::errors::ErrorCode::PositionMismatch
     );
-    position.closed_pnl = position
-        .closed_pnl
-        .checked_add(realized_pnl)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-
-    // Update position with overflow protection
-    position.size = position
-        .size
-        .checked_sub(size_to_close)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    position.notional = position
-        .notional
-        .checked_sub(closing_notional)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    position.unrealized_pnl = calculate_unrealized_pnl(
-        position.notional,
+    require_withdrawals_open(&ctx.accounts.global_state, league)?;
+    require_corporate_actions_applied(position, market)?;
+    check_acceptable_price(
+        position.direction.clone(),
+        false,
+        current_price,
+        acceptable_price,
+    )?;
+    let position_key = position.key();
+    let realized_pnl = decrease_position(
+        position,
+        position_key,
+        participant,
+        size_to_close,
         current_price,
+        now,
+    )?;
+
+    msg!(
+        "Position size decreased to {}, PnL: {}",
         position.size,
-        market.decimals,
-        position.direction.clone(),
+        realized_pnl
     );
 
-    // Update participant with overflow protection
-    participant.total_volume = participant
-        .total_volume
-        .checked_add(closing_equity)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    participant.used_margin = participant
-        .used_margin
-        .checked_sub(released_margin)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    Ok(())
+}
 
-    participant.virtual_balance = participant
-        .virtual_balance
-        .checked_add(realized_pnl)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+#[derive(Accounts)]
+pub struct NetPosition<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
 
-    let upnl_delta = position
-        .unrealized_pnl
-        .checked_sub(prev_upnl)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-    participant.unrealized_pnl = participant
-        .unrealized_pnl
-        .checked_add(upnl_delta)
-        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"participant", league.key().as_ref(), user.key().as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+
+    #[account(
+        mut,
+        seeds = [b"position", league.key().as_ref(), user.key().as_ref(), position.seq_num.to_le_bytes().as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, Position>,
+
+    pub league: Account<'info, League>,
+    pub market: Account<'info, Market>,
+    /// CHECK: oracle feed from market
+    pub oracle_feed: AccountInfo<'info>,
+    /// CHECK: denominator feed from market, pair markets only
+    pub denominator_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fallback feed from market, markets with one only
+    pub fallback_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fx feed from market, markets quoted in another currency only
+    pub fx_feed: Option<UncheckedAccount<'info>>,
+    // remaining accounts = basket constituent feeds, basket markets only
+}
+
+/// One-way mode order against the existing position on a market.
+/// Same direction increases it, opposite direction reduces it and flips it once it passes zero.
+pub fn net_position(
+    ctx: Context<NetPosition>,
+    direction: Direction,
+    size: i64,
+    acceptable_price: Option<i64>,
+) -> Result<()> {
+    let league = &ctx.accounts.league;
+    let market = &ctx.accounts.market;
+    let participant = &mut ctx.accounts.participant;
+    let position = &mut ctx.accounts.position;
+
+    require!(
+        league.status == LeagueStatus::Active,
+        crate::errors::ErrorCode::LeagueNotActive
+    );
+    require!(
+        league.position_mode == PositionMode::OneWay,
+        crate::errors::ErrorCode::InvalidPositionMode
+    );
+    require!(size > 0, crate::errors::ErrorCode::InvalidPositionSize);
+    require!(
+        position.market == market.key(),
+        crate::errors::ErrorCode::PositionMismatch
+    );
+    require!(
+        ctx.accounts.oracle_feed.key() == market.oracle_feed,
+        crate::errors::ErrorCode::OracleMismatch
+    );
+    require!(
+        participant.positions.contains(&position.key()),
+        crate::errors::ErrorCode::PositionMismatch
+    );
+    require_corporate_actions_applied(position, market)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let market_price = get_market_price(
+        &ctx.accounts.oracle_feed,
+        ctx.accounts.denominator_feed.as_deref(),
+        ctx.accounts.fallback_feed.as_deref(),
+        ctx.remaining_accounts,
+        &market.price_config(),
+        now,
+    )?;
+    let current_price = convert_with_fx(
+        market_price,
+        ctx.accounts.fx_feed.as_deref(),
+        market.fx_feed,
+        &market.price_config(),
+        now,
+    )?;
+    check_acceptable_price(direction.clone(), true, current_price, acceptable_price)?;
+
+    // A spot holding can be sold down to zero but never flipped short
+    require!(
+        league.league_type == LeagueType::Perp
+            || position.direction == direction
+            || size <= position.size,
+        crate::errors::ErrorCode::InvalidSpotOrder
+    );
+    // Selling down is allowed through a halt, adding or flipping is not
+    if position.direction == direction || size > position.size {
+        require_not_paused(&ctx.accounts.global_state, league)?;
+        require_market_not_halted(market, market_price, now)?;
+    }
+    if position.direction != direction {
+        require_withdrawals_open(&ctx.accounts.global_state, league)?;
+    }
+
+    let position_key = position.key();
+    let realized_pnl = apply_net_order(
+        position,
+        position_key,
+        participant,
+        market,
+        league.max_leverage.min(market.max_leverage),
+        direction,
+        size,
+        current_price,
+        now,
+    )?;
 
     msg!(
-        "Position size decreased to {}, PnL: {}",
+        "Position netted to {}, PnL: {}",
         position.size,
         realized_pnl
     );
 
-    if position.size == 0 {
-        // close position logic here
-        position.closed_at = Clock::get()?.unix_timestamp;
-        // remove position from participant.positions vector
-        participant.positions.retain(|p| p != &position.key());
-        msg!("Position closed and removed from participant");
+    Ok(())
+}
+
+/// Net an order into the one-way position: the same direction increases it, the opposite
+/// direction reduces it and flips whatever is left to a fresh position on the other side,
+/// opened at the market's current borrow rate and within `max_leverage`.
+/// Returns the realized PnL.
+pub(crate) fn apply_net_order(
+    position: &mut Position,
+    position_key: Pubkey,
+    participant: &mut Participant,
+    market: &Market,
+    max_leverage: u8,
+    direction: Direction,
+    size: i64,
+    price: i64,
+    now: i64,
+) -> Result<i64> {
+    if position.direction == direction {
+        increase_position(position, participant, size, price, now)?;
+        require_available_margin(participant)?;
+        msg!("Position size increased to {}", position.size);
+        return Ok(0);
     }
 
-    Ok(())
+    let size_to_close = size.min(position.size);
+    let realized_pnl = decrease_position(
+        position,
+        position_key,
+        participant,
+        size_to_close,
+        price,
+        now,
+    )?;
+
+    // Whatever is left after closing flips the position to the other side
+    let remaining_size = size - size_to_close;
+    if remaining_size > 0 {
+        let leverage = position.leverage;
+        require!(
+            leverage <= max_leverage,
+            crate::errors::ErrorCode::InvalidLeverage
+        );
+        // The closed side counts as a position of its own in the participant's history
+        record_closed_position(participant, position)?;
+        fill_position(
+            position,
+            participant,
+            direction,
+            remaining_size,
+            leverage,
+            price,
+            now,
+        )?;
+        position.borrow_rate_bps = market.borrow_rate_bps;
+        require_available_margin(participant)?;
+        participant.positions.push(position_key);
+        msg!("Position flipped, new size {}", position.size);
+    }
+    Ok(realized_pnl)
+}
+
+#[derive(Accounts)]
+pub struct AdjustMargin<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"participant", league.key().as_ref(), user.key().as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+
+    #[account(
+        mut,
+        seeds = [b"position", league.key().as_ref(), user.key().as_ref(), position.seq_num.to_le_bytes().as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, Position>,
+
+    pub league: Account<'info, League>,
+    pub market: Account<'info, Market>,
+}
+
+/// Lock more of the available balance into the position, lowering its effective leverage
+pub fn add_margin(ctx: Context<AdjustMargin>, amount: i64) -> Result<()> {
+    let league = &ctx.accounts.league;
+    let market = &ctx.accounts.market;
+    let participant = &mut ctx.accounts.participant;
+    let position = &mut ctx.accounts.position;
+
+    require!(
+        league.status == LeagueStatus::Active,
+        crate::errors::ErrorCode::LeagueNotActive
+    );
+    require!(
+        position.market == market.key(),
+        crate::errors::ErrorCode::PositionMismatch
+    );
+    require_corporate_actions_applied(position, market)?;
+    require!(amount > 0, crate::errors::ErrorCode::InvalidMarginAmount);
+    require!(
+        position.size > 0,
+        crate::errors::ErrorCode::InvalidPositionSize
+    );
+    require!(
+        participant.available_balance() >= amount,
+        crate::errors::ErrorCode::InsufficientMargin
+    );
+    // Charge the fee owed at the old margin before the borrowed amount changes
+    accrue_borrow_fee(position, participant, Clock::get()?.unix_timestamp)?;
+
+    let new_margin = position
+        .margin
+        .checked_add(amount)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    // Margin beyond the notional would mean leverage below 1x
+    require!(
+        new_margin <= position.notional,
+        crate::errors::ErrorCode::InvalidMarginAmount
+    );
+
+    position.margin = new_margin;
+    position.leverage = calculate_effective_leverage(position.notional, position.margin)?;
+    participant.used_margin = participant
+        .used_margin
+        .checked_add(amount)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    update_liquidation_prices(position, participant)?;
+
+    msg!(
+        "Margin added: {}, effective leverage: {}x",
+        position.margin,
+        position.leverage
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RemoveMargin<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"participant", league.key().as_ref(), user.key().as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+
+    #[account(
+        mut,
+        seeds = [b"position", league.key().as_ref(), user.key().as_ref(), position.seq_num.to_le_bytes().as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, Position>,
+
+    pub league: Account<'info, League>,
+    pub market: Account<'info, Market>,
+    /// CHECK: oracle feed from market
+    pub oracle_feed: AccountInfo<'info>,
+    /// CHECK: denominator feed from market, pair markets only
+    pub denominator_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fallback feed from market, markets with one only
+    pub fallback_feed: Option<UncheckedAccount<'info>>,
+    /// CHECK: fx feed from market, markets quoted in another currency only
+    pub fx_feed: Option<UncheckedAccount<'info>>,
+    // remaining accounts = basket constituent feeds, basket markets only
+}
+
+/// Release margin from the position, raising its effective leverage up to the league and market caps.
+/// The position is marked first, so margin can't be pulled out from under an unrealized loss.
+pub fn remove_margin(ctx: Context<RemoveMargin>, amount: i64) -> Result<()> {
+    let league = &ctx.accounts.league;
+    let market = &ctx.accounts.market;
+    let participant = &mut ctx.accounts.participant;
+    let position = &mut ctx.accounts.position;
+
+    require!(
+        league.status == LeagueStatus::Active,
+        crate::errors::ErrorCode::LeagueNotActive
+    );
+    require!(
+        position.market == market.key(),
+        crate::errors::ErrorCode::PositionMismatch
+    );
+    require!(
+        ctx.accounts.oracle_feed.key() == market.oracle_feed,
+        crate::errors::ErrorCode::OracleMismatch
+    );
+    require_withdrawals_open(&ctx.accounts.global_state, league)?;
+    require_corporate_actions_applied(position, market)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let current_price = get_market_price(
+        &ctx.accounts.oracle_feed,
+        ctx.accounts.denominator_feed.as_deref(),
+        ctx.accounts.fallback_feed.as_deref(),
+        ctx.remaining_accounts,
+        &market.price_config(),
+        now,
+    )?;
+    let current_price = convert_with_fx(
+        current_price,
+        ctx.accounts.fx_feed.as_deref(),
+        market.fx_feed,
+        &market.price_config(),
+        now,
+    )?;
+    release_margin(
+        position,
+        participant,
+        amount,
+        league.max_leverage.min(market.max_leverage),
+        current_price,
+        now,
+    )?;
+
+    msg!(
+        "Margin removed: {}, effective leverage: {}x",
+        position.margin,
+        position.leverage
+    );
+    Ok(())
+}
+
+/// Take `amount` of margin out of the position after marking it at `price`. The remaining
+/// margin must still cover the unrealized loss and the participant must stay solvent.
+pub(crate) fn release_margin(
+    position: &mut Position,
+    participant: &mut Participant,
+    amount: i64,
+    max_leverage: u8,
+    price: i64,
+    now: i64,
+) -> Result<()> {
+    require!(amount > 0, crate::errors::ErrorCode::InvalidMarginAmount);
+    require!(
+        position.size > 0,
+        crate::errors::ErrorCode::InvalidPositionSize
+    );
+
+    let new_margin = position
+        .margin
+        .checked_sub(amount)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    require!(
+        new_margin > 0,
+        crate::errors::ErrorCode::InvalidMarginAmount
+    );
+    let new_leverage = calculate_effective_leverage(position.notional, new_margin)?;
+    require!(
+        new_leverage <= max_leverage,
+        crate::errors::ErrorCode::InvalidLeverage
+    );
+
+    accrue_borrow_fee(position, participant, now)?;
+    mark_position(position, participant, price)?;
+    require!(
+        new_margin
+            .checked_add(position.unrealized_pnl)
+            .ok_or(crate::errors::ErrorCode::MathOverflow)?
+            > 0,
+        crate::errors::ErrorCode::InsufficientMargin
+    );
+
+    position.margin = new_margin;
+    position.leverage = new_leverage;
+    participant.used_margin = participant
+        .used_margin
+        .checked_sub(amount)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    require_available_margin(participant)?;
+    update_liquidation_prices(position, participant)?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ClosePositionAccount<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"participant", league.key().as_ref(), user.key().as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"position", league.key().as_ref(), user.key().as_ref(), position.seq_num.to_le_bytes().as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, Position>,
+
+    pub league: Account<'info, League>,
+}
+
+/// Fold the position's final stats into the participant's history and return its rent to the user.
+/// Allowed once the position is fully closed, or for any position once the league is over.
+pub fn close_position_account(ctx: Context<ClosePositionAccount>) -> Result<()> {
+    let league = &ctx.accounts.league;
+    let participant = &mut ctx.accounts.participant;
+    let position = &mut ctx.accounts.position;
+
+    let league_over =
+        league.status == LeagueStatus::Closed || league.status == LeagueStatus::Finalized;
+    require!(
+        position.size == 0 || league_over,
+        crate::errors::ErrorCode::PositionStillOpen
+    );
+
+    // A position still open at league end is settled at its last mark, which releases its
+    // margin and moves its unrealized PnL into the balance
+    let position_key = position.key();
+    if position.size > 0 {
+        let now = Clock::get()?.unix_timestamp;
+        settle_at_last_mark(position, position_key, participant, now)?;
+    }
+
+    record_closed_position(participant, position)?;
+
+    msg!("Position account {:?} closed", position_key);
+    Ok(())
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LiquidationPrices {
+    pub isolated: i64,
+    pub cross: i64,
+}
+
+#[derive(Accounts)]
+pub struct GetLiquidationPrice<'info> {
+    #[account(
+        seeds = [b"participant", position.league.as_ref(), position.user.as_ref()],
+        bump = participant.bump
+    )]
+    pub participant: Account<'info, Participant>,
+
+    pub position: Account<'info, Position>,
+}
+
+/// View: liquidation price estimates of a position against the participant's current equity
+pub fn get_liquidation_price(ctx: Context<GetLiquidationPrice>) -> Result<LiquidationPrices> {
+    let mut position = (*ctx.accounts.position).clone();
+    update_liquidation_prices(&mut position, &ctx.accounts.participant)?;
+
+    Ok(LiquidationPrices {
+        isolated: position.liquidation_price,
+        cross: position.cross_liquidation_price,
+    })
+}
+
+/// In one-way mode the participant's open positions are passed first in remaining accounts,
+/// in the same order as `participant.positions`, to prove none is on `market`
+fn require_no_position_on_market(
+    participant: &Participant,
+    remaining: &[AccountInfo],
+    market: &Pubkey,
+) -> Result<()> {
+    require!(
+        remaining.len() == participant.positions.len(),
+        crate::errors::ErrorCode::InvalidRefreshAccounts
+    );
+    for (position_key, position_ai) in participant.positions.iter().zip(remaining) {
+        require_keys_eq!(
+            *position_key,
+            position_ai.key(),
+            crate::errors::ErrorCode::PositionMismatch
+        );
+        let data = position_ai.try_borrow_data()?;
+        let position: Position = Position::try_deserialize(&mut &data[..])?;
+        require!(
+            position.size == 0 || position.market != *market,
+            crate::errors::ErrorCode::PositionAlreadyExists
+        );
+    }
+    Ok(())
+}
+
+/// Positions trade only once every corporate action recorded on their market is applied
+pub(crate) fn require_corporate_actions_applied(
+    position: &Position,
+    market: &Market,
+) -> Result<()> {
+    require!(
+        position.corporate_actions_applied == market.corporate_action_count,
+        crate::errors::ErrorCode::CorporateActionPending
+    );
+    Ok(())
+}
+
+/// New exposure needs an active market and waits out a halt, or a move past the halt
+/// threshold that hasn't been observed yet
+pub(crate) fn require_market_not_halted(market: &Market, price: i64, now: i64) -> Result<()> {
+    require!(market.is_active, crate::errors::ErrorCode::MarketNotActive);
+    require!(
+        now >= market.halted_until && !volatility_halt_triggered(market, price, now)?,
+        crate::errors::ErrorCode::MarketHalted
+    );
+    Ok(())
+}
+
+/// Creator-listed markets open positions only in their creator's leagues until promoted
+pub(crate) fn require_market_listed_for(league: &League, market: &Market) -> Result<()> {
+    require!(
+        !market.restricted || market.listed_by == league.creator,
+        crate::errors::ErrorCode::MarketRestricted
+    );
+    Ok(())
+}
+
+/// Spot leagues only open unleveraged longs
+pub(crate) fn require_spot_order(
+    league: &League,
+    direction: &Direction,
+    leverage: u8,
+) -> Result<()> {
+    if league.league_type == LeagueType::Spot {
+        require!(
+            *direction == Direction::Long && leverage == 1,
+            crate::errors::ErrorCode::InvalidSpotOrder
+        );
+    }
+    Ok(())
+}
+
+/// Refresh both liquidation price estimates of a position.
+/// The cross estimate takes the participant's other positions at their last refreshed marks.
+pub(crate) fn update_liquidation_prices(
+    position: &mut Position,
+    participant: &Participant,
+) -> Result<()> {
+    let other_equity = participant
+        .equity()
+        .checked_sub(position.unrealized_pnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.liquidation_price = calculate_liquidation_price(
+        position.notional,
+        position.size,
+        position.market_decimals,
+        position.direction.clone(),
+        position.margin,
+    )?;
+    position.cross_liquidation_price = calculate_liquidation_price(
+        position.notional,
+        position.size,
+        position.market_decimals,
+        position.direction.clone(),
+        other_equity,
+    )?;
+    Ok(())
+}
+
+/// Charge the borrow fee accrued since the last accrual to the participant's balance.
+/// Called before anything changes the position's notional or margin.
+/// The fee runs at the rate snapshotted at open, `set_borrow_rate` only prices new positions.
+pub(crate) fn accrue_borrow_fee(
+    position: &mut Position,
+    participant: &mut Participant,
+    now: i64,
+) -> Result<i64> {
+    let elapsed = now.saturating_sub(position.last_fee_accrual_ts);
+    let fee = calculate_borrow_fee(
+        position.notional,
+        position.margin,
+        position.borrow_rate_bps,
+        elapsed,
+    )?;
+    position.last_fee_accrual_ts = position.last_fee_accrual_ts.max(now);
+    if fee == 0 {
+        return Ok(0);
+    }
+
+    position.borrow_fees_paid = position
+        .borrow_fees_paid
+        .checked_add(fee)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.virtual_balance = participant
+        .virtual_balance
+        .checked_sub(fee)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    Ok(fee)
+}
+
+/// Margin is checked after fills are applied, so a batch of fills is checked once
+pub(crate) fn require_available_margin(participant: &Participant) -> Result<()> {
+    require!(
+        participant.available_balance() >= 0,
+        crate::errors::ErrorCode::InsufficientMargin
+    );
+    Ok(())
+}
+
+/// Allocate the position PDA for `seq_num` and assign it to this program, leaving its rent
+/// to the caller. Unlike `create_account` this still works when someone has already sent
+/// lamports to the predictable address. Returns its bump.
+pub(crate) fn allocate_position_account<'info>(
+    position_ai: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    league_key: &Pubkey,
+    user_key: &Pubkey,
+    seq_num: u64,
+) -> Result<u8> {
+    let seq_bytes = seq_num.to_le_bytes();
+    let (expected, bump) = Pubkey::find_program_address(
+        &[
+            b"position",
+            league_key.as_ref(),
+            user_key.as_ref(),
+            seq_bytes.as_ref(),
+        ],
+        &crate::ID,
+    );
+    require_keys_eq!(
+        position_ai.key(),
+        expected,
+        crate::errors::ErrorCode::InvalidPositionSequence
+    );
+
+    let signer_seeds: &[&[u8]] = &[
+        b"position",
+        league_key.as_ref(),
+        user_key.as_ref(),
+        seq_bytes.as_ref(),
+        &[bump],
+    ];
+    allocate(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            Allocate {
+                account_to_allocate: position_ai.clone(),
+            },
+            &[signer_seeds],
+        ),
+        Position::SPACE as u64,
+    )?;
+    assign(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            Assign {
+                account_to_assign: position_ai.clone(),
+            },
+            &[signer_seeds],
+        ),
+        &crate::ID,
+    )?;
+
+    Ok(bump)
+}
+
+/// An empty position on `market`, to be filled by `fill_position`
+pub(crate) fn new_position(
+    league: Pubkey,
+    user: Pubkey,
+    market: &Account<Market>,
+    seq_num: u64,
+    direction: Direction,
+    bump: u8,
+    now: i64,
+) -> Position {
+    Position {
+        league,
+        user,
+        market: market.key(),
+        market_decimals: market.decimals,
+        oracle_feed: market.oracle_feed,
+        corporate_actions_applied: market.corporate_action_count,
+        seq_num,
+        direction,
+        entry_price: 0,
+        entry_size: 0,
+        leverage: 0,
+        margin: 0,
+        size: 0,
+        notional: 0,
+        unrealized_pnl: 0,
+        liquidation_price: 0,
+        cross_liquidation_price: 0,
+        borrow_rate_bps: market.borrow_rate_bps,
+        last_fee_accrual_ts: now,
+        borrow_fees_paid: 0,
+        opened_at: 0,
+        closed_at: 0,
+        closed_size: 0,
+        closed_price: 0,
+        closed_equity: 0,
+        closed_pnl: 0,
+        bump,
+        version: Position::VERSION,
+    }
+}
+
+/// Fill an empty position at `price`, locking `notional / leverage` as margin.
+/// `market_decimals` must already be set on the position. Margin is not checked here.
+pub(crate) fn fill_position(
+    position: &mut Position,
+    participant: &mut Participant,
+    direction: Direction,
+    size: i64,
+    leverage: u8,
+    price: i64,
+    now: i64,
+) -> Result<()> {
+    let notional = calculate_notional(price, size, position.market_decimals)?;
+    let required_margin = calculate_required_margin(notional, leverage)?;
+
+    position.direction = direction;
+    position.entry_size = size;
+    position.size = size;
+    position.entry_price = price;
+    position.notional = notional;
+    position.leverage = leverage;
+    position.margin = required_margin;
+    position.unrealized_pnl = 0;
+    position.opened_at = now;
+    position.closed_at = 0;
+    position.last_fee_accrual_ts = now;
+    position.borrow_fees_paid = 0;
+    // A flipped position starts its closed stats over
+    position.closed_size = 0;
+    position.closed_price = 0;
+    position.closed_equity = 0;
+    position.closed_pnl = 0;
+
+    // Update participant with overflow protection
+    participant.total_volume = participant
+        .total_volume
+        .checked_add(notional)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.used_margin = participant
+        .used_margin
+        .checked_add(required_margin)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    update_liquidation_prices(position, participant)?;
+
+    Ok(())
+}
+
+/// Add `size` to the position at `price`, at the position's current leverage. Margin is not checked here.
+pub(crate) fn increase_position(
+    position: &mut Position,
+    participant: &mut Participant,
+    size: i64,
+    price: i64,
+    now: i64,
+) -> Result<()> {
+    accrue_borrow_fee(position, participant, now)?;
+    let decimals = position.market_decimals;
+    let new_notional = calculate_notional(price, size, decimals)?;
+    let additional_margin = calculate_required_margin(new_notional, position.leverage)?;
+
+    // Update entry stats with overflow protection
+    position.entry_price = calculate_average_entry_price(
+        position.entry_price,
+        position.entry_size,
+        price,
+        size,
+        decimals,
+    )?;
+    position.entry_size = position
+        .entry_size
+        .checked_add(size)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    // Update realtime stats with overflow protection
+    let prev_upnl = position.unrealized_pnl;
+    position.size = position
+        .size
+        .checked_add(size)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.notional = position
+        .notional
+        .checked_add(new_notional)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.margin = position
+        .margin
+        .checked_add(additional_margin)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.unrealized_pnl = calculate_unrealized_pnl(
+        position.notional,
+        price,
+        position.size,
+        decimals,
+        position.direction.clone(),
+    )?;
+
+    // Update participant with overflow protection
+    participant.used_margin = participant
+        .used_margin
+        .checked_add(additional_margin)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.total_volume = participant
+        .total_volume
+        .checked_add(new_notional)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    let upnl_delta = position
+        .unrealized_pnl
+        .checked_sub(prev_upnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.unrealized_pnl = participant
+        .unrealized_pnl
+        .checked_add(upnl_delta)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    update_liquidation_prices(position, participant)?;
+
+    Ok(())
+}
+
+/// Close `size_to_close` of the position at `price`, returning the realized PnL.
+/// A fully closed position is removed from the participant.
+pub(crate) fn decrease_position(
+    position: &mut Position,
+    position_key: Pubkey,
+    participant: &mut Participant,
+    size_to_close: i64,
+    price: i64,
+    now: i64,
+) -> Result<i64> {
+    require!(
+        size_to_close <= position.size,
+        crate::errors::ErrorCode::InvalidReduceSize
+    );
+    accrue_borrow_fee(position, participant, now)?;
+    let decimals = position.market_decimals;
+    let prev_upnl = position.unrealized_pnl;
+
+    // Calculate realized PnL with overflow protection
+    let closing_equity = calculate_notional(price, size_to_close, decimals)?;
+    let closing_notional = calculate_pro_rata(
+        position.notional,
+        size_to_close,
+        position.size,
+        Rounding::TowardZero,
+    )?;
+    let realized_pnl = calculate_unrealized_pnl(
+        closing_notional,
+        price,
+        size_to_close,
+        decimals,
+        position.direction.clone(),
+    )?;
+    // Release margin pro rata to the closed size, rounded against the trader
+    let released_margin = calculate_pro_rata(
+        position.margin,
+        size_to_close,
+        position.size,
+        Rounding::Down,
+    )?;
+
+    // Calculate closed stats with overflow protection
+    position.closed_size = position
+        .closed_size
+        .checked_add(size_to_close)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.closed_equity = position
+        .closed_equity
+        .checked_add(closing_equity)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.closed_price = calculate_price_from_notional_and_size(
+        position.closed_equity,
+        position.closed_size,
+        decimals,
+    )?;
+    position.closed_pnl = position
+        .closed_pnl
+        .checked_add(realized_pnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    // Update position with overflow protection
+    position.size = position
+        .size
+        .checked_sub(size_to_close)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.notional = position
+        .notional
+        .checked_sub(closing_notional)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.margin = position
+        .margin
+        .checked_sub(released_margin)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.unrealized_pnl = calculate_unrealized_pnl(
+        position.notional,
+        price,
+        position.size,
+        decimals,
+        position.direction.clone(),
+    )?;
+
+    // Update participant with overflow protection
+    participant.total_volume = participant
+        .total_volume
+        .checked_add(closing_equity)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.used_margin = participant
+        .used_margin
+        .checked_sub(released_margin)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    participant.virtual_balance = participant
+        .virtual_balance
+        .checked_add(realized_pnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    let upnl_delta = position
+        .unrealized_pnl
+        .checked_sub(prev_upnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.unrealized_pnl = participant
+        .unrealized_pnl
+        .checked_add(upnl_delta)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    update_liquidation_prices(position, participant)?;
+
+    if position.size == 0 {
+        // close position logic here
+        position.closed_at = now;
+        // remove position from participant.positions vector
+        participant.positions.retain(|p| p != &position_key);
+        msg!("Position closed and removed from participant");
+    }
+
+    Ok(realized_pnl)
+}
+
+/// Fold a closed position's final stats into the participant's history
+pub(crate) fn record_closed_position(
+    participant: &mut Participant,
+    position: &Position,
+) -> Result<()> {
+    participant.closed_positions = participant
+        .closed_positions
+        .checked_add(1)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    if position.closed_pnl > 0 {
+        participant.winning_positions = participant
+            .winning_positions
+            .checked_add(1)
+            .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    }
+    participant.realized_pnl = participant
+        .realized_pnl
+        .checked_add(position.closed_pnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    Ok(())
+}
+
+/// Re-mark the position's unrealized PnL at `price`, carrying the change into the participant
+pub(crate) fn mark_position(
+    position: &mut Position,
+    participant: &mut Participant,
+    price: i64,
+) -> Result<()> {
+    let prev_upnl = position.unrealized_pnl;
+    position.unrealized_pnl = calculate_unrealized_pnl(
+        position.notional,
+        price,
+        position.size,
+        position.market_decimals,
+        position.direction.clone(),
+    )?;
+    let upnl_delta = position
+        .unrealized_pnl
+        .checked_sub(prev_upnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.unrealized_pnl = participant
+        .unrealized_pnl
+        .checked_add(upnl_delta)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    Ok(())
+}
+
+/// Close the whole position at the price it was last marked at, returning the realized PnL.
+/// Only used once the league is over, so no borrow fee accrues and no volume is counted.
+pub(crate) fn settle_at_last_mark(
+    position: &mut Position,
+    position_key: Pubkey,
+    participant: &mut Participant,
+    now: i64,
+) -> Result<i64> {
+    let realized_pnl = position.unrealized_pnl;
+    let closing_equity = match position.direction {
+        Direction::Long => position.notional.checked_add(realized_pnl),
+        Direction::Short => position.notional.checked_sub(realized_pnl),
+    }
+    .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    position.closed_size = position
+        .closed_size
+        .checked_add(position.size)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.closed_equity = position
+        .closed_equity
+        .checked_add(closing_equity)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    position.closed_price = calculate_price_from_notional_and_size(
+        position.closed_equity,
+        position.closed_size,
+        position.market_decimals,
+    )?;
+    position.closed_pnl = position
+        .closed_pnl
+        .checked_add(realized_pnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    participant.used_margin = participant
+        .used_margin
+        .checked_sub(position.margin)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.virtual_balance = participant
+        .virtual_balance
+        .checked_add(realized_pnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+    participant.unrealized_pnl = participant
+        .unrealized_pnl
+        .checked_sub(realized_pnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    position.size = 0;
+    position.notional = 0;
+    position.margin = 0;
+    position.unrealized_pnl = 0;
+    position.closed_at = now;
+    participant.positions.retain(|p| p != &position_key);
+
+    Ok(realized_pnl)
+}
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+
+    pub(crate) fn participant(virtual_balance: i64) -> Participant {
+        let mut participant =
+            Participant::try_deserialize_unchecked(&mut &vec![0u8; Participant::space(0)][..])
+                .unwrap();
+        participant.virtual_balance = virtual_balance;
+        participant
+    }
+
+    pub(crate) fn empty_position() -> Position {
+        let mut position =
+            Position::try_deserialize_unchecked(&mut &vec![0u8; Position::SPACE][..]).unwrap();
+        position.market_decimals = 6;
+        position
+    }
+
+    /// 10 units at 100, 5x
+    pub(crate) fn open_position(participant: &mut Participant, direction: Direction) -> Position {
+        let mut position = empty_position();
+        fill_position(
+            &mut position,
+            participant,
+            direction,
+            10_000_000,
+            5,
+            100_000_000,
+            0,
+        )
+        .unwrap();
+        position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{open_position, participant};
+    use super::*;
+    use crate::errors::ErrorCode;
+
+    fn market() -> Market {
+        let mut market =
+            Market::try_deserialize_unchecked(&mut &vec![0u8; Market::SPACE][..]).unwrap();
+        market.borrow_rate_bps = 10;
+        market
+    }
+
+    #[test]
+    fn borrow_fee_accrues_at_the_rate_taken_at_open() {
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+        position.borrow_rate_bps = 10;
+
+        // 800 borrowed for an hour at 10 bps/h
+        let fee = accrue_borrow_fee(&mut position, &mut participant, 3_600).unwrap();
+        assert_eq!(fee, 800_000);
+        assert_eq!(position.borrow_fees_paid, 800_000);
+        assert_eq!(position.last_fee_accrual_ts, 3_600);
+        assert_eq!(participant.virtual_balance, 10_000_000_000 - 800_000);
+
+        // Accruing again at the same time charges nothing twice
+        assert_eq!(
+            accrue_borrow_fee(&mut position, &mut participant, 3_600).unwrap(),
+            0
+        );
+        // and a stale timestamp never moves the accrual back
+        assert_eq!(
+            accrue_borrow_fee(&mut position, &mut participant, 0).unwrap(),
+            0
+        );
+        assert_eq!(position.last_fee_accrual_ts, 3_600);
+    }
+
+    #[test]
+    fn release_margin_from_healthy_position() {
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+        assert_eq!(position.margin, 200_000_000);
+
+        release_margin(
+            &mut position,
+            &mut participant,
+            100_000_000,
+            20,
+            100_000_000,
+            0,
+        )
+        .unwrap();
+        assert_eq!(position.margin, 100_000_000);
+        assert_eq!(position.leverage, 10);
+        assert_eq!(participant.used_margin, 100_000_000);
+    }
+
+    #[test]
+    fn release_margin_rejects_margin_below_unrealized_loss() {
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+
+        // Marked at 85 the position is 150 under water, 140 of margin can't cover it
+        let err = release_margin(
+            &mut position,
+            &mut participant,
+            60_000_000,
+            20,
+            85_000_000,
+            0,
+        )
+        .unwrap_err();
+        assert_eq!(err, ErrorCode::InsufficientMargin.into());
+
+        // 160 still covers it
+        let mut position = open_position(&mut participant, Direction::Long);
+        release_margin(
+            &mut position,
+            &mut participant,
+            40_000_000,
+            20,
+            85_000_000,
+            0,
+        )
+        .unwrap();
+        assert_eq!(position.unrealized_pnl, -150_000_000);
+    }
+
+    #[test]
+    fn release_margin_keeps_participant_solvent() {
+        let mut participant = participant(400_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+        // Losses on other positions leave 100 of equity against 200 of used margin
+        participant.unrealized_pnl = -300_000_000;
+
+        let err = release_margin(
+            &mut position,
+            &mut participant,
+            50_000_000,
+            20,
+            100_000_000,
+            0,
+        )
+        .unwrap_err();
+        assert_eq!(err, ErrorCode::InsufficientMargin.into());
+    }
+
+    #[test]
+    fn net_order_same_direction_increases() {
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+
+        let pnl = apply_net_order(
+            &mut position,
+            Pubkey::default(),
+            &mut participant,
+            &market(),
+            20,
+            Direction::Long,
+            10_000_000,
+            120_000_000,
+            0,
+        )
+        .unwrap();
+        assert_eq!(pnl, 0);
+        assert_eq!(position.size, 20_000_000);
+        assert_eq!(position.entry_size, 20_000_000);
+        assert_eq!(position.entry_price, 110_000_000);
+        assert_eq!(position.closed_size, 0);
+    }
+
+    #[test]
+    fn net_order_opposite_direction_reduces() {
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+
+        let pnl = apply_net_order(
+            &mut position,
+            Pubkey::default(),
+            &mut participant,
+            &market(),
+            20,
+            Direction::Short,
+            4_000_000,
+            110_000_000,
+            0,
+        )
+        .unwrap();
+        assert_eq!(pnl, 40_000_000);
+        assert!(position.direction == Direction::Long);
+        assert_eq!(position.size, 6_000_000);
+        assert_eq!(position.closed_size, 4_000_000);
+        assert_eq!(position.closed_pnl, 40_000_000);
+        assert_eq!(participant.used_margin, 120_000_000);
+    }
+
+    #[test]
+    fn net_order_exact_close() {
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+        let position_key = Pubkey::new_unique();
+        participant.positions.push(position_key);
+
+        let pnl = apply_net_order(
+            &mut position,
+            position_key,
+            &mut participant,
+            &market(),
+            20,
+            Direction::Short,
+            10_000_000,
+            90_000_000,
+            0,
+        )
+        .unwrap();
+        assert_eq!(pnl, -100_000_000);
+        assert_eq!(position.size, 0);
+        assert_eq!(position.closed_size, 10_000_000);
+        assert_eq!(position.closed_pnl, -100_000_000);
+        assert_eq!(participant.used_margin, 0);
+        assert!(participant.positions.is_empty());
+        // History is recorded when the account is closed
+        assert_eq!(participant.closed_positions, 0);
+    }
+
+    #[test]
+    fn net_order_flip_starts_fresh_stats() {
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+        position.borrow_rate_bps = 3;
+        let position_key = Pubkey::new_unique();
+        participant.positions.push(position_key);
+
+        // Sell 15 at 110: close 10 for +100, then short 5
+        let pnl = apply_net_order(
+            &mut position,
+            position_key,
+            &mut participant,
+            &market(),
+            20,
+            Direction::Short,
+            15_000_000,
+            110_000_000,
+            0,
+        )
+        .unwrap();
+        assert_eq!(pnl, 100_000_000);
+        assert!(position.direction == Direction::Short);
+        assert_eq!(position.size, 5_000_000);
+        assert_eq!(position.entry_size, 5_000_000);
+        assert_eq!(position.entry_price, 110_000_000);
+        assert_eq!(position.notional, 550_000_000);
+        assert_eq!(position.margin, 110_000_000);
+        assert_eq!(position.closed_size, 0);
+        assert_eq!(position.closed_price, 0);
+        assert_eq!(position.closed_equity, 0);
+        assert_eq!(position.closed_pnl, 0);
+        assert_eq!(participant.used_margin, 110_000_000);
+        assert_eq!(participant.positions, vec![position_key]);
+        // The short pays the market's current rate, not the one the long was opened at
+        assert_eq!(position.borrow_rate_bps, 10);
+
+        // The closed long went into the participant's history
+        assert_eq!(participant.closed_positions, 1);
+        assert_eq!(participant.winning_positions, 1);
+        assert_eq!(participant.realized_pnl, 100_000_000);
+    }
+
+    #[test]
+    fn net_order_flip_respects_the_current_leverage_cap() {
+        let mut flipping = participant(10_000_000_000);
+        let mut position = open_position(&mut flipping, Direction::Long);
+
+        let err = apply_net_order(
+            &mut position,
+            Pubkey::default(),
+            &mut flipping,
+            &market(),
+            4,
+            Direction::Short,
+            15_000_000,
+            110_000_000,
+            0,
+        )
+        .unwrap_err();
+        assert_eq!(err, ErrorCode::InvalidLeverage.into());
+
+        // Reducing is still allowed under the lower cap
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+        apply_net_order(
+            &mut position,
+            Pubkey::default(),
+            &mut participant,
+            &market(),
+            4,
+            Direction::Short,
+            5_000_000,
+            110_000_000,
+            0,
+        )
+        .unwrap();
+        assert_eq!(position.size, 5_000_000);
+    }
+
+    #[test]
+    fn settle_at_last_mark_releases_margin_and_realizes_pnl() {
+        let mut participant = participant(10_000_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+        let position_key = Pubkey::new_unique();
+        participant.positions.push(position_key);
+        mark_position(&mut position, &mut participant, 90_000_000).unwrap();
+        assert_eq!(participant.unrealized_pnl, -100_000_000);
+
+        let pnl = settle_at_last_mark(&mut position, position_key, &mut participant, 100).unwrap();
+        assert_eq!(pnl, -100_000_000);
+        assert_eq!(position.size, 0);
+        assert_eq!(position.closed_size, 10_000_000);
+        assert_eq!(position.closed_price, 90_000_000);
+        assert_eq!(position.closed_pnl, -100_000_000);
+        assert_eq!(position.closed_at, 100);
+        assert_eq!(participant.virtual_balance, 9_900_000_000);
+        assert_eq!(participant.unrealized_pnl, 0);
+        assert_eq!(participant.used_margin, 0);
+        assert!(participant.positions.is_empty());
+    }
+
+    #[test]
+    fn liquidation_prices_track_margin_and_account_equity() {
+        let mut participant = participant(1_000_000_000);
+        let mut position = open_position(&mut participant, Direction::Long);
+        // 200 of margin on 1,000 of notional
+        assert_eq!(position.liquidation_price, 80_000_000);
+        // The account's 1,000 of equity backs the position under cross margin
+        assert_eq!(position.cross_liquidation_price, 0);
+
+        // Losses elsewhere leave 600 of equity outside this position
+        participant.unrealized_pnl = -400_000_000;
+        update_liquidation_prices(&mut position, &participant).unwrap();
+        assert_eq!(position.liquidation_price, 80_000_000);
+        assert_eq!(position.cross_liquidation_price, 40_000_000);
+    }
+
+    #[test]
+    fn spot_leagues_only_buy_without_leverage() {
+        let mut league =
+            League::try_deserialize_unchecked(&mut &vec![0u8; League::SPACE][..]).unwrap();
+        require_spot_order(&league, &Direction::Short, 20).unwrap();
+
+        league.league_type = LeagueType::Spot;
+        require_spot_order(&league, &Direction::Long, 1).unwrap();
+        for (direction, leverage) in [(Direction::Short, 1), (Direction::Long, 2)] {
+            assert_eq!(
+                require_spot_order(&league, &direction, leverage).unwrap_err(),
+                ErrorCode::InvalidSpotOrder.into()
+            );
+        }
+    }
 }
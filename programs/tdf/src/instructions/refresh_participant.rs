@@ -1,7 +1,10 @@
-use crate::state::{Leaderboard, League, Participant, Position};
+use crate::instructions::position::{
+    accrue_borrow_fee, require_corporate_actions_applied, update_liquidation_prices,
+};
+use crate::state::{Leaderboard, League, LeagueType, MarginMode, Market, Participant, Position};
 use crate::utils::{
     calculate_notional, calculate_price_from_notional_and_size, calculate_unrealized_pnl,
-    get_price_from_oracle,
+    constituent_feed_count, convert_with_fx, get_market_price,
 };
 use anchor_lang::prelude::*;
 
@@ -29,8 +32,9 @@ pub struct RefreshParticipant<'info> {
     /// CHECK: This account is validated by the participant account's user field
     pub user: AccountInfo<'info>,
     pub league: Account<'info, League>,
-
-    // remaining accounts = [position_index_0, oracle_0, position_index_1, oracle_1, ...]
+    // remaining accounts = [position_index_0, market_0, oracle_0, (denominator_0), (fallback_0), (fx_0), position_index_1, market_1, oracle_1, ...]
+    // the denominator feed follows the oracle for positions on pair markets only, then the fallback and fx feeds for markets with them,
+    // the constituent feeds follow the basket account for positions on basket markets only
 }
 
 /// commit and update leaderboard
@@ -41,18 +45,26 @@ pub fn refresh_participant<'info>(
     let remaining: &[AccountInfo<'info>] = ctx.remaining_accounts;
     let position_keys = &participant.positions.clone();
 
-    require!(
-        remaining.len() == position_keys.len() * 2,
-        crate::errors::ErrorCode::InvalidRefreshAccounts
-    );
-
     let mut total_upnl: i64 = 0;
     let mut total_used_margin: i64 = 0;
     let mut prices: Vec<i64> = Vec::new();
-
-    for (i, position_key) in position_keys.iter().enumerate() {
-        let position_ai = &remaining[i * 2];
-        let oracle_ai = &remaining[i * 2 + 1];
+    let mut position_indices: Vec<usize> = Vec::new();
+    let mut cursor = 0;
+    let mut isolated_liquidations: Vec<Pubkey> = Vec::new();
+    let is_isolated = participant.margin_mode == MarginMode::Isolated;
+    // Spot holdings are fully paid for and are never liquidated
+    let is_spot = ctx.accounts.league.league_type == LeagueType::Spot;
+    let now = Clock::get()?.unix_timestamp;
+
+    for position_key in position_keys.iter() {
+        let accounts = remaining
+            .get(cursor..cursor + 3)
+            .ok_or(crate::errors::ErrorCode::InvalidRefreshAccounts)?;
+        let position_ai = &accounts[0];
+        let market: Account<'info, Market> = Account::try_from(&accounts[1])?;
+        let oracle_ai = &accounts[2];
+        position_indices.push(cursor);
+        cursor += 3;
 
         require_keys_eq!(
             *position_key,
@@ -63,31 +75,116 @@ pub fn refresh_participant<'info>(
         let mut data = position_ai.try_borrow_mut_data()?;
         let mut position: Position = Position::try_deserialize(&mut &data[..])?;
 
-        // if position is closed, skip
-        if position.size == 0 {
-            prices.push(0); // placeholder for closed positions
-            continue;
-        }
-
+        require_keys_eq!(
+            position.market,
+            market.key(),
+            crate::errors::ErrorCode::PositionMismatch
+        );
         require_keys_eq!(
             position.oracle_feed,
             oracle_ai.key(),
             crate::errors::ErrorCode::OracleMismatch
         );
+        let denominator_ai = if market.denominator_feed.is_some() {
+            let denominator_ai = remaining
+                .get(cursor)
+                .ok_or(crate::errors::ErrorCode::InvalidRefreshAccounts)?;
+            cursor += 1;
+            Some(denominator_ai)
+        } else {
+            None
+        };
+        let fallback_ai = if market.fallback_feed.is_some() {
+            let fallback_ai = remaining
+                .get(cursor)
+                .ok_or(crate::errors::ErrorCode::InvalidRefreshAccounts)?;
+            cursor += 1;
+            Some(fallback_ai)
+        } else {
+            None
+        };
+        let fx_ai = if market.fx_feed.is_some() {
+            let fx_ai = remaining
+                .get(cursor)
+                .ok_or(crate::errors::ErrorCode::InvalidRefreshAccounts)?;
+            cursor += 1;
+            Some(fx_ai)
+        } else {
+            None
+        };
+        let constituent_count = constituent_feed_count(oracle_ai)?;
+        let constituent_feeds = remaining
+            .get(cursor..cursor + constituent_count)
+            .ok_or(crate::errors::ErrorCode::InvalidRefreshAccounts)?;
+        cursor += constituent_count;
+
+        // if position is closed, skip
+        if position.size == 0 {
+            prices.push(0); // placeholder for closed positions
+            continue;
+        }
 
-        let price = get_price_from_oracle(&oracle_ai)?;
+        // A split would otherwise read as a crash, so stale positions block the refresh
+        require_corporate_actions_applied(&position, &market)?;
+
+        let price = get_market_price(
+            oracle_ai,
+            denominator_ai,
+            fallback_ai,
+            constituent_feeds,
+            &market.price_config(),
+            now,
+        )?;
+        let price = convert_with_fx(price, fx_ai, market.fx_feed, &market.price_config(), now)?;
         prices.push(price);
 
+        // Borrow fee is settled into the balance before the position is marked
+        accrue_borrow_fee(&mut position, participant, now)?;
+
         let new_upnl = calculate_unrealized_pnl(
             position.notional,
             price,
             position.size,
             position.market_decimals,
             position.direction.clone(),
-        );
+        )?;
 
         position.unrealized_pnl = new_upnl;
 
+        // Isolated margin: the position is liquidated on its own once its loss eats its margin
+        if !is_spot
+            && is_isolated
+            && position
+                .margin
+                .checked_add(new_upnl)
+                .ok_or(crate::errors::ErrorCode::MathOverflow)?
+                < 0
+        {
+            // Loss is capped at the margin locked by the position
+            let realized_pnl = new_upnl.max(-position.margin);
+            let closing_equity = liquidate_position(&mut position, price, realized_pnl, now)?;
+
+            participant.total_volume = participant
+                .total_volume
+                .checked_add(closing_equity)
+                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+            participant.virtual_balance = participant
+                .virtual_balance
+                .checked_add(realized_pnl)
+                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+            isolated_liquidations.push(*position_key);
+
+            msg!(
+                "💥 Isolated position liquidated: {} (realized_pnl: {})",
+                position_key,
+                realized_pnl
+            );
+
+            let mut dst = &mut data[..];
+            position.try_serialize(&mut dst)?;
+            continue;
+        }
+
         let mut dst = &mut data[..];
         position.try_serialize(&mut dst)?;
 
@@ -95,17 +192,21 @@ pub fn refresh_participant<'info>(
             .checked_add(new_upnl)
             .ok_or(crate::errors::ErrorCode::MathOverflow)?;
 
-        let margin_for_pos = position
-            .notional
-            .checked_div(position.leverage as i64)
-            .ok_or(crate::errors::ErrorCode::MathOverflow)?;
         total_used_margin = total_used_margin
-            .checked_add(margin_for_pos)
+            .checked_add(position.margin)
             .ok_or(crate::errors::ErrorCode::MathOverflow)?;
     }
 
+    require!(
+        cursor == remaining.len(),
+        crate::errors::ErrorCode::InvalidRefreshAccounts
+    );
+
     participant.unrealized_pnl = total_upnl;
     participant.used_margin = total_used_margin;
+    participant
+        .positions
+        .retain(|p| !isolated_liquidations.contains(p));
 
     msg!(
         "Participant updated: unrealized_pnl: {}, used_margin: {}, equity: {}",
@@ -114,10 +215,11 @@ pub fn refresh_participant<'info>(
         participant.equity()
     );
 
-    if participant.equity() < 0 {
+    // Cross margin: the whole account is liquidated once total equity goes negative
+    if !is_spot && !is_isolated && participant.equity() < 0 {
         msg!("💥 Auto liquidation triggered");
         for (i, position_key) in position_keys.iter().enumerate() {
-            let position_ai = &remaining[i * 2];
+            let position_ai = &remaining[position_indices[i]];
 
             let mut data = position_ai.try_borrow_mut_data()?;
             let mut position: Position = Position::try_deserialize(&mut &data[..])?;
@@ -128,42 +230,8 @@ pub fn refresh_participant<'info>(
 
             let price = prices[i];
             let realized_pnl = position.unrealized_pnl;
-            let released_margin = position
-                .notional
-                .checked_div(position.leverage as i64)
-                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-            let closing_equity = calculate_notional(price, position.size, position.market_decimals);
-
-            // Calculate closed stats with overflow protection
-            position.closed_size = position
-                .closed_size
-                .checked_add(position.size)
-                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-
-            position.closed_equity = position
-                .closed_equity
-                .checked_add(closing_equity)
-                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-
-            // Safe division for closed_price
-            if position.closed_size > 0 {
-                position.closed_price = calculate_price_from_notional_and_size(
-                    position.closed_equity,
-                    position.closed_size,
-                    position.market_decimals,
-                );
-            }
-
-            position.closed_pnl = position
-                .closed_pnl
-                .checked_add(realized_pnl)
-                .ok_or(crate::errors::ErrorCode::MathOverflow)?;
-
-            // Update position
-            position.size = 0;
-            position.notional = 0;
-            position.unrealized_pnl = 0;
-            position.closed_at = Clock::get()?.unix_timestamp;
+            let released_margin = position.margin;
+            let closing_equity = liquidate_position(&mut position, price, realized_pnl, now)?;
 
             // Update participant with overflow protection
             participant.total_volume = participant
@@ -197,6 +265,21 @@ pub fn refresh_participant<'info>(
         participant.unrealized_pnl = 0;
 
         msg!("All positions liquidated. Participant equity reset.");
+    } else {
+        // Cross estimates depend on the participant's total equity, known only now
+        for (i, position_key) in position_keys.iter().enumerate() {
+            if !participant.positions.contains(position_key) {
+                continue;
+            }
+            let position_ai = &remaining[position_indices[i]];
+
+            let mut data = position_ai.try_borrow_mut_data()?;
+            let mut position: Position = Position::try_deserialize(&mut &data[..])?;
+            update_liquidation_prices(&mut position, participant)?;
+
+            let mut dst = &mut data[..];
+            position.try_serialize(&mut dst)?;
+        }
     }
 
     // Update leaderboard
@@ -218,6 +301,52 @@ pub fn refresh_participant<'info>(
     Ok(())
 }
 
+/// Close the whole position at `price`, realizing `realized_pnl`. Returns the closing equity.
+fn liquidate_position(
+    position: &mut Position,
+    price: i64,
+    realized_pnl: i64,
+    now: i64,
+) -> Result<i64> {
+    let closing_equity = calculate_notional(price, position.size, position.market_decimals)?;
+
+    // Calculate closed stats with overflow protection
+    position.closed_size = position
+        .closed_size
+        .checked_add(position.size)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    position.closed_equity = position
+        .closed_equity
+        .checked_add(closing_equity)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    // Safe division for closed_price
+    if position.closed_size > 0 {
+        position.closed_price = calculate_price_from_notional_and_size(
+            position.closed_equity,
+            position.closed_size,
+            position.market_decimals,
+        )?;
+    }
+
+    position.closed_pnl = position
+        .closed_pnl
+        .checked_add(realized_pnl)
+        .ok_or(crate::errors::ErrorCode::MathOverflow)?;
+
+    // Update position
+    position.size = 0;
+    position.notional = 0;
+    position.margin = 0;
+    position.unrealized_pnl = 0;
+    position.liquidation_price = 0;
+    position.cross_liquidation_price = 0;
+    position.closed_at = now;
+
+    Ok(closing_equity)
+}
+
 fn update_topk_equity(leaderboard: &mut Leaderboard, key: Pubkey, score: i64) -> Result<()> {
     let list = &mut leaderboard.topk_equity;
     let scores = &mut leaderboard.topk_equity_scores;
@@ -2,7 +2,9 @@ use anchor_lang::prelude::*;
 use ephemeral_rollups_sdk::anchor::ephemeral;
 
 mod errors;
+mod events;
 mod instructions;
+mod math;
 mod state;
 mod utils;
 
@@ -24,14 +26,220 @@ pub mod tdf {
         instructions::initialize_global_state(ctx, fee_bps, treasury, permission_program)
     }
 
+    pub fn grant_role(
+        ctx: Context<UpdateGlobalState>,
+        key: Pubkey,
+        role: state::Role,
+    ) -> Result<()> {
+        instructions::grant_role(ctx, key, role)
+    }
+
+    pub fn revoke_role(
+        ctx: Context<UpdateGlobalState>,
+        key: Pubkey,
+        role: state::Role,
+    ) -> Result<()> {
+        instructions::revoke_role(ctx, key, role)
+    }
+
+    pub fn propose_admin(ctx: Context<UpdateGlobalState>, new_admin: Option<Pubkey>) -> Result<()> {
+        instructions::propose_admin(ctx, new_admin)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        instructions::accept_admin(ctx)
+    }
+
+    pub fn set_fee_bps(ctx: Context<SetFeeBps>, fee_bps: u16) -> Result<()> {
+        instructions::set_fee_bps(ctx, fee_bps)
+    }
+
+    pub fn set_treasury(ctx: Context<SetTreasury>) -> Result<()> {
+        instructions::set_treasury(ctx)
+    }
+
+    pub fn set_permission_program(
+        ctx: Context<UpdateGlobalState>,
+        permission_program: Pubkey,
+    ) -> Result<()> {
+        instructions::set_permission_program(ctx, permission_program)
+    }
+
+    // Pause instructions
+    pub fn set_protocol_pause(
+        ctx: Context<SetProtocolPause>,
+        paused: bool,
+        withdrawals_paused: bool,
+    ) -> Result<()> {
+        instructions::set_protocol_pause(ctx, paused, withdrawals_paused)
+    }
+
+    pub fn set_league_pause(
+        ctx: Context<SetLeaguePause>,
+        paused: bool,
+        withdrawals_paused: bool,
+    ) -> Result<()> {
+        instructions::set_league_pause(ctx, paused, withdrawals_paused)
+    }
+
     // Market instructions
     pub fn list_market(
         ctx: Context<ListMarket>,
         symbol: [u8; 16],
         decimals: u8,
         max_leverage: u8,
+        borrow_rate_bps: u16,
+        max_price_age: i64,
+        price_decimals: u8,
+        fx_price_decimals: u8,
     ) -> Result<()> {
-        instructions::list_market(ctx, symbol, decimals, max_leverage)
+        instructions::list_market(
+            ctx,
+            symbol,
+            decimals,
+            max_leverage,
+            borrow_rate_bps,
+            max_price_age,
+            price_decimals,
+            fx_price_decimals,
+        )
+    }
+
+    pub fn list_pair_market(
+        ctx: Context<ListPairMarket>,
+        symbol: [u8; 16],
+        decimals: u8,
+        max_leverage: u8,
+        borrow_rate_bps: u16,
+        max_price_age: i64,
+        price_decimals: u8,
+        denominator_price_decimals: u8,
+        fx_price_decimals: u8,
+    ) -> Result<()> {
+        instructions::list_pair_market(
+            ctx,
+            symbol,
+            decimals,
+            max_leverage,
+            borrow_rate_bps,
+            max_price_age,
+            price_decimals,
+            denominator_price_decimals,
+            fx_price_decimals,
+        )
+    }
+
+    pub fn list_basket_market(
+        ctx: Context<ListBasketMarket>,
+        symbol: [u8; 16],
+        decimals: u8,
+        max_leverage: u8,
+        borrow_rate_bps: u16,
+        max_price_age: i64,
+        fx_price_decimals: u8,
+        feeds: Vec<Pubkey>,
+        weights: Vec<i64>,
+        price_decimals: Vec<u8>,
+    ) -> Result<()> {
+        instructions::list_basket_market(
+            ctx,
+            symbol,
+            decimals,
+            max_leverage,
+            borrow_rate_bps,
+            max_price_age,
+            fx_price_decimals,
+            feeds,
+            weights,
+            price_decimals,
+        )
+    }
+
+    pub fn set_basket_weights(
+        ctx: Context<SetBasketWeights>,
+        feeds: Vec<Pubkey>,
+        weights: Vec<i64>,
+        price_decimals: Vec<u8>,
+    ) -> Result<()> {
+        instructions::set_basket_weights(ctx, feeds, weights, price_decimals)
+    }
+
+    pub fn record_corporate_action(
+        ctx: Context<RecordCorporateAction>,
+        kind: state::CorporateActionKind,
+    ) -> Result<()> {
+        instructions::record_corporate_action(ctx, kind)
+    }
+
+    pub fn apply_corporate_action(ctx: Context<ApplyCorporateAction>) -> Result<()> {
+        instructions::apply_corporate_action(ctx)
+    }
+
+    pub fn set_borrow_rate(ctx: Context<SetBorrowRate>, borrow_rate_bps: u16) -> Result<()> {
+        instructions::set_borrow_rate(ctx, borrow_rate_bps)
+    }
+
+    pub fn set_fallback_feed(
+        ctx: Context<SetFallbackFeed>,
+        fallback_feed: Option<Pubkey>,
+        max_divergence_bps: u16,
+        fallback_price_decimals: u8,
+    ) -> Result<()> {
+        instructions::set_fallback_feed(
+            ctx,
+            fallback_feed,
+            max_divergence_bps,
+            fallback_price_decimals,
+        )
+    }
+
+    pub fn set_halt_config(
+        ctx: Context<SetHaltConfig>,
+        halt_threshold_bps: u16,
+        halt_window: i64,
+        halt_cooldown: i64,
+    ) -> Result<()> {
+        instructions::set_halt_config(ctx, halt_threshold_bps, halt_window, halt_cooldown)
+    }
+
+    pub fn observe_market_price(ctx: Context<ObserveMarketPrice>) -> Result<()> {
+        instructions::observe_market_price(ctx)
+    }
+
+    pub fn resume_market(ctx: Context<ResumeMarket>) -> Result<()> {
+        instructions::resume_market(ctx)
+    }
+
+    pub fn set_listing_bond(ctx: Context<SetListingBond>, mint: Pubkey, amount: u64) -> Result<()> {
+        instructions::set_listing_bond(ctx, mint, amount)
+    }
+
+    pub fn list_community_market(
+        ctx: Context<ListCommunityMarket>,
+        symbol: [u8; 16],
+        decimals: u8,
+        max_leverage: u8,
+        borrow_rate_bps: u16,
+        max_price_age: i64,
+        price_decimals: u8,
+    ) -> Result<()> {
+        instructions::list_community_market(
+            ctx,
+            symbol,
+            decimals,
+            max_leverage,
+            borrow_rate_bps,
+            max_price_age,
+            price_decimals,
+        )
+    }
+
+    pub fn promote_market(ctx: Context<ReleaseListingBond>) -> Result<()> {
+        instructions::promote_market(ctx)
+    }
+
+    pub fn delist_community_market(ctx: Context<ReleaseListingBond>, slash: bool) -> Result<()> {
+        instructions::delist_community_market(ctx, slash)
     }
 
     // League instructions
@@ -47,6 +255,11 @@ pub mod tdf {
         max_leverage: u8,
         nonce: u8,
         k: u16,
+        margin_mode: state::MarginMode,
+        margin_mode_selectable: bool,
+        position_mode: state::PositionMode,
+        max_open_positions: u8,
+        league_type: state::LeagueType,
     ) -> Result<()> {
         instructions::create_league(
             ctx,
@@ -60,6 +273,11 @@ pub mod tdf {
             max_leverage,
             nonce,
             k,
+            margin_mode,
+            margin_mode_selectable,
+            position_mode,
+            max_open_positions,
+            league_type,
         )
     }
 
@@ -67,6 +285,13 @@ pub mod tdf {
         instructions::join_league(ctx, amount)
     }
 
+    pub fn set_margin_mode(
+        ctx: Context<SetMarginMode>,
+        margin_mode: state::MarginMode,
+    ) -> Result<()> {
+        instructions::set_margin_mode(ctx, margin_mode)
+    }
+
     pub fn delegate_participant(ctx: Context<DelegateParticipant>, league_key: Pubkey) -> Result<()> {
         instructions::delegate_participant(ctx, league_key)
     }
@@ -90,23 +315,120 @@ pub mod tdf {
         size: i64,
         leverage: u8,
         seq_num: u64,
+        acceptable_price: Option<i64>,
+    ) -> Result<()> {
+        instructions::open_position(ctx, direction, size, leverage, seq_num, acceptable_price)
+    }
+
+    pub fn execute_orders<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteOrders<'info>>,
+        legs: Vec<OrderLeg>,
+    ) -> Result<()> {
+        instructions::execute_orders(ctx, legs)
+    }
+
+    // DCA instructions
+    pub fn create_dca_schedule(
+        ctx: Context<CreateDcaSchedule>,
+        nonce: u32,
+        action: state::DcaAction,
+        open: Option<state::DcaOpen>,
+        slice_size: i64,
+        total_slices: u32,
+        interval: i64,
+        start_ts: i64,
+        expires_at: i64,
+        acceptable_price: Option<i64>,
+    ) -> Result<()> {
+        instructions::create_dca_schedule(
+            ctx,
+            nonce,
+            action,
+            open,
+            slice_size,
+            total_slices,
+            interval,
+            start_ts,
+            expires_at,
+            acceptable_price,
+        )
+    }
+
+    pub fn execute_dca_slice(ctx: Context<ExecuteDcaSlice>) -> Result<()> {
+        instructions::execute_dca_slice(ctx)
+    }
+
+    pub fn cancel_dca_schedule(ctx: Context<CancelDcaSchedule>) -> Result<()> {
+        instructions::cancel_dca_schedule(ctx)
+    }
+
+    pub fn create_binary_instrument(
+        ctx: Context<CreateBinaryInstrument>,
+        strike_price: i64,
+        expiry_ts: i64,
     ) -> Result<()> {
-        instructions::open_position(ctx, direction, size, leverage, seq_num)
+        instructions::create_binary_instrument(ctx, strike_price, expiry_ts)
+    }
+
+    pub fn stake_binary(
+        ctx: Context<StakeBinary>,
+        side: state::BinarySide,
+        amount: i64,
+    ) -> Result<()> {
+        instructions::stake_binary(ctx, side, amount)
+    }
+
+    pub fn settle_binary_instrument(ctx: Context<SettleBinaryInstrument>) -> Result<()> {
+        instructions::settle_binary_instrument(ctx)
+    }
+
+    pub fn settle_binary_stake(ctx: Context<SettleBinaryStake>) -> Result<()> {
+        instructions::settle_binary_stake(ctx)
     }
 
     pub fn delegate_position(ctx: Context<DelegatePosition>, league_key: Pubkey, seq_num: u64) -> Result<()> {
         instructions::delegate_position(ctx, league_key, seq_num)
     }
 
-    pub fn increase_position_size(ctx: Context<IncreasePositionSize>, size: i64) -> Result<()> {
-        instructions::increase_position_size(ctx, size)
+    pub fn increase_position_size(
+        ctx: Context<IncreasePositionSize>,
+        size: i64,
+        acceptable_price: Option<i64>,
+    ) -> Result<()> {
+        instructions::increase_position_size(ctx, size, acceptable_price)
     }
 
     pub fn decrease_position_size(
         ctx: Context<DecreasePositionSize>,
         size_to_close: i64,
+        acceptable_price: Option<i64>,
     ) -> Result<()> {
-        instructions::decrease_position_size(ctx, size_to_close)
+        instructions::decrease_position_size(ctx, size_to_close, acceptable_price)
+    }
+
+    pub fn net_position(
+        ctx: Context<NetPosition>,
+        direction: state::Direction,
+        size: i64,
+        acceptable_price: Option<i64>,
+    ) -> Result<()> {
+        instructions::net_position(ctx, direction, size, acceptable_price)
+    }
+
+    pub fn close_position_account(ctx: Context<ClosePositionAccount>) -> Result<()> {
+        instructions::close_position_account(ctx)
+    }
+
+    pub fn get_liquidation_price(ctx: Context<GetLiquidationPrice>) -> Result<LiquidationPrices> {
+        instructions::get_liquidation_price(ctx)
+    }
+
+    pub fn add_margin(ctx: Context<AdjustMargin>, amount: i64) -> Result<()> {
+        instructions::add_margin(ctx, amount)
+    }
+
+    pub fn remove_margin(ctx: Context<RemoveMargin>, amount: i64) -> Result<()> {
+        instructions::remove_margin(ctx, amount)
     }
 
     // Refresh participant instruction
@@ -120,4 +442,29 @@ pub mod tdf {
     pub fn claim_reward(ctx: Context<ClaimReward>) -> Result<()> {
         instructions::claim_reward(ctx)
     }
+
+    // Migration instructions
+    pub fn migrate_global_state(ctx: Context<MigrateAccount>) -> Result<()> {
+        instructions::migrate_global_state(ctx)
+    }
+
+    pub fn migrate_market(ctx: Context<MigrateAccount>) -> Result<()> {
+        instructions::migrate_market(ctx)
+    }
+
+    pub fn migrate_league(ctx: Context<MigrateAccount>) -> Result<()> {
+        instructions::migrate_league(ctx)
+    }
+
+    pub fn migrate_leaderboard(ctx: Context<MigrateAccount>) -> Result<()> {
+        instructions::migrate_leaderboard(ctx)
+    }
+
+    pub fn migrate_participant(ctx: Context<MigrateParticipant>) -> Result<()> {
+        instructions::migrate_participant(ctx)
+    }
+
+    pub fn migrate_position(ctx: Context<MigrateAccount>) -> Result<()> {
+        instructions::migrate_position(ctx)
+    }
 }
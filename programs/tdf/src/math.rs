@@ -0,0 +1,313 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::ErrorCode;
+
+/// How a result is rounded when it has to drop precision
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,       // toward negative infinity
+    Up,         // toward positive infinity
+    TowardZero, // truncate, neither side gains from the dropped digits
+}
+
+/// Signed fixed-point number, `raw / 10^decimals`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fixed {
+    raw: i128,
+    decimals: u8,
+}
+
+impl Fixed {
+    pub const fn new(raw: i64, decimals: u8) -> Self {
+        Self {
+            raw: raw as i128,
+            decimals,
+        }
+    }
+
+    pub const fn from_int(value: i64) -> Self {
+        Self::new(value, 0)
+    }
+
+    /// Change the number of decimals, rounding only when precision is dropped
+    pub fn rescale(self, decimals: u8, rounding: Rounding) -> Result<Self> {
+        let raw = if decimals >= self.decimals {
+            self.raw
+                .checked_mul(pow10(decimals - self.decimals)?)
+                .ok_or(ErrorCode::MathOverflow)?
+        } else {
+            div_round(self.raw, pow10(self.decimals - decimals)?, rounding)?
+        };
+        Ok(Self { raw, decimals })
+    }
+
+    /// Exact sum, at the larger of the two precisions
+    pub fn checked_add(self, rhs: Self) -> Result<Self> {
+        let (lhs, rhs) = Self::align(self, rhs)?;
+        let raw = lhs
+            .raw
+            .checked_add(rhs.raw)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(Self { raw, ..lhs })
+    }
+
+    /// Exact difference, at the larger of the two precisions
+    pub fn checked_sub(self, rhs: Self) -> Result<Self> {
+        let (lhs, rhs) = Self::align(self, rhs)?;
+        let raw = lhs
+            .raw
+            .checked_sub(rhs.raw)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(Self { raw, ..lhs })
+    }
+
+    pub fn checked_neg(self) -> Result<Self> {
+        let raw = self.raw.checked_neg().ok_or(ErrorCode::MathOverflow)?;
+        Ok(Self { raw, ..self })
+    }
+
+    /// Exact product, with the sum of both precisions
+    pub fn checked_mul_exact(self, rhs: Self) -> Result<Self> {
+        let raw = self
+            .raw
+            .checked_mul(rhs.raw)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let decimals = self
+            .decimals
+            .checked_add(rhs.decimals)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(Self { raw, decimals })
+    }
+
+    /// Product with `decimals` decimals, rounded once from the exact result
+    pub fn checked_mul(self, rhs: Self, decimals: u8, rounding: Rounding) -> Result<Self> {
+        self.checked_mul_exact(rhs)?.rescale(decimals, rounding)
+    }
+
+    /// Quotient with `decimals` decimals. Scales the dividend up before dividing,
+    /// so the only precision lost is the final rounding.
+    pub fn checked_div(self, rhs: Self, decimals: u8, rounding: Rounding) -> Result<Self> {
+        // (a / 10^da) / (b / 10^db) * 10^dc = a * 10^(db + dc) / (b * 10^da)
+        let numerator = self
+            .raw
+            .checked_mul(pow10(rhs.decimals as u32 + decimals as u32)?)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let denominator = rhs
+            .raw
+            .checked_mul(pow10(self.decimals as u32)?)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let raw = div_round(numerator, denominator, rounding)?;
+        Ok(Self { raw, decimals })
+    }
+
+    /// Raw value at the current precision, if it fits an i64
+    pub fn to_i64(self) -> Result<i64> {
+        i64::try_from(self.raw).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Raw value rescaled to `decimals`, if it fits an i64
+    pub fn to_i64_at(self, decimals: u8, rounding: Rounding) -> Result<i64> {
+        self.rescale(decimals, rounding)?.to_i64()
+    }
+
+    fn align(lhs: Self, rhs: Self) -> Result<(Self, Self)> {
+        let decimals = lhs.decimals.max(rhs.decimals);
+        // Scaling up is exact, the rounding mode is never used
+        Ok((
+            lhs.rescale(decimals, Rounding::TowardZero)?,
+            rhs.rescale(decimals, Rounding::TowardZero)?,
+        ))
+    }
+}
+
+fn pow10<T: Into<u32>>(exp: T) -> Result<i128> {
+    10i128
+        .checked_pow(exp.into())
+        .ok_or(ErrorCode::MathOverflow.into())
+}
+
+/// Integer division with an explicit rounding mode
+pub fn div_round(numerator: i128, denominator: i128, rounding: Rounding) -> Result<i128> {
+    require!(denominator != 0, ErrorCode::MathOverflow);
+    let quotient = numerator
+        .checked_div(denominator)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let remainder = numerator % denominator;
+    if remainder == 0 {
+        return Ok(quotient);
+    }
+
+    // `/` truncates, so an inexact quotient sits just above the true value
+    // when it's negative and just below it when it's positive
+    let is_negative = (remainder < 0) != (denominator < 0);
+    let adjusted = match rounding {
+        Rounding::TowardZero => quotient,
+        Rounding::Down if is_negative => quotient - 1,
+        Rounding::Up if !is_negative => quotient + 1,
+        Rounding::Down | Rounding::Up => quotient,
+    };
+    Ok(adjusted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small deterministic generator for property checks
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn range(&mut self, lo: i64, hi: i64) -> i64 {
+            lo + (self.next() % (hi - lo + 1) as u64) as i64
+        }
+    }
+
+    #[test]
+    fn div_round_modes() {
+        assert_eq!(div_round(7, 2, Rounding::Down).unwrap(), 3);
+        assert_eq!(div_round(7, 2, Rounding::Up).unwrap(), 4);
+        assert_eq!(div_round(7, 2, Rounding::TowardZero).unwrap(), 3);
+        assert_eq!(div_round(-7, 2, Rounding::Down).unwrap(), -4);
+        assert_eq!(div_round(-7, 2, Rounding::Up).unwrap(), -3);
+        assert_eq!(div_round(-7, 2, Rounding::TowardZero).unwrap(), -3);
+        assert_eq!(div_round(7, -2, Rounding::Down).unwrap(), -4);
+        assert_eq!(div_round(-7, -2, Rounding::Up).unwrap(), 4);
+        assert_eq!(div_round(6, 2, Rounding::Up).unwrap(), 3);
+        assert!(div_round(1, 0, Rounding::Down).is_err());
+    }
+
+    #[test]
+    fn mul_rounds_once_from_exact_product() {
+        // 150.5 * 0.333 = 50.1165
+        let price = Fixed::new(150_500_000, 6);
+        let size = Fixed::new(333, 3);
+        assert_eq!(
+            price
+                .checked_mul(size, 2, Rounding::Down)
+                .unwrap()
+                .to_i64()
+                .unwrap(),
+            5_011
+        );
+        assert_eq!(
+            price
+                .checked_mul(size, 2, Rounding::Up)
+                .unwrap()
+                .to_i64()
+                .unwrap(),
+            5_012
+        );
+        assert_eq!(
+            price
+                .checked_mul(size, 6, Rounding::Up)
+                .unwrap()
+                .to_i64()
+                .unwrap(),
+            50_116_500
+        );
+    }
+
+    #[test]
+    fn div_multiplies_before_dividing() {
+        // 1 / 3 at 6 decimals keeps all six digits
+        let one = Fixed::new(1_000_000, 6);
+        let three = Fixed::new(3, 0);
+        assert_eq!(
+            one.checked_div(three, 6, Rounding::TowardZero)
+                .unwrap()
+                .to_i64()
+                .unwrap(),
+            333_333
+        );
+        assert_eq!(
+            one.checked_div(three, 6, Rounding::Up)
+                .unwrap()
+                .to_i64()
+                .unwrap(),
+            333_334
+        );
+        assert!(one.checked_div(Fixed::new(0, 0), 6, Rounding::Up).is_err());
+    }
+
+    #[test]
+    fn add_and_sub_align_precision() {
+        let a = Fixed::new(1_500, 3); // 1.5
+        let b = Fixed::new(25, 1); // 2.5
+        assert_eq!(a.checked_add(b).unwrap(), Fixed::new(4_000, 3));
+        assert_eq!(a.checked_sub(b).unwrap(), Fixed::new(-1_000, 3));
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let max = Fixed::new(i64::MAX, 0);
+        assert!(max
+            .checked_mul(max, 0, Rounding::Down)
+            .unwrap()
+            .to_i64()
+            .is_err());
+        assert!(max.rescale(30, Rounding::Down).is_err());
+        assert!(Fixed::new(1, 0).rescale(40, Rounding::Down).is_err());
+    }
+
+    #[test]
+    fn prop_rounding_brackets_the_exact_quotient() {
+        let mut rng = XorShift(0x5eed);
+        for _ in 0..10_000 {
+            let n = rng.range(-1_000_000_000, 1_000_000_000) as i128;
+            let mut d = rng.range(-1_000_000, 1_000_000) as i128;
+            if d == 0 {
+                d = 1;
+            }
+            let down = div_round(n, d, Rounding::Down).unwrap();
+            let up = div_round(n, d, Rounding::Up).unwrap();
+            let zero = div_round(n, d, Rounding::TowardZero).unwrap();
+
+            // down <= n / d <= up, and they differ by at most one
+            if d > 0 {
+                assert!(down * d <= n && n <= up * d);
+            } else {
+                assert!(down * d >= n && n >= up * d);
+            }
+            assert!(up - down <= 1);
+            assert_eq!(up == down, n % d == 0);
+            assert!(zero == down || zero == up);
+            assert!(zero.abs() * d.abs() <= n.abs());
+        }
+    }
+
+    #[test]
+    fn prop_mul_then_div_round_trips_within_one_unit() {
+        let mut rng = XorShift(0xfeed);
+        for _ in 0..10_000 {
+            let price = Fixed::new(rng.range(1, 1_000_000_000_000), 6);
+            let size = Fixed::new(rng.range(1, 1_000_000_000), 6);
+            let notional = price.checked_mul(size, 6, Rounding::Down).unwrap();
+            let back = notional.checked_div(size, 6, Rounding::Down).unwrap();
+            // Rounding the notional down can only lose up to one unit per unit of size
+            let max_error =
+                div_round(1_000_000, size.to_i64().unwrap() as i128, Rounding::Up).unwrap() as i64;
+            let (back, price) = (back.to_i64().unwrap(), price.to_i64().unwrap());
+            assert!(back <= price);
+            assert!(price - back <= max_error);
+        }
+    }
+
+    #[test]
+    fn prop_rescale_up_then_down_is_identity() {
+        let mut rng = XorShift(0xbeef);
+        for _ in 0..10_000 {
+            let value = Fixed::new(rng.range(-1_000_000_000_000_000, 1_000_000_000_000_000), 6);
+            let up = value.rescale(12, Rounding::Down).unwrap();
+            for rounding in [Rounding::Down, Rounding::Up, Rounding::TowardZero] {
+                assert_eq!(up.rescale(6, rounding).unwrap(), value);
+            }
+        }
+    }
+}
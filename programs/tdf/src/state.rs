@@ -1,30 +1,125 @@
 use anchor_lang::prelude::*;
 
+const MAX_ROLE_GRANTS: usize = 32;
+const MAX_BASKET_FEEDS: usize = 10;
+
 #[account]
+#[derive(InitSpace)]
 pub struct GlobalState {
     pub admin: Pubkey,
     pub fee_bps: u16,
     pub treasury: Pubkey,
     pub permission_program: Pubkey, // MagicBlock Permission Program
+    pub listing_bond_mint: Pubkey,  // token league creators post to list a market
+    pub listing_bond_amount: u64,   // 0 = creator listing disabled
+    #[max_len(MAX_ROLE_GRANTS)]
+    pub roles: Vec<RoleGrant>, // keys holding a role besides the admin, who holds them all
+    pub pending_admin: Option<Pubkey>, // proposed admin, takes over once it accepts
+    pub paused: bool,               // blocks joining, league creation and new exposure
+    pub withdrawals_paused: bool,   // also blocks decreases, margin withdrawals and claims
     pub bump: u8,
+    pub version: u8, // layout version, see Market::version
+}
+
+impl GlobalState {
+    pub const VERSION: u8 = 1;
+    pub const MAX_ROLE_GRANTS: usize = MAX_ROLE_GRANTS;
+    pub const SPACE: usize = 8 + Self::INIT_SPACE;
+
+    pub fn has_role(&self, key: &Pubkey, role: Role) -> bool {
+        *key == self.admin
+            || self
+                .roles
+                .iter()
+                .any(|grant| grant.key == *key && grant.role == role)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum Role {
+    MarketManager,   // lists and configures markets
+    FeeManager,      // sets borrow rates, protocol fees and the listing bond
+    Pauser,          // pauses the protocol and lifts market halts
+    LeagueModerator, // pauses individual leagues
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub struct RoleGrant {
+    pub key: Pubkey,
+    pub role: Role,
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct Market {
     pub symbol: [u8; 16],      // "SOL/USDC"
     pub oracle_feed: Pubkey,   // feed address
     pub base_currency: Pubkey, // e.g., USDC
-    pub decimals: u8,
+    pub decimals: u8,          // size decimals
     pub listed_by: Pubkey, // admin
     pub is_active: bool,
     pub created_at: i64,  // timestamp
     pub max_leverage: u8, // e.g. 20x
+    pub borrow_rate_bps: u16, // hourly fee on the borrowed part of notional, in bps
+    pub denominator_feed: Option<Pubkey>, // pair market, priced as oracle_feed / denominator_feed
+    pub max_price_age: i64,               // seconds a feed may lag before it's stale, 0 = unchecked
+    pub corporate_action_count: u32,      // corporate actions recorded so far, indexes CorporateAction accounts
+    pub fx_feed: Option<Pubkey>,          // league currency per unit of the quote currency, None if quoted in it, fixed at listing
+    pub halt_threshold_bps: u16,          // move within halt_window that halts new exposure, 0 = never halts
+    pub halt_window: i64,                 // seconds a reference price stays comparable
+    pub halt_cooldown: i64,               // seconds a halt lasts before trading resumes
+    pub reference_price: i64,             // start of the current window, 0 = not seeded
+    pub reference_price_ts: i64,
+    pub last_observed_price: i64,
+    pub last_observed_ts: i64,
+    pub halted_until: i64, // new exposure is blocked before this timestamp
+    pub fallback_feed: Option<Pubkey>, // used when oracle_feed is stale or halted
+    pub max_divergence_bps: u16, // allowed gap between the live feeds, 0 = unchecked
+    pub price_decimals: u8, // decimals oracle_feed quotes prices with
+    pub denominator_price_decimals: u8, // decimals of denominator_feed, pair markets only
+    pub fallback_price_decimals: u8, // decimals of fallback_feed
+    pub fx_price_decimals: u8, // decimals of fx_feed
+    pub restricted: bool, // creator-listed and not yet promoted, tradable only in listed_by's leagues
     pub bump: u8,
+    pub version: u8, // layout version, appended so accounts older than it read 0 once reallocated
+}
+
+impl Market {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + Self::INIT_SPACE;
+
+    pub fn price_config(&self) -> PriceConfig {
+        PriceConfig {
+            denominator_feed: self.denominator_feed,
+            fallback_feed: self.fallback_feed,
+            max_divergence_bps: self.max_divergence_bps,
+            max_price_age: self.max_price_age,
+            price_decimals: self.price_decimals,
+            denominator_price_decimals: self.denominator_price_decimals,
+            fallback_price_decimals: self.fallback_price_decimals,
+            fx_price_decimals: self.fx_price_decimals,
+        }
+    }
+}
+
+/// Everything besides the oracle feed that goes into reading a market's price
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub struct PriceConfig {
+    pub denominator_feed: Option<Pubkey>,
+    pub fallback_feed: Option<Pubkey>,
+    pub max_divergence_bps: u16,
+    pub max_price_age: i64,
+    pub price_decimals: u8,
+    pub denominator_price_decimals: u8,
+    pub fallback_price_decimals: u8,
+    pub fx_price_decimals: u8,
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct League {
     pub creator: Pubkey,
+    #[max_len(10)]
     pub markets: Vec<Pubkey>,
     pub start_ts: i64, // timestamp
     pub end_ts: i64,   // timestamp
@@ -35,16 +130,30 @@ pub struct League {
     pub reward_vault: Pubkey,     // SPL token vault for rewards
     pub total_reward_amount: u64, // Total reward amount fixed at close_league
 
+    #[max_len(200)]
     pub metadata_uri: String, // URI to the league metadata
     pub status: LeagueStatus,
     pub max_participants: u32,
-    pub virtual_on_deposit: i64, // Paper dollar (e.g., 10_000 * 1e6)
-    pub max_leverage: u8,        // e.g. 20x
+    pub virtual_on_deposit: i64,      // Paper dollar (e.g., 10_000 * 1e6)
+    pub max_leverage: u8,             // e.g. 20x
+    pub margin_mode: MarginMode,      // default margin mode for participants
+    pub margin_mode_selectable: bool, // participants may pick their own margin mode
+    pub position_mode: PositionMode,
+    pub max_open_positions: u8, // open positions per participant, sizes participant accounts
+    pub league_type: LeagueType,
+    pub paused: bool, // same switches as GlobalState, for this league only
+    pub withdrawals_paused: bool,
 
     pub bump: u8,
+    pub version: u8, // layout version, see Market::version
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+impl League {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + Self::INIT_SPACE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
 pub enum LeagueStatus {
     Pending,
     Active,
@@ -52,64 +161,107 @@ pub enum LeagueStatus {
     Finalized,
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum LeagueType {
+    Perp, // leveraged long and short positions, liquidated on margin
+    Spot, // unleveraged long-only holdings, never liquidated
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum PositionMode {
+    Hedge,  // every order opens an independent position
+    OneWay, // one netted position per market, opposite orders reduce and flip it
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum MarginMode {
+    Cross,    // all positions share the participant's equity, liquidated together
+    Isolated, // each position is liquidated on its own against its own margin
+}
+
 #[account]
+#[derive(InitSpace)]
 pub struct Participant {
     pub league: Pubkey,
     pub user: Pubkey,
     pub claimed: bool, // if the user has claimed the reward
+    pub margin_mode: MarginMode,
 
     // Realtime stats
     pub virtual_balance: i64, // Paper dollar (e.g., 10_000 * 1e6), only update when position is updated
     pub unrealized_pnl: i64,  // accumulated unrealized PnL, update with position checking cycle
     pub used_margin: i64, // used margin for current position, update with position is opened or updated
+    pub binary_stake: i64, // virtual balance staked on unsettled binary contracts
 
     pub total_volume: i64, // accumulated volume, only update when position is opened or updated
+
+    // History, folded in when position accounts are closed
+    pub closed_positions: u32,  // number of position accounts closed
+    pub winning_positions: u32, // closed positions with positive realized PnL
+    pub realized_pnl: i64,      // accumulated realized PnL of closed positions
     pub topk_equity_index: u16, // TopK equity index if not in, 0xFFFF
     pub topk_volume_index: u16, // TopK volume index if not in, 0xFFFF
 
     // Position tracking sequence number
     pub current_position_seq: u64, // sequence number of current position
-    pub positions: Vec<Pubkey>,    // position accounts, max length is 10
+    #[max_len(0)]
+    pub positions: Vec<Pubkey>, // position accounts, max length is league.max_open_positions
 
     pub bump: u8,
+    pub version: u8, // layout version, see Market::version
 }
 
 impl Participant {
-    // equity = virtual_balance + unrealized_pnl
-    // available balance = equity - used_margin
+    pub const VERSION: u8 = 1;
+
+    /// Sized for the league's `max_open_positions`
+    pub fn space(max_open_positions: u8) -> usize {
+        8 + Self::INIT_SPACE + 32 * max_open_positions as usize
+    }
+
+    // equity = virtual_balance + unrealized_pnl + binary_stake
+    // available balance = equity - used_margin - binary_stake
     pub fn equity(&self) -> i64 {
-        self.virtual_balance + self.unrealized_pnl
+        self.virtual_balance + self.unrealized_pnl + self.binary_stake
     }
 
     pub fn available_balance(&self) -> i64 {
-        self.equity() - self.used_margin
+        self.equity() - self.used_margin - self.binary_stake
     }
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
 pub enum Direction {
     Long = 1,
     Short = -1,
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct Position {
     pub league: Pubkey,
     pub user: Pubkey,
     pub market: Pubkey,
     pub market_decimals: u8,
     pub oracle_feed: Pubkey,
-    pub seq_num: u64, // sequence number for position tracking
+    pub corporate_actions_applied: u32, // market corporate actions reflected in this position
+    pub seq_num: u64,                   // sequence number for position tracking
 
     pub direction: Direction,
     pub entry_price: i64, // average price in price-decimal (1e6)
     pub entry_size: i64,  // token amount of entry size
-    pub leverage: u8,     // e.g. 5x
+    pub leverage: u8,     // e.g. 5x, effective leverage = notional / margin
+    pub margin: i64,      // isolated margin locked by this position
 
     // Realtime stats
-    pub size: i64,           // token amount of current position
-    pub notional: i64,       // cache: entry_price * size (1e6) - input capital in $
-    pub unrealized_pnl: i64, // (last_updated_price - entry_price) * size * direction
+    pub size: i64,                    // token amount of current position
+    pub notional: i64,                // cache: entry_price * size (1e6) - input capital in $
+    pub unrealized_pnl: i64,          // (last_updated_price - entry_price) * size * direction
+    pub liquidation_price: i64, // isolated estimate, where the loss eats the position's margin, 0 if none
+    pub cross_liquidation_price: i64, // cross estimate, where participant equity hits 0 given other positions at last marks, 0 if none
+    pub borrow_rate_bps: u16, // market borrow rate at open, fixed for the life of the position
+    pub last_fee_accrual_ts: i64, // borrow fee is charged up to this timestamp
+    pub borrow_fees_paid: i64, // total borrow fee charged to the participant
     // notional + unrealized_pnl = current value of position in $
     pub opened_at: i64,
     pub closed_at: i64,
@@ -120,18 +272,260 @@ pub struct Position {
     pub closed_pnl: i64,    // (closed_notional - notional) * direction
 
     pub bump: u8,
+    pub version: u8, // layout version, see Market::version
+}
+
+impl Position {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + Self::INIT_SPACE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum CorporateActionKind {
+    Split { numerator: u32, denominator: u32 }, // numerator new units for every denominator old units
+    Dividend { amount_per_unit: i64 },          // cash per unit held, price-decimal (1e6)
+}
+
+/// A split or dividend recorded on a market, applied to each position opened before it in order
+#[account]
+#[derive(InitSpace)]
+pub struct CorporateAction {
+    pub market: Pubkey,
+    pub index: u32, // position in the market's corporate action sequence
+    pub kind: CorporateActionKind,
+    pub recorded_at: i64,
+    pub bump: u8,
+    pub version: u8, // layout version, see Market::version
 }
 
+impl CorporateAction {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + Self::INIT_SPACE;
+}
+
+/// Bond a league creator posts to list a market, held until the admin promotes or delists it
 #[account]
+#[derive(InitSpace)]
+pub struct ListingBond {
+    pub market: Pubkey,
+    pub creator: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64, // 0 once returned or slashed
+    pub bump: u8,
+    pub version: u8, // layout version, see Market::version
+}
+
+impl ListingBond {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + Self::INIT_SPACE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum DcaAction {
+    Increase, // add a slice to the position
+    Decrease, // close a slice of the position
+}
+
+/// Direction and leverage of the position a schedule's first slice opens
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub struct DcaOpen {
+    pub direction: Direction,
+    pub leverage: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct DcaSchedule {
+    pub league: Pubkey,
+    pub user: Pubkey,
+    pub participant: Pubkey,
+    pub market: Pubkey,
+    pub nonce: u32, // tells apart schedules of a participant on the same market
+    pub position: Option<Pubkey>, // position the slices are applied to, None until the first slice opens it
+    pub open: Option<DcaOpen>, // position the first slice opens, None for schedules on an existing position
+    pub position_rent: u64,    // lamports held for the account the first slice opens
+
+    pub action: DcaAction,
+    pub slice_size: i64,               // token amount per slice
+    pub total_slices: u32,             // N slices
+    pub interval: i64,                 // seconds between slices
+    pub expires_at: i64,               // good-till-time, 0 if the schedule never expires
+    pub acceptable_price: Option<i64>, // slippage bound applied to every slice
+
+    // Progress
+    pub executed_slices: u32,
+    pub executed_size: i64,
+    pub next_execution_ts: i64,
+    pub last_executed_at: i64,
+
+    pub created_at: i64,
+    pub bump: u8,
+    pub version: u8, // layout version, see Market::version
+}
+
+impl DcaSchedule {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + Self::INIT_SPACE;
+}
+
+/// Constituents of a basket market, whose price is the weighted sum of their prices.
+/// The basket account stands in for the oracle feed of its market.
+#[account]
+#[derive(InitSpace)]
+pub struct Basket {
+    pub symbol: [u8; 16],
+    #[max_len(MAX_BASKET_FEEDS)]
+    pub feeds: Vec<Pubkey>, // constituent oracle feeds
+    #[max_len(MAX_BASKET_FEEDS)]
+    pub weights: Vec<i64>, // units of each constituent per index unit (1e6)
+    #[max_len(MAX_BASKET_FEEDS)]
+    pub price_decimals: Vec<u8>, // decimals each constituent feed quotes prices with
+    pub rebalance_count: u32,
+    pub last_rebalanced_at: i64,
+    pub bump: u8,
+    pub version: u8, // layout version, see Market::version
+}
+
+impl Basket {
+    pub const VERSION: u8 = 1;
+    pub const MAX_FEEDS: usize = MAX_BASKET_FEEDS;
+    pub const SPACE: usize = 8 + Self::INIT_SPACE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum BinarySide {
+    Up,   // settles above strike
+    Down, // settles below strike
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum BinaryOutcome {
+    Pending,
+    Up,
+    Down,
+    Void, // settled at strike or nobody on the winning side, stakes are refunded
+}
+
+/// Fixed-expiry binary contract on a market, settled parimutuel: the winning side splits the whole pool
+#[account]
+#[derive(InitSpace)]
+pub struct BinaryInstrument {
+    pub league: Pubkey,
+    pub market: Pubkey,
+    pub oracle_feed: Pubkey,
+    pub price_config: PriceConfig,
+
+    pub strike_price: i64, // price-decimal (1e6)
+    pub expiry_ts: i64,
+
+    pub total_up_stake: i64,
+    pub total_down_stake: i64,
+
+    pub outcome: BinaryOutcome,
+    pub settlement_price: i64,
+    pub settled_at: i64,
+
+    pub created_at: i64,
+    pub bump: u8,
+    pub version: u8, // layout version, see Market::version
+}
+
+impl BinaryInstrument {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + Self::INIT_SPACE;
+}
+
+/// A participant's stakes on one binary instrument
+#[account]
+#[derive(InitSpace)]
+pub struct BinaryStake {
+    pub instrument: Pubkey,
+    pub user: Pubkey,
+    pub up_stake: i64,
+    pub down_stake: i64,
+    pub payout: i64,
+    pub settled: bool,
+    pub bump: u8,
+    pub version: u8, // layout version, see Market::version
+}
+
+impl BinaryStake {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + Self::INIT_SPACE;
+}
+
+#[account]
+#[derive(InitSpace)]
 pub struct Leaderboard {
     pub league: Pubkey,
-    pub k: u16, // top k participants, max is 50 for now
+    pub k: u16, // top k participants, max is 100 for now
+    #[max_len(0)]
     pub topk_equity: Vec<Pubkey>, // participant pubkeys
+    #[max_len(0)]
     pub topk_equity_scores: Vec<i64>, // scores of top k participants
 
+    #[max_len(0)]
     pub topk_volume: Vec<Pubkey>, // participant pubkeys
+    #[max_len(0)]
     pub topk_volume_scores: Vec<i64>, // scores of top k participants
 
     pub last_updated: i64,
     pub bump: u8,
+    pub version: u8, // layout version, see Market::version
+}
+
+impl Leaderboard {
+    pub const VERSION: u8 = 1;
+
+    /// Sized for `k` entries in each of the equity and volume lists
+    pub fn space(k: u16) -> usize {
+        8 + Self::INIT_SPACE + (32 + 8) * 2 * k as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn participant_space_fits_max_open_positions() {
+        for max_open_positions in [1u8, 10, 25] {
+            let space = Participant::space(max_open_positions);
+            let mut participant =
+                Participant::try_deserialize_unchecked(&mut &vec![0u8; space][..]).unwrap();
+            participant.positions = vec![Pubkey::new_unique(); max_open_positions as usize];
+
+            let mut data = Vec::new();
+            participant.try_serialize(&mut data).unwrap();
+            assert_eq!(data.len(), space);
+        }
+    }
+
+    #[test]
+    fn roles_are_held_by_their_grantee_and_the_admin() {
+        let mut global_state =
+            GlobalState::try_deserialize_unchecked(&mut &vec![0u8; GlobalState::SPACE][..])
+                .unwrap();
+        let admin = Pubkey::new_unique();
+        let fee_manager = Pubkey::new_unique();
+        global_state.admin = admin;
+        global_state.roles.push(RoleGrant {
+            key: fee_manager,
+            role: Role::FeeManager,
+        });
+
+        for role in [
+            Role::MarketManager,
+            Role::FeeManager,
+            Role::Pauser,
+            Role::LeagueModerator,
+        ] {
+            assert!(global_state.has_role(&admin, role));
+            assert_eq!(
+                global_state.has_role(&fee_manager, role),
+                role == Role::FeeManager
+            );
+            assert!(!global_state.has_role(&Pubkey::new_unique(), role));
+        }
+    }
 }
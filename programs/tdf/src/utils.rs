@@ -1,7 +1,8 @@
 use anchor_lang::prelude::*;
 
 use crate::errors::ErrorCode;
-use crate::state::Direction;
+use crate::math::{div_round, Fixed, Rounding};
+use crate::state::{Basket, Direction, PriceConfig};
 
 // Define the Oracle PriceFeed struct locally to avoid global allocator conflicts
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
@@ -12,76 +13,808 @@ pub struct PriceFeed {
     pub bump: u8,
 }
 
-pub fn dir_sign(direction: Direction) -> i64 {
-    match direction {
-        Direction::Long => 1,
-        Direction::Short => -1,
+/// Reject the trade if the oracle moved past the trader's acceptable price.
+/// Buying (long increase, short reduce) treats it as a max price, selling as a min price.
+pub fn check_acceptable_price(
+    direction: Direction,
+    is_increase: bool,
+    price: i64,
+    acceptable_price: Option<i64>,
+) -> Result<()> {
+    let Some(acceptable_price) = acceptable_price else {
+        return Ok(());
+    };
+    let is_buy = (direction == Direction::Long) == is_increase;
+    if is_buy {
+        require!(price <= acceptable_price, ErrorCode::SlippageExceeded);
+    } else {
+        require!(price >= acceptable_price, ErrorCode::SlippageExceeded);
     }
+    Ok(())
 }
 
-pub fn calculate_notional(price: i64, size: i64, decimals: u8) -> i64 {
-    let scale = 10i128.pow(decimals as u32);
-    let price128 = price as i128;
-    let size128 = size as i128;
+/// Precision of prices, notionals and balances (1e6)
+pub const PRICE_DECIMALS: u8 = 6;
+
+/// Most decimals a feed may quote prices with
+pub const MAX_PRICE_DECIMALS: u8 = 18;
+
+/// Rescale a feed price quoted with `price_decimals` decimals to `PRICE_DECIMALS`
+pub fn normalize_price(price: i64, price_decimals: u8) -> Result<i64> {
+    let price =
+        Fixed::new(price, price_decimals).to_i64_at(PRICE_DECIMALS, Rounding::TowardZero)?;
+    // A price below the accounting precision can't be traded
+    require!(price > 0, ErrorCode::InvalidPrice);
+    Ok(price)
+}
 
-    // (price * size) / 10^decimals
-    let notional = (price128 * size128) / scale;
+/// Effective leverage of a position, rounded up so it never understates risk.
+pub fn calculate_effective_leverage(notional: i64, margin: i64) -> Result<u8> {
+    require!(margin > 0, ErrorCode::InvalidMarginAmount);
+    let leverage = div_round(notional as i128, margin as i128, Rounding::Up)?;
+    u8::try_from(leverage.max(1)).map_err(|_| ErrorCode::InvalidLeverage.into())
+}
 
-    i64::try_from(notional).expect("notional overflow")
+/// notional = price * size, truncated
+pub fn calculate_notional(price: i64, size: i64, decimals: u8) -> Result<i64> {
+    Fixed::new(price, PRICE_DECIMALS)
+        .checked_mul(
+            Fixed::new(size, decimals),
+            PRICE_DECIMALS,
+            Rounding::TowardZero,
+        )?
+        .to_i64()
 }
 
+/// PnL = (price * size - notional) * direction, rounded toward zero.
+/// Also gives the realized PnL when passed the closed part of the notional.
 pub fn calculate_unrealized_pnl(
     notional: i64,
     current_price: i64,
     size: i64,
     decimals: u8,
     direction: Direction,
-) -> i64 {
-    let current_price128 = current_price as i128;
-    let size128 = size as i128;
-    let scale = 10i128.pow(decimals as u32);
-    let notional128 = notional as i128;
-    let dir128 = dir_sign(direction.clone()) as i128;
-    let pnl128 = (current_price128 * size128 / scale - notional128) * dir128;
-    i64::try_from(pnl128).expect("pnl overflow")
+) -> Result<i64> {
+    let value =
+        Fixed::new(current_price, PRICE_DECIMALS).checked_mul_exact(Fixed::new(size, decimals))?;
+    let pnl = value.checked_sub(Fixed::new(notional, PRICE_DECIMALS))?;
+    let pnl = match direction {
+        Direction::Long => pnl,
+        Direction::Short => pnl.checked_neg()?,
+    };
+    pnl.to_i64_at(PRICE_DECIMALS, Rounding::TowardZero)
 }
 
-pub fn calculate_price_from_notional_and_size(notional: i64, size: i64, decimals: u8) -> i64 {
-    let scale = 10i128.pow(decimals as u32);
-    let notional128 = notional as i128;
-    let size128 = size as i128;
-    let price128 = notional128 / size128 * scale;
-    i64::try_from(price128).expect("price overflow")
+/// price = notional / size, scaling the notional up before dividing
+pub fn calculate_price_from_notional_and_size(
+    notional: i64,
+    size: i64,
+    decimals: u8,
+) -> Result<i64> {
+    Fixed::new(notional, PRICE_DECIMALS)
+        .checked_div(
+            Fixed::new(size, decimals),
+            PRICE_DECIMALS,
+            Rounding::TowardZero,
+        )?
+        .to_i64()
 }
 
-pub fn get_price_from_oracle(oracle_feed: &AccountInfo) -> Result<i64> {
-    let data = oracle_feed.try_borrow_data()?;
+/// Size-weighted average of the current entry price and a new fill
+pub fn calculate_average_entry_price(
+    entry_price: i64,
+    entry_size: i64,
+    fill_price: i64,
+    fill_size: i64,
+    decimals: u8,
+) -> Result<i64> {
+    let entry_cost = Fixed::new(entry_price, PRICE_DECIMALS)
+        .checked_mul_exact(Fixed::new(entry_size, decimals))?;
+    let fill_cost = Fixed::new(fill_price, PRICE_DECIMALS)
+        .checked_mul_exact(Fixed::new(fill_size, decimals))?;
+    let total_size = entry_size
+        .checked_add(fill_size)
+        .ok_or(ErrorCode::MathOverflow)?;
+    entry_cost
+        .checked_add(fill_cost)?
+        .checked_div(
+            Fixed::new(total_size, decimals),
+            PRICE_DECIMALS,
+            Rounding::TowardZero,
+        )?
+        .to_i64()
+}
 
-    // Try to deserialize the PriceFeed account using Oracle's struct
-    match PriceFeed::try_from_slice(&data) {
-        Ok(price_feed) => {
-            if price_feed.price <= 0 {
-                return Err(ErrorCode::InvalidPrice.into());
+/// Price at which the position has lost `buffer`: long (notional - buffer) / size,
+/// short (notional + buffer) / size. Rounded against the trader, 0 if no price reaches it.
+pub fn calculate_liquidation_price(
+    notional: i64,
+    size: i64,
+    decimals: u8,
+    direction: Direction,
+    buffer: i64,
+) -> Result<i64> {
+    if size == 0 {
+        return Ok(0);
+    }
+    let notional = Fixed::new(notional, PRICE_DECIMALS);
+    let buffer = Fixed::new(buffer, PRICE_DECIMALS);
+    let (threshold, rounding) = match direction {
+        Direction::Long => (notional.checked_sub(buffer)?, Rounding::Up),
+        Direction::Short => (notional.checked_add(buffer)?, Rounding::Down),
+    };
+    let price = threshold
+        .checked_div(Fixed::new(size, decimals), PRICE_DECIMALS, rounding)?
+        .to_i64()?;
+    Ok(price.max(0))
+}
+
+/// Margin locked for `notional` at `leverage`, rounded up against the trader
+pub fn calculate_required_margin(notional: i64, leverage: u8) -> Result<i64> {
+    require!(leverage > 0, ErrorCode::InvalidLeverage);
+    Fixed::new(notional, PRICE_DECIMALS)
+        .checked_div(
+            Fixed::from_int(leverage as i64),
+            PRICE_DECIMALS,
+            Rounding::Up,
+        )?
+        .to_i64()
+}
+
+/// Share of `amount` attributable to `part` of `whole`, the whole amount when `part == whole`
+pub fn calculate_pro_rata(amount: i64, part: i64, whole: i64, rounding: Rounding) -> Result<i64> {
+    if part == whole {
+        return Ok(amount);
+    }
+    Fixed::new(amount, PRICE_DECIMALS)
+        .checked_mul_exact(Fixed::from_int(part))?
+        .checked_div(Fixed::from_int(whole), PRICE_DECIMALS, rounding)?
+        .to_i64()
+}
+
+const BPS_DENOMINATOR: i64 = 10_000;
+const SECONDS_PER_HOUR: i64 = 3_600;
+
+/// Borrow fee on `notional - margin` at `rate_bps` per hour over `elapsed` seconds, rounded up
+pub fn calculate_borrow_fee(
+    notional: i64,
+    margin: i64,
+    rate_bps: u16,
+    elapsed: i64,
+) -> Result<i64> {
+    let borrowed = notional
+        .checked_sub(margin)
+        .ok_or(ErrorCode::MathOverflow)?
+        .max(0);
+    if borrowed == 0 || rate_bps == 0 || elapsed <= 0 {
+        return Ok(0);
+    }
+    let rate_seconds = (rate_bps as i64)
+        .checked_mul(elapsed)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Fixed::new(borrowed, PRICE_DECIMALS)
+        .checked_mul_exact(Fixed::from_int(rate_seconds))?
+        .checked_div(
+            Fixed::from_int(BPS_DENOMINATOR * SECONDS_PER_HOUR),
+            PRICE_DECIMALS,
+            Rounding::Up,
+        )?
+        .to_i64()
+}
+
+/// Absolute move from `reference_price` to `price` in bps, rounded down
+pub fn calculate_price_move_bps(reference_price: i64, price: i64) -> Result<i64> {
+    let diff = price
+        .checked_sub(reference_price)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_abs()
+        .ok_or(ErrorCode::MathOverflow)?;
+    calculate_pro_rata(diff, BPS_DENOMINATOR, reference_price, Rounding::Down)
+}
+
+/// Price of a market at 1e6: the feed price, numerator / denominator for a pair market,
+/// or the weighted sum of `constituent_feeds` when `oracle_feed` is a basket account.
+/// With `max_price_age` set, every leg must have been updated within that many seconds.
+/// Every feed's price is read at its own decimals from `config` and normalized to 1e6.
+pub fn get_market_price(
+    oracle_feed: &AccountInfo,
+    denominator_feed: Option<&AccountInfo>,
+    fallback_feed: Option<&AccountInfo>,
+    constituent_feeds: &[AccountInfo],
+    config: &PriceConfig,
+    now: i64,
+) -> Result<i64> {
+    if is_basket(oracle_feed) {
+        require!(
+            config.denominator_feed.is_none()
+                && denominator_feed.is_none()
+                && config.fallback_feed.is_none()
+                && fallback_feed.is_none(),
+            ErrorCode::OracleMismatch
+        );
+        let data = oracle_feed.try_borrow_data()?;
+        let basket = Basket::try_deserialize(&mut &data[..])?;
+        return get_basket_price(&basket, constituent_feeds, config, now);
+    }
+    require!(constituent_feeds.is_empty(), ErrorCode::OracleMismatch);
+
+    let numerator = read_primary_price(oracle_feed, fallback_feed, config, now)?;
+
+    let denominator = match (config.denominator_feed, denominator_feed) {
+        (None, None) => return Ok(numerator),
+        (Some(expected), Some(denominator_feed)) => {
+            require_keys_eq!(denominator_feed.key(), expected, ErrorCode::OracleMismatch);
+            read_price_feed(denominator_feed, config.denominator_price_decimals)?
+        }
+        _ => return Err(ErrorCode::OracleMismatch.into()),
+    };
+    require_fresh_price(&denominator, config.max_price_age, now)?;
+
+    let price = Fixed::new(numerator, PRICE_DECIMALS)
+        .checked_div(
+            Fixed::new(denominator.price, PRICE_DECIMALS),
+            PRICE_DECIMALS,
+            Rounding::TowardZero,
+        )?
+        .to_i64()?;
+    // A ratio too small for the price precision can't be traded
+    require!(price > 0, ErrorCode::InvalidPrice);
+    Ok(price)
+}
+
+/// Price of the market's primary feed. A stale or halted primary falls back to the
+/// secondary feed, and while both are live they may differ by at most `max_divergence_bps`.
+fn read_primary_price(
+    oracle_feed: &AccountInfo,
+    fallback_feed: Option<&AccountInfo>,
+    config: &PriceConfig,
+    now: i64,
+) -> Result<i64> {
+    let primary = read_price_feed(oracle_feed, config.price_decimals)
+        .and_then(|feed| require_fresh_price(&feed, config.max_price_age, now).map(|_| feed));
+    let fallback = match (config.fallback_feed, fallback_feed) {
+        (None, None) => return Ok(primary?.price),
+        (Some(expected), Some(fallback_feed)) => {
+            require_keys_eq!(fallback_feed.key(), expected, ErrorCode::OracleMismatch);
+            read_price_feed(fallback_feed, config.fallback_price_decimals).and_then(|feed| {
+                require_fresh_price(&feed, config.max_price_age, now).map(|_| feed)
+            })
+        }
+        _ => return Err(ErrorCode::OracleMismatch.into()),
+    };
+
+    match (primary, fallback) {
+        (Ok(primary), Ok(fallback)) => {
+            if config.max_divergence_bps > 0 {
+                let divergence_bps = calculate_price_move_bps(primary.price, fallback.price)?;
+                require!(
+                    divergence_bps <= config.max_divergence_bps as i64,
+                    ErrorCode::PriceDivergence
+                );
             }
-            Ok(price_feed.price)
+            Ok(primary.price)
+        }
+        (Err(_), Ok(fallback)) => {
+            msg!(
+                "Primary feed unavailable, using fallback price {}",
+                fallback.price
+            );
+            Ok(fallback.price)
+        }
+        (Ok(primary), Err(_)) => Ok(primary.price),
+        (Err(err), Err(_)) => Err(err),
+    }
+}
+
+/// Convert a price quoted in another currency into the league's virtual dollar
+/// through the market's FX feed, a no-op for markets without one
+pub fn convert_with_fx(
+    price: i64,
+    fx_feed: Option<&AccountInfo>,
+    expected_fx: Option<Pubkey>,
+    config: &PriceConfig,
+    now: i64,
+) -> Result<i64> {
+    let fx = match (expected_fx, fx_feed) {
+        (None, None) => return Ok(price),
+        (Some(expected), Some(fx_feed)) => {
+            require_keys_eq!(fx_feed.key(), expected, ErrorCode::OracleMismatch);
+            read_price_feed(fx_feed, config.fx_price_decimals)?
         }
+        _ => return Err(ErrorCode::OracleMismatch.into()),
+    };
+    require_fresh_price(&fx, config.max_price_age, now)?;
+
+    let price = Fixed::new(price, PRICE_DECIMALS)
+        .checked_mul(
+            Fixed::new(fx.price, PRICE_DECIMALS),
+            PRICE_DECIMALS,
+            Rounding::TowardZero,
+        )?
+        .to_i64()?;
+    require!(price > 0, ErrorCode::InvalidPrice);
+    Ok(price)
+}
+
+/// Basket price = sum(weight * price) over its constituents, passed in basket order
+pub fn get_basket_price(
+    basket: &Basket,
+    constituent_feeds: &[AccountInfo],
+    config: &PriceConfig,
+    now: i64,
+) -> Result<i64> {
+    let prices = read_basket_prices(
+        &basket.feeds,
+        &basket.price_decimals,
+        constituent_feeds,
+        config.max_price_age,
+        now,
+    )?;
+    let price = calculate_basket_value(&prices, &basket.weights)?;
+    require!(price > 0, ErrorCode::InvalidPrice);
+    Ok(price)
+}
+
+/// Fresh prices of `feeds` quoted with `price_decimals`, read from `constituent_feeds`
+/// passed in the same order
+pub fn read_basket_prices(
+    feeds: &[Pubkey],
+    price_decimals: &[u8],
+    constituent_feeds: &[AccountInfo],
+    max_price_age: i64,
+    now: i64,
+) -> Result<Vec<i64>> {
+    require!(
+        constituent_feeds.len() == feeds.len() && price_decimals.len() == feeds.len(),
+        ErrorCode::OracleMismatch
+    );
+    feeds
+        .iter()
+        .zip(price_decimals)
+        .zip(constituent_feeds)
+        .map(|((feed, decimals), feed_ai)| {
+            require_keys_eq!(feed_ai.key(), *feed, ErrorCode::OracleMismatch);
+            let price_feed = read_price_feed(feed_ai, *decimals)?;
+            require_fresh_price(&price_feed, max_price_age, now)?;
+            Ok(price_feed.price)
+        })
+        .collect()
+}
+
+/// sum(weight * price), rounded toward zero
+pub fn calculate_basket_value(prices: &[i64], weights: &[i64]) -> Result<i64> {
+    let mut value = Fixed::new(0, PRICE_DECIMALS);
+    for (price, weight) in prices.iter().zip(weights) {
+        value = value.checked_add(
+            Fixed::new(*price, PRICE_DECIMALS)
+                .checked_mul_exact(Fixed::new(*weight, PRICE_DECIMALS))?,
+        )?;
+    }
+    value.to_i64_at(PRICE_DECIMALS, Rounding::TowardZero)
+}
+
+/// Scale `weights` so the basket is worth `level` at `prices`, keeping their proportions.
+/// Rounds toward zero, so the rescaled level never exceeds `level`.
+pub fn rescale_basket_weights(weights: &[i64], prices: &[i64], level: i64) -> Result<Vec<i64>> {
+    let value = calculate_basket_value(prices, weights)?;
+    require!(value > 0, ErrorCode::InvalidPrice);
+    weights
+        .iter()
+        .map(|weight| {
+            let weight = calculate_pro_rata(*weight, level, value, Rounding::TowardZero)?;
+            require!(weight > 0, ErrorCode::InvalidBasketWeights);
+            Ok(weight)
+        })
+        .collect()
+}
+
+/// Basket accounts are owned by this program, oracle feeds by the oracle program
+pub fn is_basket(oracle_feed: &AccountInfo) -> bool {
+    oracle_feed.owner == &crate::ID
+}
+
+/// Number of constituent feeds that follow `oracle_feed` in remaining accounts
+pub fn constituent_feed_count(oracle_feed: &AccountInfo) -> Result<usize> {
+    if !is_basket(oracle_feed) {
+        return Ok(0);
+    }
+    let data = oracle_feed.try_borrow_data()?;
+    Ok(Basket::try_deserialize(&mut &data[..])?.feeds.len())
+}
+
+fn require_fresh_price(price_feed: &PriceFeed, max_price_age: i64, now: i64) -> Result<()> {
+    if max_price_age > 0 {
+        require!(
+            price_feed.last_updated <= now
+                && now.saturating_sub(price_feed.last_updated) <= max_price_age,
+            ErrorCode::StalePrice
+        );
+    }
+    Ok(())
+}
+
+/// Reads the feed with its price normalized from `price_decimals` to 1e6
+fn read_price_feed(oracle_feed: &AccountInfo, price_decimals: u8) -> Result<PriceFeed> {
+    let data = oracle_feed.try_borrow_data()?;
+
+    // Try to deserialize the PriceFeed account using Oracle's struct
+    let price_feed = match PriceFeed::try_from_slice(&data) {
+        Ok(price_feed) => price_feed,
         Err(_) => {
-            // Fallback: manual parsing after discriminator
+            // Fallback: parse the fields after the discriminator
             if data.len() < 16 {
                 return Err(ErrorCode::InvalidPrice.into());
             }
+            match PriceFeed::deserialize(&mut &data[8..]) {
+                Ok(price_feed) => price_feed,
+                Err(_) => {
+                    // Lenient fallback: read the price from bytes 8-16, and the update time
+                    // from bytes 16-24 when present, so a feed without one can't pass a
+                    // staleness check
+                    let read_i64 = |range: std::ops::Range<usize>| {
+                        data.get(range)
+                            .and_then(|bytes| bytes.try_into().ok())
+                            .map(i64::from_le_bytes)
+                    };
+                    PriceFeed {
+                        price: read_i64(8..16).ok_or(ErrorCode::InvalidPrice)?,
+                        last_updated: read_i64(16..24).unwrap_or(0),
+                        authority: Pubkey::default(),
+                        bump: 0,
+                    }
+                }
+            }
+        }
+    };
 
-            // Read price from bytes 8-16 (after discriminator)
-            let price_bytes: [u8; 8] = data[8..16]
-                .try_into()
-                .map_err(|_| ErrorCode::InvalidPrice)?;
-            let price = i64::from_le_bytes(price_bytes);
+    if price_feed.price <= 0 {
+        return Err(ErrorCode::InvalidPrice.into());
+    }
+    Ok(PriceFeed {
+        price: normalize_price(price_feed.price, price_decimals)?,
+        ..price_feed
+    })
+}
 
-            if price <= 0 {
-                return Err(ErrorCode::InvalidPrice.into());
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            Ok(price)
+    /// Realized PnL of closing `part` of a position of `size` holding `notional`
+    fn realize(notional: i64, part: i64, size: i64, price: i64, decimals: u8) -> (i64, i64) {
+        let closing_notional =
+            calculate_pro_rata(notional, part, size, Rounding::TowardZero).unwrap();
+        let pnl =
+            calculate_unrealized_pnl(closing_notional, price, part, decimals, Direction::Long)
+                .unwrap();
+        (closing_notional, pnl)
+    }
+
+    fn feed_account(data: Vec<u8>) -> AccountInfo<'static> {
+        AccountInfo::new(
+            Box::leak(Box::new(Pubkey::new_unique())),
+            false,
+            false,
+            Box::leak(Box::new(0)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(Pubkey::default())),
+            false,
+            0,
+        )
+    }
+
+    fn live_feed(price: i64, last_updated: i64) -> AccountInfo<'static> {
+        let mut data = vec![0u8; 8];
+        PriceFeed {
+            price,
+            last_updated,
+            authority: Pubkey::default(),
+            bump: 0,
+        }
+        .serialize(&mut data)
+        .unwrap();
+        feed_account(data)
+    }
+
+    /// A market with a fallback feed, prices at most 60s old and 1% apart
+    fn fallback_config(fallback: &AccountInfo) -> PriceConfig {
+        PriceConfig {
+            denominator_feed: None,
+            fallback_feed: Some(fallback.key()),
+            max_divergence_bps: 100,
+            max_price_age: 60,
+            price_decimals: 6,
+            denominator_price_decimals: 6,
+            fallback_price_decimals: 6,
+            fx_price_decimals: 6,
         }
     }
+
+    #[test]
+    fn each_feed_is_read_at_its_own_decimals() {
+        // BTC at 60,000 with 8 decimals over ETH at 3,000 with 9 decimals
+        let btc = live_feed(6_000_000_000_000, 1_000);
+        let eth = live_feed(3_000_000_000_000, 1_000);
+        let config = PriceConfig {
+            denominator_feed: Some(eth.key()),
+            fallback_feed: None,
+            max_divergence_bps: 0,
+            max_price_age: 60,
+            price_decimals: 8,
+            denominator_price_decimals: 9,
+            fallback_price_decimals: 0,
+            fx_price_decimals: 9,
+        };
+        let price = get_market_price(&btc, Some(&eth), None, &[], &config, 1_000).unwrap();
+        assert_eq!(price, 20_000_000);
+
+        // Quoted in a currency worth 0.5 league dollars, with 9 decimals
+        let fx = live_feed(500_000_000, 1_000);
+        let price = convert_with_fx(price, Some(&fx), Some(fx.key()), &config, 1_000).unwrap();
+        assert_eq!(price, 10_000_000);
+    }
+
+    #[test]
+    fn fallback_is_read_at_its_own_decimals() {
+        // 8 decimal primary gone stale, the 9 decimal fallback quotes 100.5
+        let primary = live_feed(10_000_000_000, 0);
+        let fallback = live_feed(100_500_000_000, 1_000);
+        let config = PriceConfig {
+            price_decimals: 8,
+            fallback_price_decimals: 9,
+            ..fallback_config(&fallback)
+        };
+        let price = get_market_price(&primary, None, Some(&fallback), &[], &config, 1_000).unwrap();
+        assert_eq!(price, 100_500_000);
+
+        // Both live, 100 against 100.5 is within the 1% allowed
+        let primary = live_feed(10_000_000_000, 1_000);
+        let price = get_market_price(&primary, None, Some(&fallback), &[], &config, 1_000).unwrap();
+        assert_eq!(price, 100_000_000);
+    }
+
+    #[test]
+    fn basket_constituents_are_read_at_their_own_decimals() {
+        let a = live_feed(10_000_000_000, 1_000); // 100 at 8 decimals
+        let b = live_feed(50_000_000_000, 1_000); // 50 at 9 decimals
+        let prices = read_basket_prices(
+            &[a.key(), b.key()],
+            &[8, 9],
+            &[a.clone(), b.clone()],
+            60,
+            1_000,
+        )
+        .unwrap();
+        assert_eq!(prices, vec![100_000_000, 50_000_000]);
+
+        let err = read_basket_prices(&[a.key(), b.key()], &[8], &[a, b], 60, 1_000).unwrap_err();
+        assert_eq!(err, ErrorCode::OracleMismatch.into());
+    }
+
+    #[test]
+    fn stale_primary_falls_back_to_the_secondary_feed() {
+        let primary = live_feed(100_000_000, 0);
+        let fallback = live_feed(105_000_000, 1_000);
+        let config = fallback_config(&fallback);
+
+        let price = get_market_price(&primary, None, Some(&fallback), &[], &config, 1_000).unwrap();
+        assert_eq!(price, 105_000_000);
+    }
+
+    #[test]
+    fn live_feeds_may_not_diverge() {
+        let primary = live_feed(100_000_000, 1_000);
+        let near_fallback = live_feed(100_500_000, 1_000);
+        let config = fallback_config(&near_fallback);
+        // 0.5% apart the primary is used
+        let price =
+            get_market_price(&primary, None, Some(&near_fallback), &[], &config, 1_000).unwrap();
+        assert_eq!(price, 100_000_000);
+
+        let fallback = live_feed(102_000_000, 1_000);
+        let config = fallback_config(&fallback);
+        let err =
+            get_market_price(&primary, None, Some(&fallback), &[], &config, 1_000).unwrap_err();
+        assert_eq!(err, ErrorCode::PriceDivergence.into());
+    }
+
+    #[test]
+    fn both_feeds_stale_is_an_error() {
+        let primary = live_feed(100_000_000, 0);
+        let fallback = live_feed(100_000_000, 0);
+        let config = fallback_config(&fallback);
+
+        let err =
+            get_market_price(&primary, None, Some(&fallback), &[], &config, 1_000).unwrap_err();
+        assert_eq!(err, ErrorCode::StalePrice.into());
+    }
+
+    #[test]
+    fn price_feed_falls_back_to_the_price_after_the_discriminator() {
+        // Discriminator and a bare price, as short feeds were read before timestamps
+        let mut data = vec![0u8; 8];
+        data.extend_from_slice(&100_000_000i64.to_le_bytes());
+        let price_feed = read_price_feed(&feed_account(data.clone()), 6).unwrap();
+        assert_eq!(price_feed.price, 100_000_000);
+        assert_eq!(price_feed.last_updated, 0);
+
+        // A timestamp right after the price is picked up
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        let price_feed = read_price_feed(&feed_account(data), 6).unwrap();
+        assert_eq!(price_feed.last_updated, 1_700_000_000);
+
+        // Nothing past the discriminator to read
+        let err = read_price_feed(&feed_account(vec![0u8; 12]), 6).unwrap_err();
+        assert_eq!(err, ErrorCode::InvalidPrice.into());
+    }
+
+    #[test]
+    fn rebalanced_weights_keep_the_index_level() {
+        // 1 A at 100 and 2 B at 50 make a level of 200
+        let level =
+            calculate_basket_value(&[100_000_000, 50_000_000], &[1_000_000, 2_000_000]).unwrap();
+        assert_eq!(level, 200_000_000);
+
+        // Rebalancing to equal units of A and C at 100 and 300 would jump to 400, so the
+        // units are halved
+        let prices = [100_000_000, 300_000_000];
+        let weights = rescale_basket_weights(&[1_000_000, 1_000_000], &prices, level).unwrap();
+        assert_eq!(weights, vec![500_000, 500_000]);
+        assert_eq!(calculate_basket_value(&prices, &weights).unwrap(), level);
+
+        // Weights that don't divide evenly round down to just under the old level
+        let prices = [30_000_000, 70_000_000];
+        let weights = rescale_basket_weights(&[1_000_000, 1_000_000], &prices, level).unwrap();
+        assert_eq!(weights, vec![2_000_000, 2_000_000]);
+        let weights = rescale_basket_weights(&[1_000_000, 2_000_000], &prices, level).unwrap();
+        let rescaled = calculate_basket_value(&prices, &weights).unwrap();
+        assert!(rescaled <= level && level - rescaled < 100);
+    }
+
+    #[test]
+    fn borrow_fee_is_charged_on_the_borrowed_part() {
+        // 1,000 of notional on 200 of margin borrows 800, 10 bps/h of it is 0.8 an hour
+        assert_eq!(
+            calculate_borrow_fee(1_000_000_000, 200_000_000, 10, 3_600).unwrap(),
+            800_000
+        );
+        assert_eq!(
+            calculate_borrow_fee(1_000_000_000, 200_000_000, 10, 1_800).unwrap(),
+            400_000
+        );
+        // Fully margined or without time passing nothing is owed
+        assert_eq!(
+            calculate_borrow_fee(1_000_000_000, 1_000_000_000, 10, 3_600).unwrap(),
+            0
+        );
+        assert_eq!(
+            calculate_borrow_fee(1_000_000_000, 200_000_000, 10, 0).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn borrow_fee_rounds_up() {
+        // 800 * 10 bps over one second is 222.2 units, the partial unit is charged
+        assert_eq!(
+            calculate_borrow_fee(1_000_000_000, 200_000_000, 10, 1).unwrap(),
+            223
+        );
+        assert_eq!(calculate_borrow_fee(2, 1, 1, 1).unwrap(), 1);
+    }
+
+    #[test]
+    fn acceptable_price_bounds_the_side_being_traded() {
+        let check = |direction, is_increase, price| {
+            check_acceptable_price(direction, is_increase, price, Some(100_000_000))
+        };
+        // Opening a long or closing a short buys, the price may not be above the bound
+        check(Direction::Long, true, 100_000_000).unwrap();
+        assert_eq!(
+            check(Direction::Long, true, 100_000_001).unwrap_err(),
+            ErrorCode::SlippageExceeded.into()
+        );
+        assert!(check(Direction::Short, false, 100_000_001).is_err());
+        // Opening a short or closing a long sells, the price may not be below the bound
+        check(Direction::Short, true, 100_000_000).unwrap();
+        assert_eq!(
+            check(Direction::Short, true, 99_999_999).unwrap_err(),
+            ErrorCode::SlippageExceeded.into()
+        );
+        assert!(check(Direction::Long, false, 99_999_999).is_err());
+        // No bound accepts any price
+        check_acceptable_price(Direction::Long, true, i64::MAX, None).unwrap();
+    }
+
+    #[test]
+    fn liquidation_price_is_where_the_buffer_is_lost() {
+        // 10 units bought for 1,000 with 200 of margin
+        assert_eq!(
+            calculate_liquidation_price(1_000_000_000, 10_000_000, 6, Direction::Long, 200_000_000)
+                .unwrap(),
+            80_000_000
+        );
+        assert_eq!(
+            calculate_liquidation_price(
+                1_000_000_000,
+                10_000_000,
+                6,
+                Direction::Short,
+                200_000_000
+            )
+            .unwrap(),
+            120_000_000
+        );
+        // A buffer larger than the notional leaves no price to liquidate a long at
+        assert_eq!(
+            calculate_liquidation_price(
+                1_000_000_000,
+                10_000_000,
+                6,
+                Direction::Long,
+                2_000_000_000
+            )
+            .unwrap(),
+            0
+        );
+        assert_eq!(
+            calculate_liquidation_price(1_000_000_000, 0, 6, Direction::Long, 0).unwrap(),
+            0
+        );
+        // Rounded against the trader: up for longs, down for shorts
+        assert_eq!(
+            calculate_liquidation_price(1_000_000_000, 3, 0, Direction::Long, 0).unwrap(),
+            333_333_334
+        );
+        assert_eq!(
+            calculate_liquidation_price(1_000_000_000, 3, 0, Direction::Short, 0).unwrap(),
+            333_333_333
+        );
+    }
+
+    #[test]
+    fn average_entry_price_across_size_decimals() {
+        // 1.5 at 100 and 0.5 at 200, 9 decimals
+        assert_eq!(
+            calculate_average_entry_price(100_000_000, 1_500_000_000, 200_000_000, 500_000_000, 9)
+                .unwrap(),
+            125_000_000
+        );
+        // 3 at 10.5 and 1 at 12, whole units
+        assert_eq!(
+            calculate_average_entry_price(10_500_000, 3, 12_000_000, 1, 0).unwrap(),
+            10_875_000
+        );
+        // 0.001 at 1 and 0.002 at 2, 3 decimals, rounds toward zero
+        assert_eq!(
+            calculate_average_entry_price(1_000_000, 1, 2_000_000, 2, 3).unwrap(),
+            1_666_666
+        );
+    }
+
+    #[test]
+    fn pro_rata_realized_pnl_across_size_decimals() {
+        // 3 units at 100 with 9 decimals, close 1 at 110
+        let notional = calculate_notional(100_000_000, 3_000_000_000, 9).unwrap();
+        assert_eq!(notional, 300_000_000);
+        assert_eq!(
+            realize(notional, 1_000_000_000, 3_000_000_000, 110_000_000, 9),
+            (100_000_000, 10_000_000)
+        );
+        assert_eq!(
+            calculate_unrealized_pnl(100_000_000, 110_000_000, 1_000_000_000, 9, Direction::Short)
+                .unwrap(),
+            -10_000_000
+        );
+    }
+
+    #[test]
+    fn pro_rata_realized_pnl_sums_to_the_whole_close() {
+        // 3 whole units bought for 100, closed at 40 in two steps
+        let (first_notional, first_pnl) = realize(100_000_000, 1, 3, 40_000_000, 0);
+        assert_eq!((first_notional, first_pnl), (33_333_333, 6_666_667));
+        let (rest_notional, rest_pnl) = realize(100_000_000 - first_notional, 2, 2, 40_000_000, 0);
+        assert_eq!((rest_notional, rest_pnl), (66_666_667, 13_333_333));
+        assert_eq!(first_pnl + rest_pnl, 20_000_000);
+    }
 }
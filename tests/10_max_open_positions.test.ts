@@ -0,0 +1,104 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getProvider,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("Max Open Positions Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+
+  const PRICE = 100_000_000; // $100
+  const SIZE = 1_000_000;
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE);
+  });
+
+  for (const maxOpenPositions of [0, 26]) {
+    it(`Should reject a league allowing ${maxOpenPositions} open positions`, async () => {
+      try {
+        await testHelpers.setupLeague(accounts.user4, 100 + maxOpenPositions, [], {
+          maxOpenPositions,
+        });
+        expect.fail("Should have failed");
+      } catch (error) {
+        expect(error.message).to.include("InvalidMaxOpenPositions");
+      }
+    });
+  }
+
+  it("Should size participant accounts for the league's limit", async () => {
+    const { participantPDAs: [single] } = await testHelpers.setupLeague(
+      accounts.user4,
+      101,
+      [accounts.user4],
+      { maxOpenPositions: 1 }
+    );
+    const { participantPDAs: [portfolio] } = await testHelpers.setupLeague(
+      accounts.user4,
+      125,
+      [accounts.user4],
+      { maxOpenPositions: 25 }
+    );
+
+    const connection = getProvider().connection;
+    const singleSize = (await connection.getAccountInfo(single))!.data.length;
+    const portfolioSize = (await connection.getAccountInfo(portfolio))!.data.length;
+    expect(portfolioSize - singleSize).to.equal(24 * 32);
+  });
+
+  it("Should stop opening at the league's limit", async () => {
+    const leaguePDA: PublicKey = PublicKey.findProgramAddressSync(
+      [Buffer.from("league"), accounts.user4.publicKey.toBuffer(), Buffer.from([101])],
+      getProgram().programId
+    )[0];
+    const participantPDA = globalTestState.createParticipantPDA(
+      leaguePDA,
+      accounts.user4.publicKey
+    );
+    const open = (seqNum: number) =>
+      testHelpers.openPosition(
+        accounts.user4,
+        leaguePDA,
+        pdas.marketPDA,
+        pdas.priceFeedPDA,
+        participantPDA,
+        testHelpers.positionPDA(leaguePDA, accounts.user4.publicKey, seqNum),
+        { long: {} },
+        SIZE,
+        5,
+        seqNum
+      );
+
+    await open(0);
+    try {
+      await open(1);
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("MaxOpenPositionExceeded");
+    }
+
+    // Closing the position frees the slot
+    await testHelpers.decreasePositionSize(
+      accounts.user4,
+      leaguePDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      testHelpers.positionPDA(leaguePDA, accounts.user4.publicKey, 0),
+      SIZE
+    );
+    await open(1);
+  });
+});
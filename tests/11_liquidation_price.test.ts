@@ -0,0 +1,121 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("Liquidation Price Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+  let leaguePDA: PublicKey;
+  let leaderboardPDA: PublicKey;
+  let participantPDA: PublicKey;
+  let positionPDAs: PublicKey[];
+  const nonce = 110;
+
+  const PRICE = 100_000_000; // $100
+  const SIZE = 10_000_000; // 1,000 of notional
+  const LEVERAGE = 5; // 200 of margin
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE);
+    // 1,000 of virtual balance
+    ({
+      leaguePDA,
+      leaderboardPDA,
+      participantPDAs: [participantPDA],
+    } = await testHelpers.setupLeague(accounts.user5, nonce, [accounts.user5], {
+      virtualOnDeposit: 1_000_000_000,
+    }));
+    positionPDAs = [0, 1].map((seqNum) =>
+      testHelpers.positionPDA(leaguePDA, accounts.user5.publicKey, seqNum)
+    );
+  });
+
+  it("Should store the liquidation prices when a position opens", async () => {
+    await testHelpers.openPosition(
+      accounts.user5,
+      leaguePDA,
+      pdas.marketPDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      positionPDAs[0],
+      { long: {} },
+      SIZE,
+      LEVERAGE,
+      0
+    );
+
+    const position = await getProgram().account.position.fetch(positionPDAs[0]);
+    // (1,000 - 200) / 10
+    expect(position.liquidationPrice.toNumber()).to.equal(80_000_000);
+    // The whole 1,000 of equity backs it, no price liquidates it
+    expect(position.crossLiquidationPrice.toNumber()).to.equal(0);
+  });
+
+  it("Should move the cross estimate with the other positions' marks", async () => {
+    await testHelpers.openPosition(
+      accounts.user5,
+      leaguePDA,
+      pdas.marketPDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      positionPDAs[1],
+      { long: {} },
+      SIZE,
+      LEVERAGE,
+      1
+    );
+
+    // At 90 each long is down 100, leaving 900 of equity outside either of them
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, 90_000_000);
+    await testHelpers.refreshParticipant(
+      accounts.user5,
+      leaguePDA,
+      participantPDA,
+      leaderboardPDA,
+      positionPDAs,
+      [pdas.marketPDA, pdas.marketPDA],
+      [pdas.priceFeedPDA, pdas.priceFeedPDA]
+    );
+
+    for (const positionPDA of positionPDAs) {
+      const position = await getProgram().account.position.fetch(positionPDA);
+      expect(position.liquidationPrice.toNumber()).to.equal(80_000_000);
+      // (1,000 - 900) / 10
+      expect(position.crossLiquidationPrice.toNumber()).to.equal(10_000_000);
+    }
+  });
+
+  it("Should return the same estimates from the view", async () => {
+    const prices = await testHelpers.getLiquidationPrice(participantPDA, positionPDAs[0]);
+    expect(prices).to.deep.equal({ isolated: 80_000_000, cross: 10_000_000 });
+  });
+
+  it("Should update the estimates when the position is reduced", async () => {
+    await testHelpers.decreasePositionSize(
+      accounts.user5,
+      leaguePDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      positionPDAs[1],
+      SIZE / 2
+    );
+
+    const position = await getProgram().account.position.fetch(positionPDAs[1]);
+    // Half the notional and half the margin leave the isolated price where it was
+    expect(position.liquidationPrice.toNumber()).to.equal(80_000_000);
+    expect(position.size.toNumber()).to.equal(SIZE / 2);
+  });
+});
@@ -0,0 +1,105 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("Spot League Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+  let leaguePDA: PublicKey;
+  let leaderboardPDA: PublicKey;
+  let participantPDA: PublicKey;
+  let positionPDA: PublicKey;
+  const nonce = 120;
+
+  const PRICE = 100_000_000; // $100
+  const SIZE = 10_000_000; // 1,000 of notional
+
+  const open = (direction: any, leverage: number) =>
+    testHelpers.openPosition(
+      accounts.user2,
+      leaguePDA,
+      pdas.marketPDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      positionPDA,
+      direction,
+      SIZE,
+      leverage,
+      0
+    );
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE);
+    ({
+      leaguePDA,
+      leaderboardPDA,
+      participantPDAs: [participantPDA],
+    } = await testHelpers.setupLeague(accounts.user2, nonce, [accounts.user2], {
+      virtualOnDeposit: 1_000_000_000,
+      maxLeverage: 1,
+      leagueType: { spot: {} },
+    }));
+    positionPDA = testHelpers.positionPDA(leaguePDA, accounts.user2.publicKey, 0);
+  });
+
+  it("Should reject a spot league allowing leverage", async () => {
+    try {
+      await testHelpers.setupLeague(accounts.user2, nonce + 1, [], {
+        maxLeverage: 2,
+        leagueType: { spot: {} },
+      });
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InvalidLeverage");
+    }
+  });
+
+  it("Should reject shorts", async () => {
+    try {
+      await open({ short: {} }, 1);
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InvalidSpotOrder");
+    }
+  });
+
+  it("Should buy with the whole notional paid from the balance", async () => {
+    await open({ long: {} }, 1);
+
+    const participant = await getProgram().account.participant.fetch(participantPDA);
+    expect(participant.usedMargin.toNumber()).to.equal(1_000_000_000);
+  });
+
+  it("Should never liquidate spot holdings", async () => {
+    // A 90% drop would wipe out any leveraged account
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, 10_000_000);
+    await testHelpers.refreshParticipant(
+      accounts.user2,
+      leaguePDA,
+      participantPDA,
+      leaderboardPDA,
+      [positionPDA],
+      [pdas.marketPDA],
+      [pdas.priceFeedPDA]
+    );
+
+    const position = await getProgram().account.position.fetch(positionPDA);
+    expect(position.size.toNumber()).to.equal(SIZE);
+    expect(position.unrealizedPnl.toNumber()).to.equal(-900_000_000);
+    const participant = await getProgram().account.participant.fetch(participantPDA);
+    expect(participant.positions.length).to.equal(1);
+  });
+});
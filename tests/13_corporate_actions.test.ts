@@ -0,0 +1,128 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { PublicKey } from "@solana/web3.js";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("Corporate Action Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+  let leaguePDA: PublicKey;
+  let participantPDA: PublicKey;
+  let positionPDA: PublicKey;
+  const nonce = 130;
+
+  const PRICE = 100_000_000; // $100
+  const SIZE = 10_000_000; // 1,000 of notional
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE);
+    ({ leaguePDA, participantPDAs: [participantPDA] } = await testHelpers.setupLeague(
+      accounts.user3,
+      nonce,
+      [accounts.user3]
+    ));
+    positionPDA = testHelpers.positionPDA(leaguePDA, accounts.user3.publicKey, 0);
+    await testHelpers.openPosition(
+      accounts.user3,
+      leaguePDA,
+      pdas.marketPDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      positionPDA,
+      { long: {} },
+      SIZE,
+      5,
+      0
+    );
+  });
+
+  it("Should only let market managers record corporate actions", async () => {
+    try {
+      await testHelpers.recordCorporateAction(
+        pdas.marketPDA,
+        { split: { numerator: 2, denominator: 1 } },
+        accounts.user3
+      );
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("Should block the position until a split is applied", async () => {
+    await testHelpers.recordCorporateAction(
+      pdas.marketPDA,
+      { split: { numerator: 2, denominator: 1 } },
+      accounts.admin
+    );
+    // The oracle switches to the post-split price
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE / 2);
+
+    try {
+      await testHelpers.increasePositionSize(
+        accounts.user3,
+        leaguePDA,
+        pdas.marketPDA,
+        pdas.priceFeedPDA,
+        participantPDA,
+        positionPDA,
+        SIZE
+      );
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("CorporateActionPending");
+    }
+  });
+
+  it("Should rescale size and entry price on a split, keeping the notional", async () => {
+    await testHelpers.applyCorporateAction(accounts.user3, participantPDA, positionPDA);
+
+    const position = await getProgram().account.position.fetch(positionPDA);
+    expect(position.size.toNumber()).to.equal(2 * SIZE);
+    expect(position.entryPrice.toNumber()).to.equal(PRICE / 2);
+    expect(position.notional.toNumber()).to.equal(1_000_000_000);
+    expect(position.corporateActionsApplied).to.equal(
+      (await getProgram().account.market.fetch(pdas.marketPDA)).corporateActionCount
+    );
+  });
+
+  it("Should credit a dividend to a long", async () => {
+    const before = await getProgram().account.participant.fetch(participantPDA);
+    // 0.5 per unit on 20 units
+    await testHelpers.recordCorporateAction(
+      pdas.marketPDA,
+      { dividend: { amountPerUnit: new BN(500_000) } },
+      accounts.admin
+    );
+    await testHelpers.applyCorporateAction(accounts.user3, participantPDA, positionPDA);
+
+    const after = await getProgram().account.participant.fetch(participantPDA);
+    expect(after.virtualBalance.sub(before.virtualBalance).toNumber()).to.equal(10_000_000);
+  });
+
+  it("Should reject a split that changes nothing", async () => {
+    try {
+      await testHelpers.recordCorporateAction(
+        pdas.marketPDA,
+        { split: { numerator: 3, denominator: 3 } },
+        accounts.admin
+      );
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InvalidCorporateAction");
+    }
+  });
+});
@@ -0,0 +1,161 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("DCA Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+  let leaguePDA: PublicKey;
+  let participantPDA: PublicKey;
+  const nonce = 140;
+
+  const PRICE = 100_000_000; // $100
+  const SLICE = 1_000_000; // 100 of notional
+  const HOUR = 3600;
+
+  const executeSlice = (schedulePDA: PublicKey, positionPDA: PublicKey) =>
+    testHelpers.executeDcaSlice(
+      accounts.admin,
+      accounts.user1.publicKey,
+      leaguePDA,
+      pdas.marketPDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      positionPDA,
+      schedulePDA
+    );
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE);
+    ({ leaguePDA, participantPDAs: [participantPDA] } = await testHelpers.setupLeague(
+      accounts.user1,
+      nonce,
+      [accounts.user1]
+    ));
+  });
+
+  it("Should open the position with the first slice", async () => {
+    const { dcaSchedulePDA } = await testHelpers.createDcaSchedule(
+      accounts.user1,
+      leaguePDA,
+      participantPDA,
+      pdas.marketPDA,
+      0,
+      null,
+      { increase: {} },
+      { direction: { long: {} }, leverage: 5 },
+      SLICE,
+      3,
+      HOUR,
+      0
+    );
+    const positionPDA = testHelpers.positionPDA(leaguePDA, accounts.user1.publicKey, 0);
+
+    await executeSlice(dcaSchedulePDA, positionPDA);
+
+    const position = await getProgram().account.position.fetch(positionPDA);
+    expect(position.size.toNumber()).to.equal(SLICE);
+    expect(position.leverage).to.equal(5);
+    const schedule = await getProgram().account.dcaSchedule.fetch(dcaSchedulePDA);
+    expect(schedule.position.toBase58()).to.equal(positionPDA.toBase58());
+    expect(schedule.positionRent.toNumber()).to.equal(0);
+    expect(schedule.executedSlices).to.equal(1);
+  });
+
+  it("Should not execute a slice before it is due", async () => {
+    const dcaSchedulePDA = testHelpers.dcaSchedulePDA(participantPDA, pdas.marketPDA, 0);
+    const positionPDA = testHelpers.positionPDA(leaguePDA, accounts.user1.publicKey, 0);
+    try {
+      await executeSlice(dcaSchedulePDA, positionPDA);
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("DcaSliceNotDue");
+    }
+  });
+
+  it("Should run a second schedule on the same market", async () => {
+    const positionPDA = testHelpers.positionPDA(leaguePDA, accounts.user1.publicKey, 0);
+    const { dcaSchedulePDA } = await testHelpers.createDcaSchedule(
+      accounts.user1,
+      leaguePDA,
+      participantPDA,
+      pdas.marketPDA,
+      1,
+      positionPDA,
+      { increase: {} },
+      null,
+      SLICE,
+      2,
+      HOUR,
+      0
+    );
+
+    await executeSlice(dcaSchedulePDA, positionPDA);
+
+    const position = await getProgram().account.position.fetch(positionPDA);
+    expect(position.size.toNumber()).to.equal(2 * SLICE);
+  });
+
+  it("Should refund the position deposit when cancelled before opening", async () => {
+    const { dcaSchedulePDA } = await testHelpers.createDcaSchedule(
+      accounts.user1,
+      leaguePDA,
+      participantPDA,
+      pdas.marketPDA,
+      2,
+      null,
+      { increase: {} },
+      { direction: { short: {} }, leverage: 2 },
+      SLICE,
+      2,
+      HOUR,
+      Math.floor(Date.now() / 1000) + HOUR
+    );
+    const connection = getProgram().provider.connection;
+    const deposit = await connection.getBalance(dcaSchedulePDA);
+    const before = await connection.getBalance(accounts.user1.publicKey);
+
+    await testHelpers.cancelDcaSchedule(accounts.user1, dcaSchedulePDA);
+
+    const after = await connection.getBalance(accounts.user1.publicKey);
+    // Schedule rent and the position deposit come back, less the fee
+    expect(after - before).to.be.greaterThan(deposit - 10_000);
+    expect(await connection.getAccountInfo(dcaSchedulePDA)).to.equal(null);
+  });
+
+  it("Should reject a schedule with both a position and an open", async () => {
+    const positionPDA = testHelpers.positionPDA(leaguePDA, accounts.user1.publicKey, 0);
+    try {
+      await testHelpers.createDcaSchedule(
+        accounts.user1,
+        leaguePDA,
+        participantPDA,
+        pdas.marketPDA,
+        3,
+        positionPDA,
+        { increase: {} },
+        { direction: { long: {} }, leverage: 5 },
+        SLICE,
+        2,
+        HOUR,
+        0
+      );
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InvalidDcaSchedule");
+    }
+  });
+});
@@ -0,0 +1,154 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import {
+  getAccount,
+  getAssociatedTokenAddress,
+  getOrCreateAssociatedTokenAccount,
+} from "@solana/spl-token";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getProvider,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("Community Market Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+  let leaguePDA: PublicKey;
+  let otherLeaguePDA: PublicKey;
+  let marketPDA: PublicKey;
+  let listingBondPDA: PublicKey;
+  const nonce = 150;
+
+  const BOND = 5_000_000;
+
+  const balance = async (ata: PublicKey) =>
+    Number((await getAccount(getProvider().connection, ata)).amount);
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+
+    await testHelpers.setListingBond(accounts.entryTokenMint, BOND, accounts.admin);
+    ({ leaguePDA } = await testHelpers.setupLeague(accounts.user2, nonce, []));
+    ({ leaguePDA: otherLeaguePDA } = await testHelpers.setupLeague(
+      accounts.user3,
+      nonce + 1,
+      []
+    ));
+    for (const creator of [accounts.user2, accounts.user3]) {
+      await globalTestState.setupUserTokenAccount(
+        creator,
+        accounts.entryTokenMint,
+        accounts.admin,
+        2 * BOND
+      );
+    }
+  });
+
+  it("Should post the bond and list a restricted market", async () => {
+    ({ marketPDA, listingBondPDA } = await testHelpers.listCommunityMarket(
+      "COMM-USD",
+      6,
+      10,
+      pdas.priceFeedPDA,
+      accounts.baseCurrency,
+      accounts.entryTokenMint,
+      accounts.user2,
+      leaguePDA
+    ));
+
+    const market = await getProgram().account.market.fetch(marketPDA);
+    expect(market.restricted).to.equal(true);
+    expect(market.listedBy.toBase58()).to.equal(accounts.user2.publicKey.toBase58());
+    const listingBond = await getProgram().account.listingBond.fetch(listingBondPDA);
+    expect(listingBond.amount.toNumber()).to.equal(BOND);
+    const vault = await getAssociatedTokenAddress(accounts.entryTokenMint, listingBondPDA, true);
+    expect(await balance(vault)).to.equal(BOND);
+  });
+
+  it("Should not take the address of the admin market on the same feed", async () => {
+    expect(marketPDA.toBase58()).to.not.equal(pdas.marketPDA.toBase58());
+    const adminMarket = await getProgram().account.market.fetch(pdas.marketPDA);
+    expect(adminMarket.restricted).to.equal(false);
+  });
+
+  it("Should reject a listing with someone else's league", async () => {
+    try {
+      await testHelpers.listCommunityMarket(
+        "COMM-USD",
+        6,
+        10,
+        accounts.baseCurrency,
+        accounts.baseCurrency,
+        accounts.entryTokenMint,
+        accounts.user3,
+        leaguePDA
+      );
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("NotCreator");
+    }
+  });
+
+  it("Should return the bond when the market is promoted", async () => {
+    const creatorAta = await getAssociatedTokenAddress(
+      accounts.entryTokenMint,
+      accounts.user2.publicKey
+    );
+    const before = await balance(creatorAta);
+
+    await testHelpers.promoteMarket(
+      marketPDA,
+      accounts.entryTokenMint,
+      accounts.user2.publicKey,
+      accounts.admin
+    );
+
+    const market = await getProgram().account.market.fetch(marketPDA);
+    expect(market.restricted).to.equal(false);
+    expect((await balance(creatorAta)) - before).to.equal(BOND);
+  });
+
+  it("Should slash the bond to the treasury on delisting", async () => {
+    const { marketPDA: slashedMarketPDA } = await testHelpers.listCommunityMarket(
+      "COMM-USD",
+      6,
+      10,
+      pdas.priceFeedPDA,
+      accounts.baseCurrency,
+      accounts.entryTokenMint,
+      accounts.user3,
+      otherLeaguePDA
+    );
+    const treasuryAta = (
+      await getOrCreateAssociatedTokenAccount(
+        getProvider().connection,
+        accounts.admin,
+        accounts.entryTokenMint,
+        accounts.treasury.publicKey,
+        true // allowOwnerOffCurve
+      )
+    ).address;
+    const before = await balance(treasuryAta);
+
+    await testHelpers.delistCommunityMarket(
+      slashedMarketPDA,
+      accounts.entryTokenMint,
+      accounts.treasury.publicKey,
+      true,
+      accounts.admin
+    );
+
+    const market = await getProgram().account.market.fetch(slashedMarketPDA);
+    expect(market.isActive).to.equal(false);
+    expect((await balance(treasuryAta)) - before).to.equal(BOND);
+  });
+});
@@ -0,0 +1,108 @@
+import { expect } from "chai";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("Role Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+  let borrowRateBps: number;
+
+  const expectUnauthorized = async (call: () => Promise<string>) => {
+    try {
+      await call();
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  };
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+
+    await testHelpers.ensureMarketListed();
+    borrowRateBps = (await getProgram().account.market.fetch(pdas.marketPDA)).borrowRateBps;
+  });
+
+  after(async () => {
+    const globalState = await getProgram().account.globalState.fetch(pdas.globalStatePDA);
+    for (const grant of globalState.roles) {
+      if (grant.key.equals(accounts.user4.publicKey)) {
+        await testHelpers.revokeRole(grant.key, grant.role, accounts.admin);
+      }
+    }
+  });
+
+  it("Should reject a key without the role", async () => {
+    await expectUnauthorized(() =>
+      testHelpers.setBorrowRate(pdas.marketPDA, borrowRateBps + 1, accounts.user4)
+    );
+  });
+
+  it("Should let a grantee use its role", async () => {
+    await testHelpers.grantRole(accounts.user4.publicKey, { feeManager: {} }, accounts.admin);
+
+    await testHelpers.setBorrowRate(pdas.marketPDA, borrowRateBps + 1, accounts.user4);
+    const market = await getProgram().account.market.fetch(pdas.marketPDA);
+    expect(market.borrowRateBps).to.equal(borrowRateBps + 1);
+
+    await testHelpers.setBorrowRate(pdas.marketPDA, borrowRateBps, accounts.user4);
+  });
+
+  it("Should not let a role stand in for another", async () => {
+    await expectUnauthorized(() =>
+      testHelpers.setProtocolPause(true, false, accounts.user4)
+    );
+  });
+
+  it("Should let a pauser pause and unpause the protocol", async () => {
+    await testHelpers.grantRole(accounts.user4.publicKey, { pauser: {} }, accounts.admin);
+
+    await testHelpers.setProtocolPause(true, false, accounts.user4);
+    expect((await getProgram().account.globalState.fetch(pdas.globalStatePDA)).paused).to.equal(
+      true
+    );
+    await testHelpers.setProtocolPause(false, false, accounts.user4);
+    expect((await getProgram().account.globalState.fetch(pdas.globalStatePDA)).paused).to.equal(
+      false
+    );
+  });
+
+  it("Should not let a role holder grant roles", async () => {
+    await expectUnauthorized(() =>
+      testHelpers.grantRole(accounts.user5.publicKey, { feeManager: {} }, accounts.user4)
+    );
+  });
+
+  it("Should reject granting a role twice", async () => {
+    try {
+      await testHelpers.grantRole(accounts.user4.publicKey, { feeManager: {} }, accounts.admin);
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("RoleAlreadyGranted");
+    }
+  });
+
+  it("Should take the role away when revoked", async () => {
+    await testHelpers.revokeRole(accounts.user4.publicKey, { feeManager: {} }, accounts.admin);
+
+    await expectUnauthorized(() =>
+      testHelpers.setBorrowRate(pdas.marketPDA, borrowRateBps + 1, accounts.user4)
+    );
+    try {
+      await testHelpers.revokeRole(accounts.user4.publicKey, { feeManager: {} }, accounts.admin);
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("RoleNotGranted");
+    }
+  });
+});
@@ -0,0 +1,125 @@
+import { expect } from "chai";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { getOrCreateAssociatedTokenAccount } from "@solana/spl-token";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getProvider,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("Admin Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+
+  const fetchGlobalState = () => getProgram().account.globalState.fetch(pdas.globalStatePDA);
+
+  const expectError = async (call: () => Promise<string>, error: string) => {
+    try {
+      await call();
+      expect.fail("Should have failed");
+    } catch (e) {
+      expect(e.message).to.include(error);
+    }
+  };
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+  });
+
+  describe("Admin transfer", () => {
+    it("Should only let the admin propose", async () => {
+      await expectError(
+        () => testHelpers.proposeAdmin(accounts.user5.publicKey, accounts.user5),
+        "Unauthorized"
+      );
+    });
+
+    it("Should reject acceptance by anyone but the proposed admin", async () => {
+      await testHelpers.proposeAdmin(accounts.user5.publicKey, accounts.admin);
+      const globalState = await fetchGlobalState();
+      expect(globalState.pendingAdmin.toBase58()).to.equal(accounts.user5.publicKey.toBase58());
+
+      await expectError(() => testHelpers.acceptAdmin(accounts.user4), "NotPendingAdmin");
+    });
+
+    it("Should cancel a pending proposal", async () => {
+      await testHelpers.proposeAdmin(null, accounts.admin);
+      expect((await fetchGlobalState()).pendingAdmin).to.equal(null);
+
+      await expectError(() => testHelpers.acceptAdmin(accounts.user5), "NotPendingAdmin");
+    });
+
+    it("Should hand over once the proposed admin accepts", async () => {
+      await testHelpers.proposeAdmin(accounts.user5.publicKey, accounts.admin);
+      await testHelpers.acceptAdmin(accounts.user5);
+
+      let globalState = await fetchGlobalState();
+      expect(globalState.admin.toBase58()).to.equal(accounts.user5.publicKey.toBase58());
+      expect(globalState.pendingAdmin).to.equal(null);
+      await expectError(() => testHelpers.setFeeBps(0, accounts.admin), "Unauthorized");
+
+      // Hand it back for the rest of the suites
+      await testHelpers.proposeAdmin(accounts.admin.publicKey, accounts.user5);
+      await testHelpers.acceptAdmin(accounts.admin);
+      globalState = await fetchGlobalState();
+      expect(globalState.admin.toBase58()).to.equal(accounts.admin.publicKey.toBase58());
+    });
+  });
+
+  describe("Global config", () => {
+    it("Should set a new treasury wallet", async () => {
+      const treasury = Keypair.generate().publicKey;
+      await testHelpers.setTreasury(treasury, accounts.admin);
+      expect((await fetchGlobalState()).treasury.toBase58()).to.equal(treasury.toBase58());
+
+      await testHelpers.setTreasury(accounts.treasury.publicKey, accounts.admin);
+      expect((await fetchGlobalState()).treasury.toBase58()).to.equal(
+        accounts.treasury.publicKey.toBase58()
+      );
+    });
+
+    it("Should reject the default key as treasury", async () => {
+      await expectError(
+        () => testHelpers.setTreasury(PublicKey.default, accounts.admin),
+        "InvalidTreasury"
+      );
+    });
+
+    it("Should reject a token account as treasury", async () => {
+      const tokenAccount = (
+        await getOrCreateAssociatedTokenAccount(
+          getProvider().connection,
+          accounts.admin,
+          accounts.entryTokenMint,
+          accounts.admin.publicKey
+        )
+      ).address;
+      await expectError(
+        () => testHelpers.setTreasury(tokenAccount, accounts.admin),
+        "InvalidTreasury"
+      );
+    });
+
+    it("Should only let the admin set the treasury", async () => {
+      await expectError(
+        () => testHelpers.setTreasury(accounts.user5.publicKey, accounts.user5),
+        "Unauthorized"
+      );
+    });
+
+    it("Should reject the default key as permission program", async () => {
+      await expectError(
+        () => testHelpers.setPermissionProgram(PublicKey.default, accounts.admin),
+        "InvalidPermissionProgram"
+      );
+    });
+  });
+});
@@ -0,0 +1,79 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("League Pause Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+  let leaguePDA: PublicKey;
+  const nonce = 160;
+
+  const fetchLeague = () => getProgram().account.league.fetch(leaguePDA);
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+
+    ({ leaguePDA } = await testHelpers.setupLeague(accounts.user4, nonce, []));
+  });
+
+  after(async () => {
+    await testHelpers.revokeRole(
+      accounts.user5.publicKey,
+      { leagueModerator: {} },
+      accounts.admin
+    );
+  });
+
+  it("Should let the creator pause and resume their league", async () => {
+    await testHelpers.setLeaguePause(leaguePDA, true, false, accounts.user4);
+    expect((await fetchLeague()).paused).to.equal(true);
+
+    await testHelpers.setLeaguePause(leaguePDA, false, false, accounts.user4);
+    expect((await fetchLeague()).paused).to.equal(false);
+  });
+
+  it("Should not let the creator pause withdrawals", async () => {
+    try {
+      await testHelpers.setLeaguePause(leaguePDA, true, true, accounts.user4);
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("Should let a league moderator pause and lift withdrawals", async () => {
+    await testHelpers.grantRole(
+      accounts.user5.publicKey,
+      { leagueModerator: {} },
+      accounts.admin
+    );
+
+    await testHelpers.setLeaguePause(leaguePDA, true, true, accounts.user5);
+    expect((await fetchLeague()).withdrawalsPaused).to.equal(true);
+
+    // The creator can't lift it either, but can still leave the league paused
+    try {
+      await testHelpers.setLeaguePause(leaguePDA, false, false, accounts.user4);
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+    await testHelpers.setLeaguePause(leaguePDA, true, true, accounts.user4);
+
+    await testHelpers.setLeaguePause(leaguePDA, false, false, accounts.user5);
+    const league = await fetchLeague();
+    expect(league.paused).to.equal(false);
+    expect(league.withdrawalsPaused).to.equal(false);
+  });
+});
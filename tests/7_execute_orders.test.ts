@@ -0,0 +1,165 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { PublicKey, SystemProgram } from "@solana/web3.js";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("Execute Orders Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+  let leaguePDA: PublicKey;
+  let participantPDA: PublicKey;
+  const nonce = 70;
+
+  const PRICE = 100_000_000; // $100
+  const meta = (pubkey: PublicKey, isWritable = false) => ({
+    pubkey,
+    isWritable,
+    isSigner: false,
+  });
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE);
+    // 10,000 of virtual balance
+    ({ leaguePDA, participantPDAs: [participantPDA] } = await testHelpers.setupLeague(
+      accounts.user1,
+      nonce,
+      [accounts.user1]
+    ));
+
+    // A 1x long of 60 holds 6,000
+    await testHelpers.openPosition(
+      accounts.user1,
+      leaguePDA,
+      pdas.marketPDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      testHelpers.positionPDA(leaguePDA, accounts.user1.publicKey, 0),
+      { long: {} },
+      60_000_000,
+      1,
+      0
+    );
+  });
+
+  const leg = (kind: any, size: number, seqNum: number) => ({
+    kind,
+    size,
+    positionPDA: testHelpers.positionPDA(leaguePDA, accounts.user1.publicKey, seqNum),
+    marketPDA: pdas.marketPDA,
+    oracleFeed: pdas.priceFeedPDA,
+  });
+
+  it("Should reject a leg that needs more margin than the legs free", async () => {
+    try {
+      await testHelpers.executeOrders(accounts.user1, leaguePDA, participantPDA, [
+        leg({ open: { direction: { short: {} }, leverage: 1 } }, 60_000_000, 1),
+      ]);
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("InsufficientMargin");
+    }
+
+    // Nothing from the failed batch was applied
+    const participant = await getProgram().account.participant.fetch(participantPDA);
+    expect(participant.positions.length).to.equal(1);
+    expect(participant.currentPositionSeq.toNumber()).to.equal(1);
+  });
+
+  it("Should check margin once after every leg", async () => {
+    // The short needs 6,000 with only 4,000 free, the reduce after it frees 3,000
+    await testHelpers.executeOrders(accounts.user1, leaguePDA, participantPDA, [
+      leg({ open: { direction: { short: {} }, leverage: 1 } }, 60_000_000, 1),
+      leg({ decrease: {} }, 30_000_000, 0),
+    ]);
+
+    const participant = await getProgram().account.participant.fetch(participantPDA);
+    expect(participant.positions.length).to.equal(2);
+    expect(participant.usedMargin.toNumber()).to.equal(9_000_000_000);
+
+    const long = await getProgram().account.position.fetch(leg({}, 0, 0).positionPDA);
+    expect(long.size.toNumber()).to.equal(30_000_000);
+    const short = await getProgram().account.position.fetch(leg({}, 0, 1).positionPDA);
+    expect(short.direction).to.deep.equal({ short: {} });
+    expect(short.size.toNumber()).to.equal(60_000_000);
+  });
+
+  describe("Remaining accounts layout", () => {
+    const executeRaw = (remainingAccounts: any[]) =>
+      getProgram()
+        .methods.executeOrders([
+          { kind: { increase: {} }, size: new BN(1_000_000), acceptablePrice: null },
+        ])
+        .accounts({
+          globalState: pdas.globalStatePDA,
+          user: accounts.user1.publicKey,
+          league: leaguePDA,
+          participant: participantPDA,
+          systemProgram: SystemProgram.programId,
+        } as any)
+        .remainingAccounts(remainingAccounts)
+        .signers([accounts.user1])
+        .rpc();
+
+    it("Should reject a leg missing its oracle", async () => {
+      try {
+        await executeRaw([meta(leg({}, 0, 0).positionPDA, true), meta(pdas.marketPDA)]);
+        expect.fail("Should have failed");
+      } catch (error) {
+        expect(error.message).to.include("InvalidOrderLegs");
+      }
+    });
+
+    it("Should reject accounts left over after the last leg", async () => {
+      try {
+        await executeRaw([
+          meta(leg({}, 0, 0).positionPDA, true),
+          meta(pdas.marketPDA),
+          meta(pdas.priceFeedPDA),
+          meta(pdas.priceFeedPDA),
+        ]);
+        expect.fail("Should have failed");
+      } catch (error) {
+        expect(error.message).to.include("InvalidOrderLegs");
+      }
+    });
+
+    it("Should reject an oracle the market was not listed with", async () => {
+      try {
+        await executeRaw([
+          meta(leg({}, 0, 0).positionPDA, true),
+          meta(pdas.marketPDA),
+          meta(accounts.oracleFeed.publicKey),
+        ]);
+        expect.fail("Should have failed");
+      } catch (error) {
+        expect(error.message).to.include("OracleMismatch");
+      }
+    });
+
+    it("Should reject a position that is not the participant's", async () => {
+      try {
+        await executeRaw([
+          meta(leg({}, 0, 5).positionPDA, true),
+          meta(pdas.marketPDA),
+          meta(pdas.priceFeedPDA),
+        ]);
+        expect.fail("Should have failed");
+      } catch (error) {
+        expect(error.message).to.include("PositionMismatch");
+      }
+    });
+  });
+});
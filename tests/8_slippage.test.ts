@@ -0,0 +1,127 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("Slippage Guard Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+  let leaguePDA: PublicKey;
+  let participantPDA: PublicKey;
+  let positionPDA: PublicKey;
+  const nonce = 80;
+
+  const PRICE = 100_000_000; // $100
+  const SIZE = 1_000_000;
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE);
+    ({ leaguePDA, participantPDAs: [participantPDA] } = await testHelpers.setupLeague(
+      accounts.user1,
+      nonce,
+      [accounts.user1]
+    ));
+    positionPDA = testHelpers.positionPDA(leaguePDA, accounts.user1.publicKey, 0);
+  });
+
+  it("Should reject opening a long above the acceptable price", async () => {
+    try {
+      await testHelpers.openPosition(
+        accounts.user1,
+        leaguePDA,
+        pdas.marketPDA,
+        pdas.priceFeedPDA,
+        participantPDA,
+        positionPDA,
+        { long: {} },
+        SIZE,
+        5,
+        0,
+        PRICE - 1
+      );
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("SlippageExceeded");
+    }
+  });
+
+  it("Should open a long at the acceptable price", async () => {
+    await testHelpers.openPosition(
+      accounts.user1,
+      leaguePDA,
+      pdas.marketPDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      positionPDA,
+      { long: {} },
+      SIZE,
+      5,
+      0,
+      PRICE
+    );
+
+    const position = await getProgram().account.position.fetch(positionPDA);
+    expect(position.entryPrice.toNumber()).to.equal(PRICE);
+  });
+
+  it("Should reject increasing after the price moved past the bound", async () => {
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE + 1_000_000);
+    try {
+      await testHelpers.increasePositionSize(
+        accounts.user1,
+        leaguePDA,
+        pdas.marketPDA,
+        pdas.priceFeedPDA,
+        participantPDA,
+        positionPDA,
+        SIZE,
+        PRICE
+      );
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("SlippageExceeded");
+    }
+  });
+
+  it("Should reject selling a long below the acceptable price", async () => {
+    try {
+      await testHelpers.decreasePositionSize(
+        accounts.user1,
+        leaguePDA,
+        pdas.priceFeedPDA,
+        participantPDA,
+        positionPDA,
+        SIZE,
+        PRICE + 2_000_000
+      );
+      expect.fail("Should have failed");
+    } catch (error) {
+      expect(error.message).to.include("SlippageExceeded");
+    }
+
+    // A bound the price clears closes the position
+    await testHelpers.decreasePositionSize(
+      accounts.user1,
+      leaguePDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      positionPDA,
+      SIZE,
+      PRICE
+    );
+    const position = await getProgram().account.position.fetch(positionPDA);
+    expect(position.size.toNumber()).to.equal(0);
+  });
+});
@@ -0,0 +1,220 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import {
+  globalTestState,
+  getProgram,
+  getOracleProgram,
+  getAccounts,
+  getPDAs,
+} from "./0_global-setup";
+import { TestHelpers } from "./helpers";
+
+describe("Margin Mode Tests", () => {
+  let testHelpers: TestHelpers;
+  let accounts: any;
+  let pdas: any;
+
+  const PRICE = 100_000_000; // $100
+  const SIZE = 10_000_000; // 10 units, 1,000 of notional
+  const LEVERAGE = 10; // 100 of margin
+
+  before(async () => {
+    await globalTestState.initialize();
+    accounts = getAccounts();
+    pdas = getPDAs();
+    testHelpers = new TestHelpers(getProgram(), getOracleProgram(), accounts, pdas);
+  });
+
+  // Open a long and a short of SIZE at PRICE, then refresh at `price`
+  async function openPairAndRefresh(
+    leaguePDA: PublicKey,
+    leaderboardPDA: PublicKey,
+    participantPDA: PublicKey,
+    user: any,
+    price: number
+  ): Promise<PublicKey[]> {
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE);
+    const positionPDAs = [0, 1].map((seqNum) =>
+      testHelpers.positionPDA(leaguePDA, user.publicKey, seqNum)
+    );
+    for (const [seqNum, direction] of [{ long: {} }, { short: {} }].entries()) {
+      await testHelpers.openPosition(
+        user,
+        leaguePDA,
+        pdas.marketPDA,
+        pdas.priceFeedPDA,
+        participantPDA,
+        positionPDAs[seqNum],
+        direction as any,
+        SIZE,
+        LEVERAGE,
+        seqNum
+      );
+    }
+
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, price);
+    await testHelpers.refreshParticipant(
+      user,
+      leaguePDA,
+      participantPDA,
+      leaderboardPDA,
+      positionPDAs,
+      [pdas.marketPDA, pdas.marketPDA],
+      [pdas.priceFeedPDA, pdas.priceFeedPDA]
+    );
+    return positionPDAs;
+  }
+
+  it("Should liquidate an isolated position on its own margin", async () => {
+    const {
+      leaguePDA,
+      leaderboardPDA,
+      participantPDAs: [participantPDA],
+    } = await testHelpers.setupLeague(accounts.user1, 90, [accounts.user1], {
+      marginMode: { isolated: {} },
+    });
+
+    // At 85 the long has lost 150 against 100 of margin, the short is up 150
+    const [longPDA, shortPDA] = await openPairAndRefresh(
+      leaguePDA,
+      leaderboardPDA,
+      participantPDA,
+      accounts.user1,
+      85_000_000
+    );
+
+    const long = await getProgram().account.position.fetch(longPDA);
+    expect(long.size.toNumber()).to.equal(0);
+    // The loss is capped at the position's margin
+    expect(long.closedPnl.toNumber()).to.equal(-100_000_000);
+
+    const short = await getProgram().account.position.fetch(shortPDA);
+    expect(short.size.toNumber()).to.equal(SIZE);
+
+    const participant = await getProgram().account.participant.fetch(participantPDA);
+    expect(participant.positions.map((p) => p.toString())).to.deep.equal([
+      shortPDA.toString(),
+    ]);
+    expect(participant.virtualBalance.toNumber()).to.equal(10_000_000_000 - 100_000_000);
+    expect(participant.usedMargin.toNumber()).to.equal(100_000_000);
+  });
+
+  it("Should keep a cross position open while the account has equity", async () => {
+    const {
+      leaguePDA,
+      leaderboardPDA,
+      participantPDAs: [participantPDA],
+    } = await testHelpers.setupLeague(accounts.user1, 91, [accounts.user1]);
+
+    const [longPDA] = await openPairAndRefresh(
+      leaguePDA,
+      leaderboardPDA,
+      participantPDA,
+      accounts.user1,
+      85_000_000
+    );
+
+    const long = await getProgram().account.position.fetch(longPDA);
+    expect(long.size.toNumber()).to.equal(SIZE);
+    expect(long.unrealizedPnl.toNumber()).to.equal(-150_000_000);
+
+    const participant = await getProgram().account.participant.fetch(participantPDA);
+    expect(participant.positions.length).to.equal(2);
+  });
+
+  it("Should liquidate every cross position once equity goes negative", async () => {
+    const {
+      leaguePDA,
+      leaderboardPDA,
+      participantPDAs: [participantPDA],
+    } = await testHelpers.setupLeague(accounts.user2, 92, [accounts.user2], {
+      virtualOnDeposit: 1_000_000_000,
+    });
+
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE);
+    const positionPDA = testHelpers.positionPDA(leaguePDA, accounts.user2.publicKey, 0);
+    // 90 units at 10x holds 900 of the 1,000 balance
+    await testHelpers.openPosition(
+      accounts.user2,
+      leaguePDA,
+      pdas.marketPDA,
+      pdas.priceFeedPDA,
+      participantPDA,
+      positionPDA,
+      { long: {} },
+      90_000_000,
+      LEVERAGE,
+      0
+    );
+
+    // At 88 the loss is 1,080
+    await testHelpers.setOraclePrice(pdas.priceFeedPDA, 88_000_000);
+    await testHelpers.refreshParticipant(
+      accounts.user2,
+      leaguePDA,
+      participantPDA,
+      leaderboardPDA,
+      [positionPDA],
+      [pdas.marketPDA],
+      [pdas.priceFeedPDA]
+    );
+
+    const participant = await getProgram().account.participant.fetch(participantPDA);
+    expect(participant.positions.length).to.equal(0);
+    expect(participant.usedMargin.toNumber()).to.equal(0);
+    expect(participant.virtualBalance.toNumber()).to.equal(1_000_000_000 - 1_080_000_000);
+  });
+
+  describe("Participant choice", () => {
+    it("Should reject a mode change when the league fixes the mode", async () => {
+      const {
+        leaguePDA,
+        participantPDAs: [participantPDA],
+      } = await testHelpers.setupLeague(accounts.user3, 93, [accounts.user3]);
+      try {
+        await testHelpers.setMarginMode(accounts.user3, leaguePDA, participantPDA, {
+          isolated: {},
+        });
+        expect.fail("Should have failed");
+      } catch (error) {
+        expect(error.message).to.include("MarginModeNotSelectable");
+      }
+    });
+
+    it("Should switch modes only without open positions", async () => {
+      const {
+        leaguePDA,
+        participantPDAs: [participantPDA],
+      } = await testHelpers.setupLeague(accounts.user3, 94, [accounts.user3], {
+        marginModeSelectable: true,
+      });
+      await testHelpers.setMarginMode(accounts.user3, leaguePDA, participantPDA, {
+        isolated: {},
+      });
+      let participant = await getProgram().account.participant.fetch(participantPDA);
+      expect(participant.marginMode).to.deep.equal({ isolated: {} });
+
+      await testHelpers.setOraclePrice(pdas.priceFeedPDA, PRICE);
+      await testHelpers.openPosition(
+        accounts.user3,
+        leaguePDA,
+        pdas.marketPDA,
+        pdas.priceFeedPDA,
+        participantPDA,
+        testHelpers.positionPDA(leaguePDA, accounts.user3.publicKey, 0),
+        { long: {} },
+        SIZE,
+        LEVERAGE,
+        0
+      );
+      try {
+        await testHelpers.setMarginMode(accounts.user3, leaguePDA, participantPDA, {
+          cross: {},
+        });
+        expect.fail("Should have failed");
+      } catch (error) {
+        expect(error.message).to.include("OpenPositionsExist");
+      }
+    });
+  });
+});
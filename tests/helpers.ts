@@ -30,174 +30,1229 @@ export class TestHelpers {
     this.pdas = pdas;
   }
 
+  // Grant a role, e.g. { marketManager: {} }, to a key
+  async grantRole(key: PublicKey, role: any, admin: Keypair): Promise<string> {
+    const tx = await this.program.methods
+      .grantRole(key, role)
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Grant role tx:", tx);
+    return tx;
+  }
+
+  async revokeRole(key: PublicKey, role: any, admin: Keypair): Promise<string> {
+    const tx = await this.program.methods
+      .revokeRole(key, role)
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Revoke role tx:", tx);
+    return tx;
+  }
+
+  // Propose a new admin, null cancels the proposal
+  async proposeAdmin(newAdmin: PublicKey | null, admin: Keypair): Promise<string> {
+    const tx = await this.program.methods
+      .proposeAdmin(newAdmin)
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Propose admin tx:", tx);
+    return tx;
+  }
+
+  // Take over as admin, signed by the proposed admin
+  async acceptAdmin(newAdmin: Keypair): Promise<string> {
+    const tx = await this.program.methods
+      .acceptAdmin()
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        newAdmin: newAdmin.publicKey,
+      } as any)
+      .signers([newAdmin])
+      .rpc();
+
+    console.log("Accept admin tx:", tx);
+    return tx;
+  }
+
+  async setFeeBps(feeBps: number, admin: Keypair): Promise<string> {
+    const tx = await this.program.methods
+      .setFeeBps(feeBps)
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Set fee bps tx:", tx);
+    return tx;
+  }
+
+  async setTreasury(treasury: PublicKey, admin: Keypair): Promise<string> {
+    const tx = await this.program.methods
+      .setTreasury()
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        treasury,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Set treasury tx:", tx);
+    return tx;
+  }
+
+  async setPermissionProgram(permissionProgram: PublicKey, admin: Keypair): Promise<string> {
+    const tx = await this.program.methods
+      .setPermissionProgram(permissionProgram)
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Set permission program tx:", tx);
+    return tx;
+  }
+
+  async setProtocolPause(
+    paused: boolean,
+    withdrawalsPaused: boolean,
+    admin: Keypair
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .setProtocolPause(paused, withdrawalsPaused)
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Set protocol pause tx:", tx);
+    return tx;
+  }
+
+  async setLeaguePause(
+    leaguePDA: PublicKey,
+    paused: boolean,
+    withdrawalsPaused: boolean,
+    authority: Keypair
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .setLeaguePause(paused, withdrawalsPaused)
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        league: leaguePDA,
+        authority: authority.publicKey,
+      } as any)
+      .signers([authority])
+      .rpc();
+
+    console.log("Set league pause tx:", tx);
+    return tx;
+  }
+
   // List a market
   async listMarket(
     symbol: string,
     decimals: number,
     maxLeverage: number,
     oracleFeed: PublicKey,
-    baseCurrency: PublicKey,
-    user: Keypair // User who is listing the market
+    baseCurrency: PublicKey,
+    user: Keypair, // User who is listing the market
+    borrowRateBps: number = 0,
+    maxPriceAge: number = 0,
+    priceDecimals: number = 6,
+    fxFeed: PublicKey | null = null, // markets quoted in another currency only
+    fxPriceDecimals: number = 6
+  ): Promise<string> {
+    const symbolBuffer = Array.from(Buffer.from(symbol.padEnd(16, "\0")));
+    const marketPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("market"), oracleFeed.toBuffer()],
+      this.program.programId
+    )[0];
+
+    const tx = await this.program.methods
+      .listMarket(
+        symbolBuffer,
+        decimals,
+        maxLeverage,
+        borrowRateBps,
+        new BN(maxPriceAge),
+        priceDecimals,
+        fxPriceDecimals
+      )
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        market: marketPDA,
+        oracleFeed: oracleFeed,
+        baseCurrency: baseCurrency,
+        fxFeed,
+        admin: user.publicKey,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .signers([user])
+      .rpc();
+
+    console.log("Market listing tx:", tx);
+    return tx;
+  }
+
+  // List a pair market priced as oracleFeed / denominatorFeed
+  async listPairMarket(
+    symbol: string,
+    decimals: number,
+    maxLeverage: number,
+    oracleFeed: PublicKey,
+    denominatorFeed: PublicKey,
+    baseCurrency: PublicKey,
+    user: Keypair,
+    borrowRateBps: number = 0,
+    maxPriceAge: number = 0,
+    priceDecimals: number = 6,
+    denominatorPriceDecimals: number = 6,
+    fxFeed: PublicKey | null = null,
+    fxPriceDecimals: number = 6
+  ): Promise<{ marketPDA: PublicKey; tx: string }> {
+    const symbolBuffer = Array.from(Buffer.from(symbol.padEnd(16, "\0")));
+    const marketPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("market"), oracleFeed.toBuffer(), denominatorFeed.toBuffer()],
+      this.program.programId
+    )[0];
+
+    const tx = await this.program.methods
+      .listPairMarket(
+        symbolBuffer,
+        decimals,
+        maxLeverage,
+        borrowRateBps,
+        new BN(maxPriceAge),
+        priceDecimals,
+        denominatorPriceDecimals,
+        fxPriceDecimals
+      )
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        market: marketPDA,
+        oracleFeed,
+        denominatorFeed,
+        baseCurrency,
+        fxFeed,
+        admin: user.publicKey,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .signers([user])
+      .rpc();
+
+    console.log("Pair market listing tx:", tx);
+    return { marketPDA, tx };
+  }
+
+  // List a basket market priced as the weighted sum of its constituent feeds
+  async listBasketMarket(
+    symbol: string,
+    decimals: number,
+    maxLeverage: number,
+    feeds: PublicKey[],
+    weights: number[],
+    baseCurrency: PublicKey,
+    user: Keypair,
+    borrowRateBps: number = 0,
+    maxPriceAge: number = 0,
+    priceDecimals: number[] = feeds.map(() => 6), // per constituent feed
+    fxFeed: PublicKey | null = null,
+    fxPriceDecimals: number = 6
+  ): Promise<{ basketPDA: PublicKey; marketPDA: PublicKey; tx: string }> {
+    const symbolBuffer = Array.from(Buffer.from(symbol.padEnd(16, "\0")));
+    const basketPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("basket"), Buffer.from(symbolBuffer)],
+      this.program.programId
+    )[0];
+    const marketPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("market"), basketPDA.toBuffer()],
+      this.program.programId
+    )[0];
+
+    const tx = await this.program.methods
+      .listBasketMarket(
+        symbolBuffer,
+        decimals,
+        maxLeverage,
+        borrowRateBps,
+        new BN(maxPriceAge),
+        fxPriceDecimals,
+        feeds,
+        weights.map((weight) => new BN(weight)),
+        priceDecimals
+      )
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        basket: basketPDA,
+        market: marketPDA,
+        baseCurrency,
+        fxFeed,
+        admin: user.publicKey,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .signers([user])
+      .rpc();
+
+    console.log("Basket market listing tx:", tx);
+    return { basketPDA, marketPDA, tx };
+  }
+
+  // Rebalance a basket, the new weights are rescaled to the current index level
+  async setBasketWeights(
+    basketPDA: PublicKey,
+    marketPDA: PublicKey,
+    currentFeeds: PublicKey[],
+    feeds: PublicKey[],
+    weights: number[],
+    admin: Keypair,
+    priceDecimals: number[] = feeds.map(() => 6) // per constituent feed
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .setBasketWeights(
+        feeds,
+        weights.map((weight) => new BN(weight)),
+        priceDecimals
+      )
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        basket: basketPDA,
+        market: marketPDA,
+        admin: admin.publicKey,
+      } as any)
+      .remainingAccounts(
+        [...currentFeeds, ...feeds].map((pubkey) => ({ pubkey, isWritable: false, isSigner: false }))
+      )
+      .signers([admin])
+      .rpc();
+
+    console.log("Set basket weights tx:", tx);
+    return tx;
+  }
+
+  // Record a split or dividend on a market
+  async recordCorporateAction(
+    marketPDA: PublicKey,
+    kind:
+      | { split: { numerator: number; denominator: number } }
+      | { dividend: { amountPerUnit: BN } },
+    admin: Keypair
+  ): Promise<{ corporateActionPDA: PublicKey; tx: string }> {
+    const market = await this.program.account.market.fetch(marketPDA);
+    const corporateActionPDA = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("corporate_action"),
+        marketPDA.toBuffer(),
+        new BN(market.corporateActionCount).toArrayLike(Buffer, "le", 4),
+      ],
+      this.program.programId
+    )[0];
+
+    const tx = await this.program.methods
+      .recordCorporateAction(kind)
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        market: marketPDA,
+        corporateAction: corporateActionPDA,
+        admin: admin.publicKey,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Record corporate action tx:", tx);
+    return { corporateActionPDA, tx };
+  }
+
+  // Apply a position's next pending corporate action
+  async applyCorporateAction(
+    signer: Keypair,
+    participantPDA: PublicKey,
+    positionPDA: PublicKey
+  ): Promise<string> {
+    const position = await this.program.account.position.fetch(positionPDA);
+    const corporateActionPDA = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("corporate_action"),
+        position.market.toBuffer(),
+        new BN(position.corporateActionsApplied).toArrayLike(Buffer, "le", 4),
+      ],
+      this.program.programId
+    )[0];
+
+    const tx = await this.program.methods
+      .applyCorporateAction()
+      .accounts({
+        signer: signer.publicKey,
+        participant: participantPDA,
+        position: positionPDA,
+        market: position.market,
+        corporateAction: corporateActionPDA,
+      } as any)
+      .signers([signer])
+      .rpc();
+
+    console.log("✅ Apply corporate action tx:", tx);
+    return tx;
+  }
+
+  // Set a market's hourly borrow rate
+  async setBorrowRate(
+    marketPDA: PublicKey,
+    borrowRateBps: number,
+    admin: Keypair
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .setBorrowRate(borrowRateBps)
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        market: marketPDA,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Set borrow rate tx:", tx);
+    return tx;
+  }
+
+  // Set or clear a market's fallback feed and the allowed divergence from the primary
+  async setFallbackFeed(
+    marketPDA: PublicKey,
+    fallbackFeed: PublicKey | null,
+    maxDivergenceBps: number,
+    admin: Keypair,
+    fallbackPriceDecimals: number = 6
+  ): Promise<string> {
+    const market = await this.program.account.market.fetch(marketPDA);
+    const tx = await this.program.methods
+      .setFallbackFeed(fallbackFeed, maxDivergenceBps, fallbackPriceDecimals)
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        market: marketPDA,
+        oracleFeed: market.oracleFeed,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Set fallback feed tx:", tx);
+    return tx;
+  }
+
+  // Configure a market's volatility halt, a threshold of 0 disables it
+  async setHaltConfig(
+    marketPDA: PublicKey,
+    haltThresholdBps: number,
+    haltWindow: number,
+    haltCooldown: number,
+    admin: Keypair
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .setHaltConfig(haltThresholdBps, new BN(haltWindow), new BN(haltCooldown))
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        market: marketPDA,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Set halt config tx:", tx);
+    return tx;
+  }
+
+  // Record the market price, halting the market on a large move
+  async observeMarketPrice(
+    signer: Keypair,
+    marketPDA: PublicKey,
+    oracleFeed: PublicKey,
+    denominatorFeed: PublicKey | null = null,
+    constituentFeeds: PublicKey[] = [],
+    fallbackFeed: PublicKey | null = null
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .observeMarketPrice()
+      .accounts({
+        signer: signer.publicKey,
+        market: marketPDA,
+        oracleFeed,
+        denominatorFeed,
+        fallbackFeed,
+      } as any)
+      .remainingAccounts(
+        constituentFeeds.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false }))
+      )
+      .signers([signer])
+      .rpc();
+
+    console.log("Observe market price tx:", tx);
+    return tx;
+  }
+
+  // Lift a market halt before its cool-down ends
+  async resumeMarket(marketPDA: PublicKey, admin: Keypair): Promise<string> {
+    const tx = await this.program.methods
+      .resumeMarket()
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        market: marketPDA,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Resume market tx:", tx);
+    return tx;
+  }
+
+  // Set the token and amount league creators bond to list a market
+  async setListingBond(mint: PublicKey, amount: number, admin: Keypair): Promise<string> {
+    const tx = await this.program.methods
+      .setListingBond(mint, new BN(amount))
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        admin: admin.publicKey,
+      } as any)
+      .signers([admin])
+      .rpc();
+
+    console.log("Set listing bond tx:", tx);
+    return tx;
+  }
+
+  // List a market as a league creator by posting the listing bond
+  async listCommunityMarket(
+    symbol: string,
+    decimals: number,
+    maxLeverage: number,
+    oracleFeed: PublicKey,
+    baseCurrency: PublicKey,
+    bondMint: PublicKey,
+    creator: Keypair,
+    leaguePDA: PublicKey, // any league the creator created
+    borrowRateBps: number = 0,
+    maxPriceAge: number = 0,
+    priceDecimals: number = 6
+  ): Promise<{ marketPDA: PublicKey; listingBondPDA: PublicKey; tx: string }> {
+    const symbolBuffer = Array.from(Buffer.from(symbol.padEnd(16, "\0")));
+    const marketPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("community_market"), creator.publicKey.toBuffer(), oracleFeed.toBuffer()],
+      this.program.programId
+    )[0];
+    const listingBondPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("listing_bond"), marketPDA.toBuffer()],
+      this.program.programId
+    )[0];
+
+    const tx = await this.program.methods
+      .listCommunityMarket(
+        symbolBuffer,
+        decimals,
+        maxLeverage,
+        borrowRateBps,
+        new BN(maxPriceAge),
+        priceDecimals
+      )
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        league: leaguePDA,
+        market: marketPDA,
+        listingBond: listingBondPDA,
+        oracleFeed,
+        baseCurrency,
+        bondMint,
+        creatorBondAta: await getAssociatedTokenAddress(bondMint, creator.publicKey),
+        bondVault: await getAssociatedTokenAddress(bondMint, listingBondPDA, true),
+        creator: creator.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: new PublicKey(
+          "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"
+        ),
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .signers([creator])
+      .rpc();
+
+    console.log("Community market listing tx:", tx);
+    return { marketPDA, listingBondPDA, tx };
+  }
+
+  // Open a creator-listed market to every league, returning the bond
+  async promoteMarket(
+    marketPDA: PublicKey,
+    bondMint: PublicKey,
+    creator: PublicKey,
+    admin: Keypair
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .promoteMarket()
+      .accounts(await this.listingBondAccounts(marketPDA, bondMint, creator, admin))
+      .signers([admin])
+      .rpc();
+
+    console.log("Promote market tx:", tx);
+    return tx;
+  }
+
+  // Delist a creator-listed market, slashing the bond to the treasury or returning it
+  async delistCommunityMarket(
+    marketPDA: PublicKey,
+    bondMint: PublicKey,
+    recipient: PublicKey, // treasury when slashing, the creator otherwise
+    slash: boolean,
+    admin: Keypair
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .delistCommunityMarket(slash)
+      .accounts(await this.listingBondAccounts(marketPDA, bondMint, recipient, admin))
+      .signers([admin])
+      .rpc();
+
+    console.log("Delist community market tx:", tx);
+    return tx;
+  }
+
+  private async listingBondAccounts(
+    marketPDA: PublicKey,
+    bondMint: PublicKey,
+    recipient: PublicKey,
+    admin: Keypair
+  ): Promise<any> {
+    const listingBondPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("listing_bond"), marketPDA.toBuffer()],
+      this.program.programId
+    )[0];
+    return {
+      globalState: this.pdas.globalStatePDA!,
+      market: marketPDA,
+      listingBond: listingBondPDA,
+      bondVault: await getAssociatedTokenAddress(bondMint, listingBondPDA, true),
+      recipientAta: await getAssociatedTokenAddress(bondMint, recipient, true),
+      admin: admin.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+  }
+
+  // Create a league
+  async createLeague(
+    creator: Keypair,
+    startTs: number,
+    endTs: number,
+    entryAmount: number,
+    markets: PublicKey[],
+    leaderboardPDA: PublicKey,
+    metadataUri: string,
+    maxParticipants: number,
+    virtualOnDeposit: number,
+    maxLeverage: number,
+    nonce: number,
+    k: number = 50,
+    marginMode: { cross: {} } | { isolated: {} } = { cross: {} },
+    marginModeSelectable: boolean = false,
+    positionMode: { hedge: {} } | { oneWay: {} } = { hedge: {} },
+    maxOpenPositions: number = 10,
+    leagueType: { perp: {} } | { spot: {} } = { perp: {} }
+  ): Promise<{ leaguePDA: PublicKey; tx: string }> {
+    const leaguePDA = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("league"),
+        creator.publicKey.toBuffer(),
+        Buffer.from([nonce]),
+      ],
+      this.program.programId
+    )[0];
+
+    const rewardVaultPDA = await this.getRewardVaultATA(leaguePDA);
+
+    const tx = await this.program.methods
+      .createLeague(
+        new BN(startTs),
+        new BN(endTs),
+        new BN(entryAmount),
+        markets,
+        metadataUri,
+        maxParticipants,
+        new BN(virtualOnDeposit),
+        maxLeverage,
+        nonce,
+        k,
+        marginMode,
+        marginModeSelectable,
+        positionMode,
+        maxOpenPositions,
+        leagueType
+      )
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        creator: creator.publicKey,
+        league: leaguePDA,
+        entryTokenMint: this.accounts.entryTokenMint,
+        rewardVault: rewardVaultPDA,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: new PublicKey(
+          "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"
+        ),
+        leaderboard: leaderboardPDA,
+      } as any)
+      .signers([creator])
+      .rpc();
+
+    console.log("✅ League creation tx:", tx);
+    return { leaguePDA, tx };
+  }
+
+  // Start a league
+  async startLeague(leaguePDA: PublicKey, leaderboardPDA: PublicKey, user: Keypair): Promise<string> {
+    console.log("League PDA:", leaguePDA.toString());
+    const tx = await this.program.methods
+      .startLeague()
+      .accounts({
+        league: leaguePDA,
+        leaderboard: leaderboardPDA,
+        user: user.publicKey,
+        tdfProgram: this.program.programId,
+      } as any)
+      .remainingAccounts([
+        {
+          pubkey: this.accounts.ERProgram,
+          isWritable: false,
+          isSigner: false,
+        },
+      ])
+      .signers([user])
+      .rpc();
+
+    console.log("✅ Start league tx:", tx);
+    return tx;
+  }
+
+  // Join a league
+  async joinLeague(
+    user: Keypair,
+    leaguePDA: PublicKey,
+    participantPDA: PublicKey,
+    amount: number
+  ): Promise<string> {
+    const rewardVaultAta = await this.getRewardVaultATA(leaguePDA);
+
+    // Get existing user token account instead of creating/minting new tokens
+    const userTokenAccount = await getAssociatedTokenAddress(
+      this.accounts.entryTokenMint,
+      user.publicKey
+    );
+
+    const tx = await this.program.methods
+      .joinLeague(new BN(amount))
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        user: user.publicKey,
+        league: leaguePDA,
+        participant: participantPDA,
+        rewardVault: rewardVaultAta,
+        entryTokenMint: this.accounts.entryTokenMint,
+        userEntryAta: userTokenAccount,
+        vaultEntryAta: rewardVaultAta,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .signers([user])
+      .rpc();
+
+    console.log("✅ Join league tx:", tx);
+    return tx;
+  }
+
+  // Pick the participant's margin mode, if the league allows it
+  async setMarginMode(
+    user: Keypair,
+    leaguePDA: PublicKey,
+    participantPDA: PublicKey,
+    marginMode: { cross: {} } | { isolated: {} }
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .setMarginMode(marginMode)
+      .accounts({
+        user: user.publicKey,
+        league: leaguePDA,
+        participant: participantPDA,
+      } as any)
+      .signers([user])
+      .rpc();
+
+    console.log("✅ Set margin mode tx:", tx);
+    return tx;
+  }
+
+  // Close a league
+  async closeLeague(leaguePDA: PublicKey, user: Keypair, rewardVaultAta: PublicKey): Promise<string> {
+    const tx = await this.program.methods
+      .closeLeague()
+      .accounts({
+        league: leaguePDA,
+        user: user.publicKey,
+        rewardVault: rewardVaultAta,
+      } as any)
+      .signers([user])
+      .rpc();
+
+    console.log("✅ Close league tx:", tx);
+    return tx;
+  }
+
+  // Open a position
+  async openPosition(
+    user: Keypair,
+    leaguePDA: PublicKey,
+    marketPDA: PublicKey,
+    oracleFeed: PublicKey,
+    participantPDA: PublicKey,
+    positionPDA: PublicKey,
+    direction: { long: {} } | { short: {} },
+    size: number,
+    leverage: number,
+    seqNum: number,
+    acceptablePrice: number | null = null,
+    openPositionPDAs: PublicKey[] = [], // required in one-way leagues
+    denominatorFeed: PublicKey | null = null, // pair markets only
+    constituentFeeds: PublicKey[] = [], // basket markets only
+    fxFeed: PublicKey | null = null, // markets quoted in another currency only
+    fallbackFeed: PublicKey | null = null // markets with a fallback feed only
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .openPosition(
+        direction,
+        new BN(size),
+        leverage,
+        new BN(seqNum),
+        acceptablePrice === null ? null : new BN(acceptablePrice)
+      )
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        user: user.publicKey,
+        league: leaguePDA,
+        market: marketPDA,
+        participant: participantPDA,
+        position: positionPDA,
+        oracleFeed: oracleFeed,
+        denominatorFeed,
+        fxFeed,
+        fallbackFeed,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .remainingAccounts(
+        [...openPositionPDAs, ...constituentFeeds].map((pubkey) => ({
+          pubkey,
+          isWritable: false,
+          isSigner: false,
+        }))
+      )
+      .signers([user])
+      .rpc();
+
+    console.log("✅ Open position tx:", tx);
+    return tx;
+  }
+
+  // Increase a position size
+  async increasePositionSize(
+    user: Keypair,
+    leaguePDA: PublicKey,
+    marketPDA: PublicKey,
+    oracleFeed: PublicKey,
+    participantPDA: PublicKey,
+    positionPDA: PublicKey,
+    size: number,
+    acceptablePrice: number | null = null,
+    denominatorFeed: PublicKey | null = null,
+    constituentFeeds: PublicKey[] = [],
+    fxFeed: PublicKey | null = null,
+    fallbackFeed: PublicKey | null = null
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .increasePositionSize(
+        new BN(size),
+        acceptablePrice === null ? null : new BN(acceptablePrice)
+      )
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        user: user.publicKey,
+        league: leaguePDA,
+        market: marketPDA,
+        participant: participantPDA,
+        position: positionPDA,
+        oracleFeed: oracleFeed,
+        denominatorFeed,
+        fxFeed,
+        fallbackFeed,
+      } as any)
+      .remainingAccounts(
+        constituentFeeds.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false }))
+      )
+      .signers([user])
+      .rpc();
+
+    console.log("✅ Update position tx:", tx);
+    return tx;
+  }
+
+  // Decrease a position size
+  async decreasePositionSize(
+    user: Keypair,
+    leaguePDA: PublicKey,
+    oracleFeed: PublicKey,
+    participantPDA: PublicKey,
+    positionPDA: PublicKey,
+    sizeToClose: number,
+    acceptablePrice: number | null = null,
+    denominatorFeed: PublicKey | null = null,
+    constituentFeeds: PublicKey[] = [],
+    fxFeed: PublicKey | null = null,
+    fallbackFeed: PublicKey | null = null
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .decreasePositionSize(
+        new BN(sizeToClose),
+        acceptablePrice === null ? null : new BN(acceptablePrice)
+      )
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        user: user.publicKey,
+        league: leaguePDA,
+        participant: participantPDA,
+        position: positionPDA,
+        market: this.pdas.marketPDA!,
+        oracleFeed: oracleFeed,
+        denominatorFeed,
+        fxFeed,
+        fallbackFeed,
+      } as any)
+      .remainingAccounts(
+        constituentFeeds.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false }))
+      )
+      .signers([user])
+      .rpc();
+
+    console.log("✅ Close position tx:", tx);
+    return tx;
+  }
+
+  // Execute several order legs atomically, margin is checked once at the end
+  async executeOrders(
+    user: Keypair,
+    leaguePDA: PublicKey,
+    participantPDA: PublicKey,
+    legs: {
+      kind: any; // { open: { direction, leverage } } | { increase: {} } | { decrease: {} }
+      size: number;
+      acceptablePrice?: number;
+      positionPDA: PublicKey;
+      marketPDA: PublicKey;
+      oracleFeed: PublicKey;
+      denominatorFeed?: PublicKey; // pair markets only
+      constituentFeeds?: PublicKey[]; // basket markets only
+      fxFeed?: PublicKey; // markets quoted in another currency only
+      fallbackFeed?: PublicKey; // markets with a fallback feed only
+    }[]
+  ): Promise<string> {
+    const remainingAccounts = [];
+    for (const leg of legs) {
+      remainingAccounts.push({ pubkey: leg.positionPDA, isWritable: true, isSigner: false });
+      remainingAccounts.push({ pubkey: leg.marketPDA, isWritable: false, isSigner: false });
+      remainingAccounts.push({ pubkey: leg.oracleFeed, isWritable: false, isSigner: false });
+      if (leg.denominatorFeed) {
+        remainingAccounts.push({ pubkey: leg.denominatorFeed, isWritable: false, isSigner: false });
+      }
+      if (leg.fallbackFeed) {
+        remainingAccounts.push({ pubkey: leg.fallbackFeed, isWritable: false, isSigner: false });
+      }
+      if (leg.fxFeed) {
+        remainingAccounts.push({ pubkey: leg.fxFeed, isWritable: false, isSigner: false });
+      }
+      for (const pubkey of leg.constituentFeeds ?? []) {
+        remainingAccounts.push({ pubkey, isWritable: false, isSigner: false });
+      }
+    }
+
+    const tx = await this.program.methods
+      .executeOrders(
+        legs.map((leg) => ({
+          kind: leg.kind,
+          size: new BN(leg.size),
+          acceptablePrice:
+            leg.acceptablePrice === undefined ? null : new BN(leg.acceptablePrice),
+        }))
+      )
+      .accounts({
+        globalState: this.pdas.globalStatePDA!,
+        user: user.publicKey,
+        league: leaguePDA,
+        participant: participantPDA,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .remainingAccounts(remainingAccounts)
+      .signers([user])
+      .rpc();
+
+    console.log("✅ Execute orders tx:", tx);
+    return tx;
+  }
+
+  // Derive the DCA schedule PDA of a participant on a market
+  dcaSchedulePDA(participantPDA: PublicKey, marketPDA: PublicKey, nonce: number): PublicKey {
+    const nonceBytes = Buffer.alloc(4);
+    nonceBytes.writeUInt32LE(nonce);
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("dca"), participantPDA.toBuffer(), marketPDA.toBuffer(), nonceBytes],
+      this.program.programId
+    )[0];
+  }
+
+  // Schedule recurring slices on a position, or on a new one opened by the first slice
+  async createDcaSchedule(
+    user: Keypair,
+    leaguePDA: PublicKey,
+    participantPDA: PublicKey,
+    marketPDA: PublicKey,
+    nonce: number,
+    positionPDA: PublicKey | null,
+    action: { increase: {} } | { decrease: {} },
+    open: { direction: { long: {} } | { short: {} }; leverage: number } | null,
+    sliceSize: number,
+    totalSlices: number,
+    interval: number,
+    startTs: number,
+    expiresAt: number = 0,
+    acceptablePrice: number | null = null
+  ): Promise<{ dcaSchedulePDA: PublicKey; tx: string }> {
+    const dcaSchedulePDA = this.dcaSchedulePDA(participantPDA, marketPDA, nonce);
+
+    const tx = await this.program.methods
+      .createDcaSchedule(
+        nonce,
+        action,
+        open,
+        new BN(sliceSize),
+        totalSlices,
+        new BN(interval),
+        new BN(startTs),
+        new BN(expiresAt),
+        acceptablePrice === null ? null : new BN(acceptablePrice)
+      )
+      .accounts({
+        user: user.publicKey,
+        league: leaguePDA,
+        participant: participantPDA,
+        market: marketPDA,
+        position: positionPDA,
+        dcaSchedule: dcaSchedulePDA,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .signers([user])
+      .rpc();
+
+    console.log("✅ Create DCA schedule tx:", tx);
+    return { dcaSchedulePDA, tx };
+  }
+
+  // Execute the next due DCA slice as a keeper
+  async executeDcaSlice(
+    keeper: Keypair,
+    user: PublicKey,
+    leaguePDA: PublicKey,
+    marketPDA: PublicKey,
+    oracleFeed: PublicKey,
+    participantPDA: PublicKey,
+    positionPDA: PublicKey,
+    dcaSchedulePDA: PublicKey,
+    denominatorFeed: PublicKey | null = null,
+    constituentFeeds: PublicKey[] = [],
+    fxFeed: PublicKey | null = null,
+    fallbackFeed: PublicKey | null = null
   ): Promise<string> {
-    const symbolBuffer = Array.from(Buffer.from(symbol.padEnd(16, "\0")));
-    const marketPDA = PublicKey.findProgramAddressSync(
-      [Buffer.from("market"), oracleFeed.toBuffer()],
-      this.program.programId
-    )[0];
-
     const tx = await this.program.methods
-      .listMarket(symbolBuffer, decimals, maxLeverage)
+      .executeDcaSlice()
       .accounts({
         globalState: this.pdas.globalStatePDA!,
+        keeper: keeper.publicKey,
+        user,
+        league: leaguePDA,
         market: marketPDA,
-        oracleFeed: oracleFeed,
-        baseCurrency: baseCurrency,
-        admin: user.publicKey,
+        oracleFeed,
+        denominatorFeed,
+        fxFeed,
+        fallbackFeed,
+        participant: participantPDA,
+        position: positionPDA,
+        dcaSchedule: dcaSchedulePDA,
         systemProgram: SystemProgram.programId,
       } as any)
+      .remainingAccounts(
+        constituentFeeds.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false }))
+      )
+      .signers([keeper])
+      .rpc();
+
+    console.log("✅ Execute DCA slice tx:", tx);
+    return tx;
+  }
+
+  // Cancel a DCA schedule and reclaim its rent
+  async cancelDcaSchedule(user: Keypair, dcaSchedulePDA: PublicKey): Promise<string> {
+    const tx = await this.program.methods
+      .cancelDcaSchedule()
+      .accounts({
+        user: user.publicKey,
+        dcaSchedule: dcaSchedulePDA,
+      } as any)
       .signers([user])
       .rpc();
 
-    console.log("Market listing tx:", tx);
+    console.log("✅ Cancel DCA schedule tx:", tx);
     return tx;
   }
 
-  // Create a league
-  async createLeague(
+  // Offer a binary up/down contract on a league market
+  async createBinaryInstrument(
     creator: Keypair,
-    startTs: number,
-    endTs: number,
-    entryAmount: number,
-    markets: PublicKey[],
-    leaderboardPDA: PublicKey,
-    metadataUri: string,
-    maxParticipants: number,
-    virtualOnDeposit: number,
-    maxLeverage: number,
-    nonce: number,
-    k: number = 50
-  ): Promise<{ leaguePDA: PublicKey; tx: string }> {
-    const leaguePDA = PublicKey.findProgramAddressSync(
+    leaguePDA: PublicKey,
+    marketPDA: PublicKey,
+    strikePrice: number,
+    expiryTs: number
+  ): Promise<{ instrumentPDA: PublicKey; tx: string }> {
+    const instrumentPDA = PublicKey.findProgramAddressSync(
       [
-        Buffer.from("league"),
-        creator.publicKey.toBuffer(),
-        Buffer.from([nonce]),
+        Buffer.from("binary"),
+        leaguePDA.toBuffer(),
+        marketPDA.toBuffer(),
+        new BN(strikePrice).toArrayLike(Buffer, "le", 8),
+        new BN(expiryTs).toArrayLike(Buffer, "le", 8),
       ],
       this.program.programId
     )[0];
 
-    const rewardVaultPDA = await this.getRewardVaultATA(leaguePDA);
-
     const tx = await this.program.methods
-      .createLeague(
-        new BN(startTs),
-        new BN(endTs),
-        new BN(entryAmount),
-        markets,
-        metadataUri,
-        maxParticipants,
-        new BN(virtualOnDeposit),
-        maxLeverage,
-        nonce,
-        k
-      )
+      .createBinaryInstrument(new BN(strikePrice), new BN(expiryTs))
       .accounts({
         creator: creator.publicKey,
         league: leaguePDA,
-        entryTokenMint: this.accounts.entryTokenMint,
-        rewardVault: rewardVaultPDA,
+        market: marketPDA,
+        instrument: instrumentPDA,
         systemProgram: SystemProgram.programId,
-        tokenProgram: TOKEN_PROGRAM_ID,
-        associatedTokenProgram: new PublicKey(
-          "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"
-        ),
-        leaderboard: leaderboardPDA,
       } as any)
       .signers([creator])
       .rpc();
 
-    console.log("✅ League creation tx:", tx);
-    return { leaguePDA, tx };
-  }
-
-  // Start a league
-  async startLeague(leaguePDA: PublicKey, leaderboardPDA: PublicKey, user: Keypair): Promise<string> {
-    console.log("League PDA:", leaguePDA.toString());
-    const tx = await this.program.methods
-      .startLeague()
-      .accounts({
-        league: leaguePDA,
-        leaderboard: leaderboardPDA,
-        user: user.publicKey,
-        tdfProgram: this.program.programId,
-      } as any)
-      .remainingAccounts([
-        {
-          pubkey: this.accounts.ERProgram,
-          isWritable: false,
-          isSigner: false,
-        },
-      ])
-      .signers([user])
-      .rpc();
-
-    console.log("✅ Start league tx:", tx);
-    return tx;
+    console.log("✅ Create binary instrument tx:", tx);
+    return { instrumentPDA, tx };
   }
 
-  // Join a league
-  async joinLeague(
+  // Stake virtual balance on one side of a binary instrument
+  async stakeBinary(
     user: Keypair,
     leaguePDA: PublicKey,
     participantPDA: PublicKey,
+    instrumentPDA: PublicKey,
+    side: { up: {} } | { down: {} },
     amount: number
-  ): Promise<string> {
-    const rewardVaultAta = await this.getRewardVaultATA(leaguePDA);
-
-    // Get existing user token account instead of creating/minting new tokens
-    const userTokenAccount = await getAssociatedTokenAddress(
-      this.accounts.entryTokenMint,
-      user.publicKey
-    );
+  ): Promise<{ stakePDA: PublicKey; tx: string }> {
+    const stakePDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("binary_stake"), instrumentPDA.toBuffer(), user.publicKey.toBuffer()],
+      this.program.programId
+    )[0];
 
     const tx = await this.program.methods
-      .joinLeague(new BN(amount))
+      .stakeBinary(side, new BN(amount))
       .accounts({
+        globalState: this.pdas.globalStatePDA!,
         user: user.publicKey,
         league: leaguePDA,
         participant: participantPDA,
-        rewardVault: rewardVaultAta,
-        entryTokenMint: this.accounts.entryTokenMint,
-        userEntryAta: userTokenAccount,
-        vaultEntryAta: rewardVaultAta,
-        tokenProgram: TOKEN_PROGRAM_ID,
+        instrument: instrumentPDA,
+        stake: stakePDA,
         systemProgram: SystemProgram.programId,
       } as any)
       .signers([user])
       .rpc();
 
-    console.log("✅ Join league tx:", tx);
+    console.log("✅ Stake binary tx:", tx);
+    return { stakePDA, tx };
+  }
+
+  // Settle an expired binary instrument against its market price
+  async settleBinaryInstrument(
+    signer: Keypair,
+    instrumentPDA: PublicKey,
+    oracleFeed: PublicKey,
+    denominatorFeed: PublicKey | null = null,
+    constituentFeeds: PublicKey[] = [],
+    fallbackFeed: PublicKey | null = null
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .settleBinaryInstrument()
+      .accounts({
+        signer: signer.publicKey,
+        instrument: instrumentPDA,
+        oracleFeed,
+        denominatorFeed,
+        fallbackFeed,
+      } as any)
+      .remainingAccounts(
+        constituentFeeds.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false }))
+      )
+      .signers([signer])
+      .rpc();
+
+    console.log("✅ Settle binary instrument tx:", tx);
     return tx;
   }
 
-  // Close a league
-  async closeLeague(leaguePDA: PublicKey, user: Keypair, rewardVaultAta: PublicKey): Promise<string> {
+  // Pay out a participant's stake on a settled binary instrument
+  async settleBinaryStake(
+    signer: Keypair,
+    participantPDA: PublicKey,
+    instrumentPDA: PublicKey,
+    stakePDA: PublicKey
+  ): Promise<string> {
     const tx = await this.program.methods
-      .closeLeague()
+      .settleBinaryStake()
       .accounts({
-        league: leaguePDA,
-        user: user.publicKey,
-        rewardVault: rewardVaultAta,
+        signer: signer.publicKey,
+        participant: participantPDA,
+        instrument: instrumentPDA,
+        stake: stakePDA,
       } as any)
-      .signers([user])
+      .signers([signer])
       .rpc();
 
-    console.log("✅ Close league tx:", tx);
+    console.log("✅ Settle binary stake tx:", tx);
     return tx;
   }
 
-  // Open a position
-  async openPosition(
+  // Net an order against the existing position in a one-way league
+  async netPosition(
     user: Keypair,
     leaguePDA: PublicKey,
     marketPDA: PublicKey,
@@ -206,77 +1261,142 @@ export class TestHelpers {
     positionPDA: PublicKey,
     direction: { long: {} } | { short: {} },
     size: number,
-    leverage: number,
-    seqNum: number
+    acceptablePrice: number | null = null,
+    denominatorFeed: PublicKey | null = null,
+    constituentFeeds: PublicKey[] = [],
+    fxFeed: PublicKey | null = null,
+    fallbackFeed: PublicKey | null = null
   ): Promise<string> {
     const tx = await this.program.methods
-      .openPosition(direction, new BN(size), leverage, new BN(seqNum))
+      .netPosition(
+        direction,
+        new BN(size),
+        acceptablePrice === null ? null : new BN(acceptablePrice)
+      )
       .accounts({
+        globalState: this.pdas.globalStatePDA!,
         user: user.publicKey,
         league: leaguePDA,
         market: marketPDA,
         participant: participantPDA,
         position: positionPDA,
         oracleFeed: oracleFeed,
-        systemProgram: SystemProgram.programId,
+        denominatorFeed,
+        fxFeed,
+        fallbackFeed,
       } as any)
+      .remainingAccounts(
+        constituentFeeds.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false }))
+      )
       .signers([user])
       .rpc();
 
-    console.log("✅ Open position tx:", tx);
+    console.log("✅ Net position tx:", tx);
     return tx;
   }
 
-  // Increase a position size
-  async increasePositionSize(
+  // Close a fully closed position account and reclaim its rent
+  async closePositionAccount(
+    user: Keypair,
+    leaguePDA: PublicKey,
+    participantPDA: PublicKey,
+    positionPDA: PublicKey
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .closePositionAccount()
+      .accounts({
+        user: user.publicKey,
+        league: leaguePDA,
+        participant: participantPDA,
+        position: positionPDA,
+      } as any)
+      .signers([user])
+      .rpc();
+
+    console.log("✅ Close position account tx:", tx);
+    return tx;
+  }
+
+  // Read the liquidation price estimates of a position
+  async getLiquidationPrice(
+    participantPDA: PublicKey,
+    positionPDA: PublicKey
+  ): Promise<{ isolated: number; cross: number }> {
+    const prices = await this.program.methods
+      .getLiquidationPrice()
+      .accounts({
+        participant: participantPDA,
+        position: positionPDA,
+      } as any)
+      .view();
+
+    return {
+      isolated: prices.isolated.toNumber(),
+      cross: prices.cross.toNumber(),
+    };
+  }
+
+  // Add margin to a position
+  async addMargin(
     user: Keypair,
     leaguePDA: PublicKey,
     marketPDA: PublicKey,
-    oracleFeed: PublicKey,
     participantPDA: PublicKey,
     positionPDA: PublicKey,
-    size: number
+    amount: number
   ): Promise<string> {
     const tx = await this.program.methods
-      .increasePositionSize(new BN(size))
+      .addMargin(new BN(amount))
       .accounts({
+        globalState: this.pdas.globalStatePDA!,
         user: user.publicKey,
         league: leaguePDA,
         market: marketPDA,
         participant: participantPDA,
         position: positionPDA,
-        oracleFeed: oracleFeed,
       } as any)
       .signers([user])
       .rpc();
 
-    console.log("✅ Update position tx:", tx);
+    console.log("✅ Add margin tx:", tx);
     return tx;
   }
 
-  // Decrease a position size
-  async decreasePositionSize(
+  // Remove margin from a position, marked at the current oracle price
+  async removeMargin(
     user: Keypair,
     leaguePDA: PublicKey,
+    marketPDA: PublicKey,
     oracleFeed: PublicKey,
     participantPDA: PublicKey,
     positionPDA: PublicKey,
-    sizeToClose: number
+    amount: number,
+    denominatorFeed: PublicKey | null = null,
+    constituentFeeds: PublicKey[] = [],
+    fxFeed: PublicKey | null = null,
+    fallbackFeed: PublicKey | null = null
   ): Promise<string> {
     const tx = await this.program.methods
-      .decreasePositionSize(new BN(sizeToClose))
+      .removeMargin(new BN(amount))
       .accounts({
+        globalState: this.pdas.globalStatePDA!,
         user: user.publicKey,
         league: leaguePDA,
+        market: marketPDA,
         participant: participantPDA,
         position: positionPDA,
-        market: this.pdas.marketPDA!,
-        oracleFeed: oracleFeed,
+        oracleFeed,
+        denominatorFeed,
+        fxFeed,
+        fallbackFeed,
       } as any)
+      .remainingAccounts(
+        constituentFeeds.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false }))
+      )
       .signers([user])
       .rpc();
 
-    console.log("✅ Close position tx:", tx);
+    console.log("✅ Remove margin tx:", tx);
     return tx;
   }
 
@@ -289,6 +1409,103 @@ export class TestHelpers {
     );
   }
 
+  // List the market on the shared oracle feed once, later suites trade the same market
+  async ensureMarketListed(maxLeverage: number = 20): Promise<PublicKey> {
+    const marketPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("market"), this.pdas.priceFeedPDA!.toBuffer()],
+      this.program.programId
+    )[0];
+    if (!(await getProvider().connection.getAccountInfo(marketPDA))) {
+      await this.listMarket(
+        "SOL/USDC",
+        6,
+        maxLeverage,
+        this.pdas.priceFeedPDA!,
+        this.accounts.baseCurrency.publicKey,
+        this.accounts.admin
+      );
+    }
+    this.pdas.marketPDA = marketPDA;
+    return marketPDA;
+  }
+
+  // Create and start a league on the shared market and join it with every user
+  async setupLeague(
+    creator: Keypair,
+    nonce: number,
+    users: Keypair[],
+    options: {
+      virtualOnDeposit?: number;
+      maxLeverage?: number;
+      marginMode?: { cross: {} } | { isolated: {} };
+      marginModeSelectable?: boolean;
+      positionMode?: { hedge: {} } | { oneWay: {} };
+      maxOpenPositions?: number;
+      leagueType?: { perp: {} } | { spot: {} };
+    } = {}
+  ): Promise<{
+    leaguePDA: PublicKey;
+    leaderboardPDA: PublicKey;
+    participantPDAs: PublicKey[];
+  }> {
+    const marketPDA = await this.ensureMarketListed();
+    const leaguePDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("league"), creator.publicKey.toBuffer(), Buffer.from([nonce])],
+      this.program.programId
+    )[0];
+    const leaderboardPDA = PublicKey.findProgramAddressSync(
+      [Buffer.from("leaderboard"), leaguePDA.toBuffer()],
+      this.program.programId
+    )[0];
+
+    const startTs = Math.floor(Date.now() / 1000);
+    await this.createLeague(
+      creator,
+      startTs,
+      startTs + 86400,
+      1_000_000,
+      [marketPDA],
+      leaderboardPDA,
+      "https://example.com/metadata",
+      100,
+      options.virtualOnDeposit ?? 10_000_000_000,
+      options.maxLeverage ?? 20,
+      nonce,
+      50,
+      options.marginMode ?? { cross: {} },
+      options.marginModeSelectable ?? false,
+      options.positionMode ?? { hedge: {} },
+      options.maxOpenPositions ?? 10,
+      options.leagueType ?? { perp: {} }
+    );
+    await this.startLeague(leaguePDA, leaderboardPDA, creator);
+
+    const participantPDAs = [];
+    for (const user of users) {
+      await this.setupUserTokenAccount(user, 1_000_000);
+      const participantPDA = PublicKey.findProgramAddressSync(
+        [Buffer.from("participant"), leaguePDA.toBuffer(), user.publicKey.toBuffer()],
+        this.program.programId
+      )[0];
+      await this.joinLeague(user, leaguePDA, participantPDA, 1_000_000);
+      participantPDAs.push(participantPDA);
+    }
+
+    return { leaguePDA, leaderboardPDA, participantPDAs };
+  }
+
+  positionPDA(leaguePDA: PublicKey, user: PublicKey, seqNum: number): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("position"),
+        leaguePDA.toBuffer(),
+        user.toBuffer(),
+        new BN(seqNum).toArrayLike(Buffer, "le", 8),
+      ],
+      this.program.programId
+    )[0];
+  }
+
   private async setupUserTokenAccount(
     user: Keypair,
     amount: number
@@ -364,12 +1581,30 @@ export class TestHelpers {
     participantPDA: PublicKey,
     leaderboardPDA: PublicKey,
     positionPDAs: PublicKey[],
-    oracleFeedPDAs: PublicKey[]
+    marketPDAs: PublicKey[],
+    oracleFeedPDAs: PublicKey[],
+    denominatorFeedPDAs: (PublicKey | null)[] = [], // per position, pair markets only
+    constituentFeedPDAs: PublicKey[][] = [], // per position, basket markets only
+    fxFeedPDAs: (PublicKey | null)[] = [], // per position, markets with an fx feed only
+    fallbackFeedPDAs: (PublicKey | null)[] = [] // per position, markets with a fallback feed only
   ): Promise<string> {
     const remainingAccounts = [];
     for (let i = 0; i < positionPDAs.length; i++) {
-      remainingAccounts.push(positionPDAs[i]);
-      remainingAccounts.push(oracleFeedPDAs[i]);
+      remainingAccounts.push({ pubkey: positionPDAs[i], isWritable: true, isSigner: false });
+      remainingAccounts.push({ pubkey: marketPDAs[i], isWritable: false, isSigner: false });
+      remainingAccounts.push({ pubkey: oracleFeedPDAs[i], isWritable: false, isSigner: false });
+      if (denominatorFeedPDAs[i]) {
+        remainingAccounts.push({ pubkey: denominatorFeedPDAs[i], isWritable: false, isSigner: false });
+      }
+      if (fallbackFeedPDAs[i]) {
+        remainingAccounts.push({ pubkey: fallbackFeedPDAs[i], isWritable: false, isSigner: false });
+      }
+      if (fxFeedPDAs[i]) {
+        remainingAccounts.push({ pubkey: fxFeedPDAs[i], isWritable: false, isSigner: false });
+      }
+      for (const pubkey of constituentFeedPDAs[i] ?? []) {
+        remainingAccounts.push({ pubkey, isWritable: false, isSigner: false });
+      }
     }
 
     const tx = await this.program.methods
@@ -381,13 +1616,7 @@ export class TestHelpers {
         user: user.publicKey,
         league: leaguePDA,
       } as any)
-      .remainingAccounts(
-        remainingAccounts.map((acc, index) => ({
-          pubkey: acc,
-          isWritable: index % 2 === 0, // Position accounts (even indices) are writable, oracle accounts (odd indices) are not
-          isSigner: false,
-        }))
-      )
+      .remainingAccounts(remainingAccounts)
       .signers([user])
       .rpc();
 
@@ -407,6 +1636,7 @@ export class TestHelpers {
     const tx = await this.program.methods
       .claimReward()
       .accounts({
+        globalState: this.pdas.globalStatePDA!,
         league: leaguePDA,
         leaderboard: leaderboardPDA,
         participant: participantPDA,
@@ -421,6 +1651,52 @@ export class TestHelpers {
     console.log("✅ Claim reward tx:", tx);
     return tx;
   }
+
+  // Grow an account created by an older program version and backfill its new fields
+  async migrateAccount(
+    kind: "globalState" | "market" | "league" | "leaderboard" | "position",
+    account: PublicKey,
+    payer: Keypair
+  ): Promise<string> {
+    const methods = {
+      globalState: () => this.program.methods.migrateGlobalState(),
+      market: () => this.program.methods.migrateMarket(),
+      league: () => this.program.methods.migrateLeague(),
+      leaderboard: () => this.program.methods.migrateLeaderboard(),
+      position: () => this.program.methods.migratePosition(),
+    };
+    const tx = await methods[kind]()
+      .accounts({
+        account,
+        payer: payer.publicKey,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .signers([payer])
+      .rpc();
+
+    console.log(`Migrate ${kind} tx:`, tx);
+    return tx;
+  }
+
+  async migrateParticipant(
+    participantPDA: PublicKey,
+    leaguePDA: PublicKey,
+    payer: Keypair
+  ): Promise<string> {
+    const tx = await this.program.methods
+      .migrateParticipant()
+      .accounts({
+        participant: participantPDA,
+        league: leaguePDA,
+        payer: payer.publicKey,
+        systemProgram: SystemProgram.programId,
+      } as any)
+      .signers([payer])
+      .rpc();
+
+    console.log("Migrate participant tx:", tx);
+    return tx;
+  }
 }
 
 // Utility functions for test assertions